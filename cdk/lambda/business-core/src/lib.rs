@@ -2,5 +2,6 @@
 
 pub mod mvt;
 pub mod tables;
+pub mod tile_cache;
 pub mod types;
 pub mod web_mercator;
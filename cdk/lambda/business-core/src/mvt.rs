@@ -4,20 +4,93 @@
 
 use serde::Deserialize;
 
+use crate::types::GeolocationCoordinates;
+use crate::web_mercator::{
+    MAX_ZOOM,
+    normalized_x_from_longitude,
+    normalized_y_from_latitude,
+    tiles_per_edge_at_zoom,
+    x_from_longitude_at_zoom,
+    y_from_latitude_at_zoom,
+};
+
 /// Tile coordinates.
+///
+/// Always valid; i.e., the zoom level is at most [`MAX_ZOOM`], and x and y
+/// are within the range at the zoom level. Fields are private so that
+/// coordinates can only be made by [`TileCoordinates::new`] or the other
+/// constructors that keep the invariant.
+///
+/// Deserialization fails with [`TileCoordinatesError`] if the coordinates
+/// are invalid. See [`TileCoordinates::new`] for more details.
 #[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "UncheckedTileCoordinates")]
 pub struct TileCoordinates {
     /// Zoom level of the tile.
-    pub zoom: u32,
+    zoom: u32,
 
     /// X coordinate of the tile.
-    pub x: u32,
+    x: u32,
 
     /// Y coordinate of the tile.
-    pub y: u32,
+    y: u32,
 }
 
 impl TileCoordinates {
+    /// Returns the zoom level of the tile.
+    #[inline]
+    pub const fn zoom(&self) -> u32 {
+        self.zoom
+    }
+
+    /// Returns the x coordinate of the tile.
+    #[inline]
+    pub const fn x(&self) -> u32 {
+        self.x
+    }
+
+    /// Returns the y coordinate of the tile.
+    #[inline]
+    pub const fn y(&self) -> u32 {
+        self.y
+    }
+
+    /// Creates a new `TileCoordinates` with validation.
+    ///
+    /// May return the following error:
+    /// - [`TileCoordinatesError::InvalidZoom`]: if `zoom` is greater than
+    ///   [`MAX_ZOOM`]
+    /// - [`TileCoordinatesError::OutOfRange`]: if `x` or `y` is not less than
+    ///   `2^zoom`
+    pub fn new(zoom: u32, x: u32, y: u32) -> Result<Self, TileCoordinatesError> {
+        if zoom > MAX_ZOOM {
+            return Err(TileCoordinatesError::InvalidZoom(zoom));
+        }
+        let tiles_per_edge = 1u32 << zoom;
+        if x >= tiles_per_edge || y >= tiles_per_edge {
+            return Err(TileCoordinatesError::OutOfRange { zoom, x, y });
+        }
+        Ok(Self { zoom, x, y })
+    }
+
+    /// Creates a new `TileCoordinates` of the tile that contains a given
+    /// location at a given zoom level.
+    ///
+    /// Locations beyond the edges of the world are clamped into the world.
+    ///
+    /// Panics if `zoom` is greater than [`MAX_ZOOM`].
+    pub fn from_location(location: &GeolocationCoordinates, zoom: u32) -> Self {
+        let tiles_per_edge = tiles_per_edge_at_zoom(zoom);
+        let to_index = |normalized: f64| {
+            (tiles_per_edge * normalized).floor().clamp(0.0, tiles_per_edge - 1.0) as u32
+        };
+        Self {
+            zoom,
+            x: to_index(normalized_x_from_longitude(location.longitude)),
+            y: to_index(normalized_y_from_latitude(location.latitude)),
+        }
+    }
+
     /// Creates a new `TileCoordinates` that zooms out to a given level.
     ///
     /// Returns `None` if `new_zoom` is larger than the current zoom level.
@@ -35,12 +108,164 @@ impl TileCoordinates {
         }
         Some(Self { zoom, x, y })
     }
+
+    /// Returns all the tiles at a given zoom level that the tile covers.
+    ///
+    /// Tiles are ordered row by row from the top-left.
+    ///
+    /// Returns `None` if `new_zoom` is smaller than the current zoom level, or
+    /// greater than [`MAX_ZOOM`].
+    pub fn zoom_in_to(&self, new_zoom: u32) -> Option<Vec<Self>> {
+        if new_zoom < self.zoom || new_zoom > MAX_ZOOM {
+            return None;
+        }
+        let shift = new_zoom - self.zoom;
+        let tiles_per_edge = 1u32 << shift;
+        let x0 = self.x << shift;
+        let y0 = self.y << shift;
+        let tiles = (0..tiles_per_edge)
+            .flat_map(|dy| (0..tiles_per_edge).map(move |dx| Self {
+                zoom: new_zoom,
+                x: x0 + dx,
+                y: y0 + dy,
+            }))
+            .collect();
+        Some(tiles)
+    }
+
+    /// Returns if the tile contains a given location.
+    pub fn contains_location(&self, location: &GeolocationCoordinates) -> bool {
+        self.contains_location_with_margin(location, 0.0)
+    }
+
+    /// Returns if the tile expanded by a given margin contains a given
+    /// location.
+    ///
+    /// `margin` is the ratio to the edge length of the tile.
+    pub fn contains_location_with_margin(
+        &self,
+        location: &GeolocationCoordinates,
+        margin: f64,
+    ) -> bool {
+        let x = x_from_longitude_at_zoom(location.longitude, self.zoom);
+        let y = y_from_latitude_at_zoom(location.latitude, self.zoom);
+        let x_range = (self.x as f64 - margin)..(self.x as f64 + 1.0 + margin);
+        let y_range = (self.y as f64 - margin)..(self.y as f64 + 1.0 + margin);
+        x_range.contains(&x) && y_range.contains(&y)
+    }
+
+    /// Returns the tiles at a given zoom level that overlap the tile expanded
+    /// by a given margin.
+    ///
+    /// `margin` is the ratio to the edge length of the tile.
+    ///
+    /// Tiles are ordered row by row from the top-left.
+    /// Tiles beyond the edges of the world are excluded.
+    pub fn overlapping_tiles_at(&self, zoom: u32, margin: f64) -> Vec<Self> {
+        let scale = tiles_per_edge_at_zoom(zoom) / tiles_per_edge_at_zoom(self.zoom);
+        let max_index = tiles_per_edge_at_zoom(zoom) - 1.0;
+        let min_x = ((self.x as f64 - margin) * scale).floor().max(0.0) as u32;
+        let min_y = ((self.y as f64 - margin) * scale).floor().max(0.0) as u32;
+        let max_x = (((self.x as f64 + 1.0 + margin) * scale).ceil() - 1.0)
+            .min(max_index) as u32;
+        let max_y = (((self.y as f64 + 1.0 + margin) * scale).ceil() - 1.0)
+            .min(max_index) as u32;
+        (min_y..=max_y)
+            .flat_map(|y| (min_x..=max_x).map(move |x| Self { zoom, x, y }))
+            .collect()
+    }
+}
+
+/// Tile coordinates before validation.
+#[derive(Deserialize)]
+struct UncheckedTileCoordinates {
+    zoom: u32,
+    x: u32,
+    y: u32,
+}
+
+impl TryFrom<UncheckedTileCoordinates> for TileCoordinates {
+    type Error = TileCoordinatesError;
+
+    fn try_from(coords: UncheckedTileCoordinates) -> Result<Self, Self::Error> {
+        Self::new(coords.zoom, coords.x, coords.y)
+    }
+}
+
+/// Error on tile coordinates.
+#[derive(Debug, thiserror::Error)]
+pub enum TileCoordinatesError {
+    /// Zoom level is greater than [`MAX_ZOOM`].
+    #[error("invalid tile coordinates: zoom level {0} is greater than {MAX_ZOOM}")]
+    InvalidZoom(u32),
+    /// X or Y coordinate is out of range at the zoom level.
+    #[error("invalid tile coordinates: {zoom}/{x}/{y} is out of range")]
+    OutOfRange {
+        /// Zoom level.
+        zoom: u32,
+        /// X coordinate.
+        x: u32,
+        /// Y coordinate.
+        y: u32,
+    },
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_tile_coordinates_new() {
+        assert!(TileCoordinates::new(0, 0, 0).is_ok());
+        assert!(TileCoordinates::new(16, 58138, 25860).is_ok());
+        assert!(TileCoordinates::new(22, (1 << 22) - 1, (1 << 22) - 1).is_ok());
+        assert!(matches!(
+            TileCoordinates::new(23, 0, 0),
+            Err(TileCoordinatesError::InvalidZoom(23)),
+        ));
+        assert!(matches!(
+            TileCoordinates::new(0, 1, 0),
+            Err(TileCoordinatesError::OutOfRange { zoom: 0, x: 1, y: 0 }),
+        ));
+        assert!(matches!(
+            TileCoordinates::new(10, 0, 1024),
+            Err(TileCoordinatesError::OutOfRange { zoom: 10, x: 0, y: 1024 }),
+        ));
+    }
+
+    #[test]
+    fn test_tile_coordinates_deserialize() {
+        let coords: TileCoordinates = serde_json::from_str(
+            r#"{ "zoom": 16, "x": 58138, "y": 25860 }"#,
+        ).unwrap();
+        assert_eq!((coords.zoom, coords.x, coords.y), (16, 58138, 25860));
+
+        let e = serde_json::from_str::<TileCoordinates>(r#"{ "zoom": 32, "x": 0, "y": 0 }"#)
+            .unwrap_err();
+        assert!(e.to_string().starts_with("invalid tile coordinates"));
+        assert!(serde_json::from_str::<TileCoordinates>(r#"{ "zoom": 2, "x": 4, "y": 0 }"#).is_err());
+    }
+
+    #[test]
+    fn test_tile_coordinates_from_location() {
+        let tokyo_station = GeolocationCoordinates {
+            longitude: 139.7670506677,
+            latitude: 35.6814709332,
+        };
+        let coords = TileCoordinates::from_location(&tokyo_station, 0);
+        assert_eq!((coords.zoom, coords.x, coords.y), (0, 0, 0));
+        let coords = TileCoordinates::from_location(&tokyo_station, 16);
+        assert_eq!((coords.zoom, coords.x, coords.y), (16, 58211, 25806));
+        assert!(coords.contains_location(&tokyo_station));
+
+        let north_pole = GeolocationCoordinates {
+            longitude: 180.0,
+            latitude: 90.0,
+        };
+        let coords = TileCoordinates::from_location(&north_pole, 2);
+        assert_eq!((coords.zoom, coords.x, coords.y), (2, 3, 0));
+    }
+
     #[test]
     fn test_tile_coordinates_zoom_out() {
         // z = 1 → 0
@@ -68,4 +293,94 @@ mod tests {
         let coords = TileCoordinates { zoom: 0, x: 0, y: 0 };
         assert!(coords.zoom_out_to(1).is_none());
     }
+
+    #[test]
+    fn test_tile_coordinates_zoom_in() {
+        // z = 0 → 1
+        let coords = TileCoordinates { zoom: 0, x: 0, y: 0 };
+        let tiles = coords.zoom_in_to(1).unwrap();
+        let tiles: Vec<_> = tiles.iter().map(|t| (t.zoom, t.x, t.y)).collect();
+        assert_eq!(tiles, vec![(1, 0, 0), (1, 1, 0), (1, 0, 1), (1, 1, 1)]);
+
+        // z = 10 → 12
+        let coords = TileCoordinates { zoom: 10, x: 308, y: 833 };
+        let tiles = coords.zoom_in_to(12).unwrap();
+        assert_eq!(tiles.len(), 16);
+        assert!(tiles.iter().all(|t| t.zoom == 12));
+        assert!(tiles.iter().any(|t| t.x == 1234 && t.y == 3333));
+        assert!(tiles.iter().all(|t| t.zoom_out_to(10).unwrap().x == 308));
+        assert!(tiles.iter().all(|t| t.zoom_out_to(10).unwrap().y == 833));
+
+        // z = 16 → 16
+        let coords = TileCoordinates { zoom: 16, x: 58138, y: 25860 };
+        let tiles = coords.zoom_in_to(16).unwrap();
+        assert_eq!(tiles.len(), 1);
+        assert_eq!((tiles[0].x, tiles[0].y), (58138, 25860));
+
+        // z = 1 → 0
+        let coords = TileCoordinates { zoom: 1, x: 1, y: 1 };
+        assert!(coords.zoom_in_to(0).is_none());
+
+        // beyond the maximum zoom level
+        let coords = TileCoordinates { zoom: MAX_ZOOM, x: 0, y: 0 };
+        assert!(coords.zoom_in_to(MAX_ZOOM + 1).is_none());
+    }
+
+    #[test]
+    fn test_tile_coordinates_contains_location() {
+        let tokyo_station = GeolocationCoordinates {
+            longitude: 139.7670506677,
+            latitude: 35.6814709332,
+        };
+        let coords = TileCoordinates { zoom: 10, x: 909, y: 403 };
+        assert!(coords.contains_location(&tokyo_station));
+        let coords = TileCoordinates { zoom: 10, x: 910, y: 403 };
+        assert!(!coords.contains_location(&tokyo_station));
+        let coords = TileCoordinates { zoom: 0, x: 0, y: 0 };
+        assert!(coords.contains_location(&tokyo_station));
+
+        // Tokyo Station is near the left edge of the tile at zoom level 16
+        let coords = TileCoordinates { zoom: 16, x: 58211, y: 25806 };
+        assert!(coords.contains_location(&tokyo_station));
+        let coords = TileCoordinates { zoom: 16, x: 58212, y: 25806 };
+        assert!(!coords.contains_location(&tokyo_station));
+        assert!(!coords.contains_location_with_margin(&tokyo_station, 0.1));
+        assert!(coords.contains_location_with_margin(&tokyo_station, 0.25));
+    }
+
+    #[test]
+    fn test_tile_coordinates_overlapping_tiles_at() {
+        let tiles_of = |coords: &TileCoordinates, zoom: u32, margin: f64| {
+            coords
+                .overlapping_tiles_at(zoom, margin)
+                .into_iter()
+                .map(|t| (t.zoom, t.x, t.y))
+                .collect::<Vec<_>>()
+        };
+
+        // no margin is equivalent to zooming in or out
+        let coords = TileCoordinates { zoom: 12, x: 1234, y: 3333 };
+        assert_eq!(tiles_of(&coords, 10, 0.0), vec![(10, 308, 833)]);
+        assert_eq!(tiles_of(&coords, 12, 0.0), vec![(12, 1234, 3333)]);
+        assert_eq!(tiles_of(&coords, 13, 0.0).len(), 4);
+
+        // margin at the same zoom level includes the neighbors
+        assert_eq!(tiles_of(&coords, 12, 0.1), vec![
+            (12, 1233, 3332), (12, 1234, 3332), (12, 1235, 3332),
+            (12, 1233, 3333), (12, 1234, 3333), (12, 1235, 3333),
+            (12, 1233, 3334), (12, 1234, 3334), (12, 1235, 3334),
+        ]);
+
+        // margin at a coarser zoom level includes the neighbors only at edges
+        // 1234 = 308 * 4 + 2, 3333 = 833 * 4 + 1
+        assert_eq!(tiles_of(&coords, 10, 0.1), vec![(10, 308, 833)]);
+        let coords = TileCoordinates { zoom: 12, x: 1235, y: 3332 };
+        assert_eq!(tiles_of(&coords, 10, 0.1), vec![
+            (10, 308, 832), (10, 309, 832), (10, 308, 833), (10, 309, 833),
+        ]);
+
+        // tiles beyond the edges of the world are excluded
+        let coords = TileCoordinates { zoom: 0, x: 0, y: 0 };
+        assert_eq!(tiles_of(&coords, 0, 0.1), vec![(0, 0, 0)]);
+    }
 }
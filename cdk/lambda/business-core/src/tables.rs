@@ -2,25 +2,40 @@
 
 use aws_sdk_dynamodb::{
     error::SdkError,
-    operation::{get_item::GetItemError, query::QueryError},
-    types::AttributeValue,
+    operation::{
+        get_item::GetItemError,
+        query::QueryError,
+        transact_write_items::TransactWriteItemsError,
+        update_item::UpdateItemError,
+    },
+    types::{AttributeValue, ConditionCheck, Delete, Put, TransactWriteItem},
 };
 use aws_smithy_async::future::pagination_stream::PaginationStream;
+use base64::{
+    Engine as _,
+    engine::general_purpose::URL_SAFE_NO_PAD as base64_engine,
+};
 use core::pin::Pin;
 use core::task::{Context, Poll};
 use derive_builder::Builder;
 use futures::{future, stream::{self, Stream, TryStreamExt as _}};
 use pin_project::pin_project;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::marker::{Send, Sync};
 
 use crate::mvt::TileCoordinates;
 use crate::types::{
+    AdvocacySetting,
     BusinessRecord,
     BusinessRecordBuilder,
     BusinessType,
     GeolocationCoordinates,
 };
+use crate::web_mercator::MAX_ZOOM;
+
+/// Number of seconds in an hour.
+const SECONDS_PER_HOUR: i64 = 3600;
 
 /// Resource table.
 #[derive(Debug)]
@@ -58,9 +73,120 @@ impl ResourceTable {
             .send()
             .await?;
         res.item
-            .map(|_| Ok(UserDogRelationship::Friend))
+            .map(|item| {
+                let is_guardian = item
+                    .get("isGuardian")
+                    .map(|v| v.as_bool().map_err(|_| TableError::item_error("isGuardian must be a boolean")))
+                    .transpose()?
+                    .copied()
+                    .unwrap_or(false);
+                Ok(if is_guardian {
+                    UserDogRelationship::Guardian
+                } else {
+                    UserDogRelationship::Friend
+                })
+            })
+            .transpose()
+    }
+
+    /// Returns the advocacy setting of a given dog.
+    ///
+    /// Always reads the latest setting with a strongly consistent read.
+    ///
+    /// Returns `None` if the dog does not exist.
+    /// Returns the default [`AdvocacySetting`] if the dog has no advocacy
+    /// setting.
+    pub async fn get_dog_advocacy_setting(
+        &self,
+        dog_id: &str,
+    ) -> Result<Option<AdvocacySetting>, TableError> {
+        let res = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(format!("dog#{dog_id}")))
+            .key("sk", AttributeValue::S("info".to_string()))
+            .projection_expression("advocacySetting")
+            .consistent_read(true)
+            .send()
+            .await?;
+        res.item
+            .map(|item| Self::parse_advocacy_setting(&item))
             .transpose()
     }
+
+    /// Updates the advocacy setting of a given dog.
+    ///
+    /// `updated_at` is the number of seconds elapsed since 00:00:00 on
+    /// January 1, 1970 UTC.
+    ///
+    /// Fails with a [`TableError::ConditionFailed`] if the dog does not exist.
+    pub async fn update_dog_advocacy_setting(
+        &self,
+        dog_id: &str,
+        advocacy_setting: AdvocacySetting,
+        updated_at: i64,
+    ) -> Result<(), TableError> {
+        self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(format!("dog#{dog_id}")))
+            .key("sk", AttributeValue::S("info".to_string()))
+            .update_expression("SET #advocacySetting = :advocacySetting, #updatedAt = :updatedAt")
+            .condition_expression("attribute_exists(pk)")
+            .expression_attribute_names("#advocacySetting", "advocacySetting")
+            .expression_attribute_names("#updatedAt", "updatedAt")
+            .expression_attribute_values(
+                ":advocacySetting",
+                AttributeValue::S(advocacy_setting.to_string()),
+            )
+            .expression_attribute_values(":updatedAt", AttributeValue::N(updated_at.to_string()))
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Returns a transaction item that checks if the advocacy setting of a
+    /// given dog still equals `setting`.
+    ///
+    /// A missing setting is regarded as the default one.
+    fn check_advocacy_setting(
+        &self,
+        dog_id: &str,
+        setting: AdvocacySetting,
+    ) -> Result<TransactWriteItem, TableError> {
+        let condition = if setting == AdvocacySetting::default() {
+            "attribute_not_exists(#advocacySetting) OR #advocacySetting = :advocacySetting"
+        } else {
+            "#advocacySetting = :advocacySetting"
+        };
+        let check = ConditionCheck::builder()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(format!("dog#{dog_id}")))
+            .key("sk", AttributeValue::S("info".to_string()))
+            .condition_expression(condition)
+            .expression_attribute_names("#advocacySetting", "advocacySetting")
+            .expression_attribute_values(":advocacySetting", AttributeValue::S(setting.to_string()))
+            .build()
+            .map_err(|e| TableError::InternalError(e.into()))?;
+        Ok(TransactWriteItem::builder().condition_check(check).build())
+    }
+
+    /// Parses the advocacy setting in a dog item.
+    ///
+    /// Returns the default [`AdvocacySetting`] if it is missing.
+    pub fn parse_advocacy_setting(
+        item: &HashMap<String, AttributeValue>,
+    ) -> Result<AdvocacySetting, TableError> {
+        item.get("advocacySetting")
+            .map(|v| {
+                v.as_s()
+                    .map_err(|_| TableError::item_error("advocacySetting must be a string"))
+                    .and_then(|s| s.parse().map_err(TableError::item_error))
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
 }
 
 /// Business record table.
@@ -84,46 +210,268 @@ pub struct BusinessRecordTable {
 }
 
 impl BusinessRecordTable {
-    /// Queries business records carried out by a given dog.
+    /// Creates a new business record.
+    ///
+    /// Puts both the private and public rows of the business record in a
+    /// single transaction, so either both or neither of them are created.
+    /// The public row is omitted if the dog does not publish business
+    /// records.
+    ///
+    /// The business record is created only if the advocacy setting of the dog
+    /// in `resource_table` still equals `record.advocacy_setting` at the time
+    /// of the creation. otherwise, returns [`RecordWrite::SettingChanged`].
+    ///
+    /// Fails with a [`TableError::ConditionFailed`] if a business record with
+    /// the same ID already exists.
+    pub async fn create_record(
+        &self,
+        record: &NewBusinessRecord,
+        resource_table: &ResourceTable,
+    ) -> Result<RecordWrite, TableError> {
+        let put_item = |item| -> Result<TransactWriteItem, TableError> {
+            let put = Put::builder()
+                .table_name(&self.table_name)
+                .set_item(Some(item))
+                .condition_expression("attribute_not_exists(pk)")
+                .build()
+                .map_err(|e| TableError::InternalError(e.into()))?;
+            Ok(TransactWriteItem::builder().put(put).build())
+        };
+        let request = self
+            .client
+            .transact_write_items()
+            .transact_items(
+                resource_table.check_advocacy_setting(&record.dog_id, record.advocacy_setting)?,
+            )
+            .transact_items(put_item(record.private_item())?);
+        let request = match record.public_item() {
+            Some(item) => request.transact_items(put_item(item)?),
+            None => request,
+        };
+        match request.send().await {
+            Ok(_) => Ok(RecordWrite::Written),
+            Err(e) if is_condition_check_failed_at(&e, 0) => Ok(RecordWrite::SettingChanged),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns the private business record with a given ID.
+    ///
+    /// Returns `None` if the business record does not exist.
+    pub async fn get_record(&self, record_id: &str) -> Result<Option<BusinessRecord>, TableError> {
+        let res = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(record_id.to_string()))
+            .key("sk", AttributeValue::S("private".to_string()))
+            .consistent_read(true)
+            .send()
+            .await?;
+        res.item.map(Self::parse_business_record_item).transpose()
+    }
+
+    /// Updates an existing business record.
+    ///
+    /// Replaces both the private and public rows of the business record in a
+    /// single transaction. The public row is deleted instead if the dog does
+    /// not publish business records.
+    ///
+    /// The business record is updated only if the advocacy setting of the dog
+    /// in `resource_table` still equals `record.advocacy_setting` at the time
+    /// of the update. otherwise, returns [`RecordWrite::SettingChanged`].
+    ///
+    /// Fails with a [`TableError::ConditionFailed`] if no business record with
+    /// the ID exists, or if the business record was carried out by another
+    /// dog.
+    pub async fn update_record(
+        &self,
+        record: &NewBusinessRecord,
+        resource_table: &ResourceTable,
+    ) -> Result<RecordWrite, TableError> {
+        let put_private_row = Put::builder()
+            .table_name(&self.table_name)
+            .set_item(Some(record.private_item()))
+            .condition_expression("attribute_exists(pk) AND #dogId = :dogId")
+            .expression_attribute_names("#dogId", "dogId")
+            .expression_attribute_values(":dogId", AttributeValue::S(record.dog_id.clone()))
+            .build()
+            .map_err(|e| TableError::InternalError(e.into()))?;
+        let res = self
+            .client
+            .transact_write_items()
+            .transact_items(
+                resource_table.check_advocacy_setting(&record.dog_id, record.advocacy_setting)?,
+            )
+            .transact_items(TransactWriteItem::builder().put(put_private_row).build())
+            .transact_items(self.change_public_row(record)?)
+            .send()
+            .await;
+        match res {
+            Ok(_) => Ok(RecordWrite::Written),
+            Err(e) if is_condition_check_failed_at(&e, 0) => Ok(RecordWrite::SettingChanged),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Deletes a business record.
+    ///
+    /// Deletes both the private and public rows of the business record in a
+    /// single transaction.
+    ///
+    /// Fails with a [`TableError::ConditionFailed`] if no business record with
+    /// the ID exists, or if the business record was carried out by another
+    /// dog.
+    pub async fn delete_record(&self, record_id: &str, dog_id: &str) -> Result<(), TableError> {
+        let delete_row = |sk: &str| {
+            Delete::builder()
+                .table_name(&self.table_name)
+                .key("pk", AttributeValue::S(record_id.to_string()))
+                .key("sk", AttributeValue::S(sk.to_string()))
+        };
+        let delete_private_row = delete_row("private")
+            .condition_expression("attribute_exists(pk) AND #dogId = :dogId")
+            .expression_attribute_names("#dogId", "dogId")
+            .expression_attribute_values(":dogId", AttributeValue::S(dog_id.to_string()))
+            .build()
+            .map_err(|e| TableError::InternalError(e.into()))?;
+        let delete_public_row = delete_row("public")
+            .build()
+            .map_err(|e| TableError::InternalError(e.into()))?;
+        self.client
+            .transact_write_items()
+            .transact_items(TransactWriteItem::builder().delete(delete_private_row).build())
+            .transact_items(TransactWriteItem::builder().delete(delete_public_row).build())
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Synchronizes the public row of a business record with the advocacy
+    /// setting of the dog.
+    ///
+    /// `record` must reflect the private row of the business record.
+    /// Puts or overwrites the public row if the dog publishes business
+    /// records, and deletes the public row otherwise.
+    ///
+    /// The public row is changed only if the following conditions are
+    /// satisfied at the time of the change:
+    /// - the advocacy setting of the dog in `resource_table` still equals
+    ///   `record.advocacy_setting`. otherwise, returns
+    ///   [`PublicRecordSync::SettingChanged`].
+    /// - the private row still exists. otherwise, returns
+    ///   [`PublicRecordSync::RecordNotFound`].
+    pub async fn sync_public_record(
+        &self,
+        record: &NewBusinessRecord,
+        resource_table: &ResourceTable,
+    ) -> Result<PublicRecordSync, TableError> {
+        let check_setting = resource_table
+            .check_advocacy_setting(&record.dog_id, record.advocacy_setting)?;
+        let check_private_row = ConditionCheck::builder()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(record.record_id.clone()))
+            .key("sk", AttributeValue::S("private".to_string()))
+            .condition_expression("attribute_exists(pk)")
+            .build()
+            .map_err(|e| TableError::InternalError(e.into()))?;
+        let res = self
+            .client
+            .transact_write_items()
+            .transact_items(check_setting)
+            .transact_items(TransactWriteItem::builder().condition_check(check_private_row).build())
+            .transact_items(self.change_public_row(record)?)
+            .send()
+            .await;
+        // reasons are in the same order as the items
+        match res {
+            Ok(_) => Ok(PublicRecordSync::Synced),
+            Err(e) if is_condition_check_failed_at(&e, 0) => Ok(PublicRecordSync::SettingChanged),
+            Err(e) if is_condition_check_failed_at(&e, 1) => Ok(PublicRecordSync::RecordNotFound),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns a transaction item that puts or overwrites the public row of a
+    /// business record if the dog publishes business records, and deletes the
+    /// public row otherwise.
+    fn change_public_row(&self, record: &NewBusinessRecord) -> Result<TransactWriteItem, TableError> {
+        let item = match record.public_item() {
+            Some(item) => TransactWriteItem::builder().put(
+                Put::builder()
+                    .table_name(&self.table_name)
+                    .set_item(Some(item))
+                    .build()
+                    .map_err(|e| TableError::InternalError(e.into()))?,
+            ),
+            None => TransactWriteItem::builder().delete(
+                Delete::builder()
+                    .table_name(&self.table_name)
+                    .key("pk", AttributeValue::S(record.record_id.clone()))
+                    .key("sk", AttributeValue::S("public".to_string()))
+                    .build()
+                    .map_err(|e| TableError::InternalError(e.into()))?,
+            ),
+        };
+        Ok(item.build())
+    }
+
+    /// Queries a page of business records carried out by a given dog.
+    ///
+    /// Business records are in descending order of timestamps. Returns the
+    /// first page if `next_token` is `None`, and otherwise the page following
+    /// the one that returned `next_token`.
     ///
     /// Fails with a [`TableError::BadConfiguration`] if no GSI name for dog
     /// IDs is configured.
-    pub fn query_by_dog_id(
+    ///
+    /// Fails with a [`TableError::InvalidContinuationToken`] if `next_token`
+    /// is broken.
+    pub async fn query_by_dog_id(
         &self,
-        dog_id: impl Into<String>,
+        dog_id: &str,
         max_records: usize,
-    ) -> Result<impl Stream<Item = Result<BusinessRecord, TableError>>, TableError> {
+        next_token: Option<&ContinuationToken>,
+    ) -> Result<RecordPage, TableError> {
         let dog_index_name = self
             .dog_index_name
             .as_ref()
             .ok_or_else(|| TableError::BadConfiguration("dog index name must be set".into()))?;
-        let paginator = self
+        let res = self
             .client
             .query()
             .table_name(&self.table_name)
             .index_name(dog_index_name)
             .key_condition_expression("#dogId = :dogId")
             .expression_attribute_names("#dogId", "dogId")
-            .expression_attribute_values(":dogId", AttributeValue::S(dog_id.into()))
+            .expression_attribute_values(":dogId", AttributeValue::S(dog_id.to_string()))
+            .set_exclusive_start_key(next_token.map(|token| token.to_key(dog_id)).transpose()?)
             .scan_index_forward(false) // newest first
             .limit(max_records as i32)
-            .into_paginator()
-            .send();
-        let records = PaginationStreamExt(paginator)
-            .and_then(|output| {
-                let items = output
-                    .items
-                    .unwrap_or_default()
-                    .into_iter()
-                    .map(Self::parse_business_record_item);
-                future::ok(stream::iter(items))
-            })
-            .try_flatten();
-        Ok(records)
+            .send()
+            .await?;
+        let records = res
+            .items
+            .unwrap_or_default()
+            .into_iter()
+            .map(Self::parse_business_record_item)
+            .collect::<Result<Vec<_>, _>>()?;
+        let next_token = res
+            .last_evaluated_key
+            .map(|key| ContinuationToken::from_key(&key))
+            .transpose()?;
+        Ok(RecordPage {
+            records,
+            next_token,
+        })
     }
 
     /// Queries public business records in a map tile at a given location.
     ///
+    /// Only business records that satisfy `filter` are returned.
+    /// Since timestamps of public business records are in hours, `since` and
+    /// `until` of `filter` are rounded down to hours.
+    ///
     /// Fails with a [`TableError::BadConfiguration`] if no GSI name prefix for
     /// map tiles at specific zoom levels is configured.
     ///
@@ -131,24 +479,100 @@ impl BusinessRecordTable {
     pub fn query_by_tile(
         &self,
         coordinates: &TileCoordinates,
+        filter: &RecordFilter,
+        max_records: usize,
+    ) -> Result<impl Stream<Item = Result<BusinessRecord, TableError>>, TableError> {
+        // public timestamps are in hours
+        let filter = RecordFilter {
+            since: filter.since.map(|t| t.div_euclid(SECONDS_PER_HOUR)),
+            until: filter.until.map(|t| t.div_euclid(SECONDS_PER_HOUR)),
+            business_type: filter.business_type.clone(),
+        };
+        self.query_by_tile_key(
+            coordinates.zoom(),
+            format!("public#{}/{}", coordinates.x(), coordinates.y()),
+            filter,
+            max_records,
+        )
+    }
+
+    /// Queries private business records of a given dog in a map tile at a
+    /// given location.
+    ///
+    /// Only business records that satisfy `filter` are returned.
+    /// Unlike [`BusinessRecordTable::query_by_tile`], timestamps are in
+    /// seconds.
+    ///
+    /// Fails with a [`TableError::BadConfiguration`] if no GSI name prefix for
+    /// map tiles at specific zoom levels is configured.
+    ///
+    /// Fails if the zoom level is not indexed.
+    pub fn query_by_dog_tile(
+        &self,
+        dog_id: &str,
+        coordinates: &TileCoordinates,
+        filter: &RecordFilter,
+        max_records: usize,
+    ) -> Result<impl Stream<Item = Result<BusinessRecord, TableError>>, TableError> {
+        self.query_by_tile_key(
+            coordinates.zoom(),
+            format!("dog#{}#{}/{}", dog_id, coordinates.x(), coordinates.y()),
+            filter.clone(),
+            max_records,
+        )
+    }
+
+    /// Queries business records with a given tile key at a given zoom level.
+    ///
+    /// `since` and `until` of `filter` must be in the same unit as the
+    /// timestamps of the queried records.
+    fn query_by_tile_key(
+        &self,
+        zoom: u32,
+        tile_key: String,
+        filter: RecordFilter,
         max_records: usize,
     ) -> Result<impl Stream<Item = Result<BusinessRecord, TableError>>, TableError> {
         let tile_index_name = self
             .tile_index_name_prefix
             .as_ref()
-            .map(|prefix| format!("{}{}", prefix, coordinates.zoom))
+            .map(|prefix| format!("{}{}", prefix, zoom))
             .ok_or_else(|| TableError::BadConfiguration("tile index name prefix must be set".into()))?;
-        let paginator = self
+        let request = self
             .client
             .query()
             .table_name(&self.table_name)
             .index_name(tile_index_name)
-            .key_condition_expression("#tileAtZ = :tileXY")
-            .expression_attribute_names("#tileAtZ", format!("tileAtZ{}", coordinates.zoom))
-            .expression_attribute_values(
-                ":tileXY",
-                AttributeValue::S(format!("public#{}/{}", coordinates.x, coordinates.y))
-            )
+            .expression_attribute_names("#tileAtZ", format!("tileAtZ{}", zoom))
+            .expression_attribute_values(":tileXY", AttributeValue::S(tile_key));
+        let request = match (filter.since, filter.until) {
+            (Some(since), Some(until)) => request
+                .key_condition_expression("#tileAtZ = :tileXY AND #timestamp BETWEEN :since AND :until")
+                .expression_attribute_names("#timestamp", "timestamp")
+                .expression_attribute_values(":since", AttributeValue::N(since.to_string()))
+                .expression_attribute_values(":until", AttributeValue::N(until.to_string())),
+            (Some(since), None) => request
+                .key_condition_expression("#tileAtZ = :tileXY AND #timestamp >= :since")
+                .expression_attribute_names("#timestamp", "timestamp")
+                .expression_attribute_values(":since", AttributeValue::N(since.to_string())),
+            (None, Some(until)) => request
+                .key_condition_expression("#tileAtZ = :tileXY AND #timestamp <= :until")
+                .expression_attribute_names("#timestamp", "timestamp")
+                .expression_attribute_values(":until", AttributeValue::N(until.to_string())),
+            (None, None) => request
+                .key_condition_expression("#tileAtZ = :tileXY"),
+        };
+        let request = match filter.business_type {
+            Some(business_type) => request
+                .filter_expression("#businessType = :businessType")
+                .expression_attribute_names("#businessType", "businessType")
+                .expression_attribute_values(
+                    ":businessType",
+                    AttributeValue::S(business_type.to_string()),
+                ),
+            None => request,
+        };
+        let paginator = request
             .scan_index_forward(false) // newest first
             .limit(max_records as i32)
             .into_paginator()
@@ -181,6 +605,18 @@ impl BusinessRecordTable {
                     .transpose()?
                     .cloned(),
             )
+            .masked_dog_id(
+                item.get("maskedDogId")
+                    .map(|v| v.as_s().map_err(|_| TableError::item_error("maskedDogId must be a string")))
+                    .transpose()?
+                    .cloned(),
+            )
+            .is_advocated(
+                item.get("isAdvocated")
+                    .map(|v| v.as_bool().map_err(|_| TableError::item_error("isAdvocated must be a boolean")))
+                    .transpose()?
+                    .copied(),
+            )
             .business_type(
                 item.get("businessType")
                     .ok_or_else(|| TableError::item_error("businessType is missing"))
@@ -214,11 +650,255 @@ impl BusinessRecordTable {
     }
 }
 
+/// Business record to be created or updated.
+#[derive(Builder, Clone, Debug)]
+#[builder(setter(into), pattern = "owned")]
+pub struct NewBusinessRecord {
+    /// ID of the business record.
+    pub record_id: String,
+    /// ID of the dog who carried out the business.
+    pub dog_id: String,
+    /// Type of the business.
+    pub business_type: BusinessType,
+    /// Location of the business.
+    pub location: GeolocationCoordinates,
+    /// Timestamp when the business record was created.
+    ///
+    /// Represented as the number of seconds elapsed since 00:00:00 on
+    /// January 1, 1970 UTC.
+    pub timestamp: i64,
+    /// Advocacy setting of the dog who carried out the business.
+    ///
+    /// No public row is created if the dog does not publish business records.
+    ///
+    /// Builder: [`AdvocacySetting::default`] by default.
+    #[builder(default)]
+    pub advocacy_setting: AdvocacySetting,
+}
+
+impl NewBusinessRecord {
+    /// Creates from a private business record.
+    ///
+    /// Fails with a [`TableError::ItemError`] if `record` has no dog ID.
+    pub fn from_private_record(
+        record: &BusinessRecord,
+        advocacy_setting: AdvocacySetting,
+    ) -> Result<Self, TableError> {
+        Ok(Self {
+            record_id: record.record_id.clone(),
+            dog_id: record
+                .dog_id
+                .clone()
+                .ok_or_else(|| TableError::item_error("private record must have dogId"))?,
+            business_type: record.business_type.clone(),
+            location: record.location.clone(),
+            timestamp: record.timestamp,
+            advocacy_setting,
+        })
+    }
+
+    /// Returns the masked (semi-unique) dog ID.
+    ///
+    /// The latter half of the dog ID.
+    pub fn masked_dog_id(&self) -> &str {
+        let (_, masked_dog_id) = self.dog_id.split_at(self.dog_id.len() / 2);
+        masked_dog_id
+    }
+
+    /// Returns the item of the private row.
+    fn private_item(&self) -> HashMap<String, AttributeValue> {
+        let mut item = self.common_item("private", self.timestamp);
+        item.insert("dogId".into(), AttributeValue::S(self.dog_id.clone()));
+        item.extend(self.tile_attributes(|x, y| format!("dog#{}#{x}/{y}", self.dog_id)));
+        item
+    }
+
+    /// Returns the item of the public row.
+    ///
+    /// Returns `None` if the dog does not publish business records.
+    ///
+    /// Timestamps of public rows are in hours.
+    fn public_item(&self) -> Option<HashMap<String, AttributeValue>> {
+        if !self.advocacy_setting.is_published() {
+            return None;
+        }
+        let mut item = self.common_item(
+            "public",
+            self.timestamp.div_euclid(SECONDS_PER_HOUR),
+        );
+        item.insert("maskedDogId".into(), AttributeValue::S(self.masked_dog_id().to_string()));
+        item.insert(
+            "isAdvocated".into(),
+            AttributeValue::Bool(self.advocacy_setting.is_advocated()),
+        );
+        item.extend(self.tile_attributes(|x, y| format!("public#{x}/{y}")));
+        Some(item)
+    }
+
+    /// Returns the attributes shared by the private and public rows.
+    fn common_item(&self, sk: &str, timestamp: i64) -> HashMap<String, AttributeValue> {
+        HashMap::from([
+            ("pk".into(), AttributeValue::S(self.record_id.clone())),
+            ("sk".into(), AttributeValue::S(sk.into())),
+            ("businessType".into(), AttributeValue::S(self.business_type.to_string())),
+            ("longitude".into(), AttributeValue::N(format_geo_coordinate(self.location.longitude))),
+            ("latitude".into(), AttributeValue::N(format_geo_coordinate(self.location.latitude))),
+            ("timestamp".into(), AttributeValue::N(timestamp.to_string())),
+        ])
+    }
+
+    /// Returns the `tileAtZ{z}` attributes at all the zoom levels.
+    ///
+    /// `tile_key` formats the value from the tile coordinates.
+    fn tile_attributes(
+        &self,
+        tile_key: impl Fn(u32, u32) -> String,
+    ) -> impl Iterator<Item = (String, AttributeValue)> {
+        (0..=MAX_ZOOM).map(move |zoom| {
+            let tile = TileCoordinates::from_location(&self.location, zoom);
+            (format!("tileAtZ{zoom}"), AttributeValue::S(tile_key(tile.x(), tile.y())))
+        })
+    }
+}
+
+/// Formats a geographic coordinate (longitude or latitude) as a string.
+fn format_geo_coordinate(coord: f64) -> String {
+    format!("{coord:.10}")
+}
+
+/// Opaque token to continue a query of the private business records of a
+/// dog.
+///
+/// URL-safe Base64 encoding of the last evaluated key of the previous query.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct ContinuationToken(String);
+
+impl ContinuationToken {
+    /// Creates from the last evaluated key of the GSI for dog IDs.
+    fn from_key(key: &HashMap<String, AttributeValue>) -> Result<Self, TableError> {
+        let record_id = key
+            .get("pk")
+            .ok_or_else(|| TableError::item_error("pk (record ID) is missing"))
+            .and_then(|v| v.as_s().map_err(|_| TableError::item_error("pk (record ID) must be a string")))?;
+        let timestamp = key
+            .get("timestamp")
+            .ok_or_else(|| TableError::item_error("timestamp is missing"))
+            .and_then(|v| v.as_n().map_err(|_| TableError::item_error("timestamp must be a number")))
+            .and_then(|n| n.parse::<i64>().map_err(|_| TableError::item_error("invalid timestamp")))?;
+        Ok(Self::encode(timestamp, record_id))
+    }
+
+    /// Converts into the exclusive start key of the GSI for dog IDs.
+    fn to_key(&self, dog_id: &str) -> Result<HashMap<String, AttributeValue>, TableError> {
+        let invalid = || TableError::InvalidContinuationToken(self.0.clone());
+        let decoded = base64_engine.decode(&self.0).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (timestamp, record_id) = decoded.split_once('#').ok_or_else(invalid)?;
+        let timestamp: i64 = timestamp.parse().map_err(|_| invalid())?;
+        Ok(HashMap::from([
+            ("pk".into(), AttributeValue::S(record_id.to_string())),
+            ("sk".into(), AttributeValue::S("private".into())),
+            ("dogId".into(), AttributeValue::S(dog_id.to_string())),
+            ("timestamp".into(), AttributeValue::N(timestamp.to_string())),
+        ]))
+    }
+
+    /// Encodes the timestamp and ID of the last business record.
+    fn encode(timestamp: i64, record_id: &str) -> Self {
+        Self(base64_engine.encode(format!("{timestamp}#{record_id}")))
+    }
+
+    /// Returns the token as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Token to continue right after a given private business record.
+impl From<&BusinessRecord> for ContinuationToken {
+    fn from(record: &BusinessRecord) -> Self {
+        Self::encode(record.timestamp, &record.record_id)
+    }
+}
+
+/// Page of business records.
+#[derive(Clone, Debug)]
+pub struct RecordPage {
+    /// Business records in the page.
+    pub records: Vec<BusinessRecord>,
+    /// Token to query the next page.
+    ///
+    /// `None` if there are no more business records. May be `Some` even if
+    /// the next page turns out to be empty.
+    pub next_token: Option<ContinuationToken>,
+}
+
+/// Result of a write operation of a business record that depends on the
+/// advocacy setting of the dog.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RecordWrite {
+    /// Business record has been written.
+    Written,
+    /// Advocacy setting of the dog has been changed since it was read.
+    SettingChanged,
+}
+
+/// Result of [`BusinessRecordTable::sync_public_record`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PublicRecordSync {
+    /// Public row has been synchronized.
+    Synced,
+    /// Private row does not exist; e.g., the business record has been
+    /// deleted.
+    RecordNotFound,
+    /// Advocacy setting of the dog has been changed since it was read.
+    SettingChanged,
+}
+
+/// Conditions to filter business records.
+///
+/// Every condition is optional, and no condition is applied by default.
+#[derive(Builder, Clone, Debug, Default)]
+#[builder(setter(into), pattern = "owned", default)]
+pub struct RecordFilter {
+    /// Earliest timestamp (inclusive) of business records.
+    ///
+    /// Represented as the number of seconds elapsed since 00:00:00 on
+    /// January 1, 1970 UTC.
+    pub since: Option<i64>,
+    /// Latest timestamp (inclusive) of business records.
+    ///
+    /// Represented as the number of seconds elapsed since 00:00:00 on
+    /// January 1, 1970 UTC.
+    pub until: Option<i64>,
+    /// Type of business records.
+    pub business_type: Option<BusinessType>,
+}
+
+impl RecordFilter {
+    /// Makes sure that the conditions are consistent.
+    ///
+    /// Fails with a message if `since` is later than `until`.
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.since, self.until) {
+            (Some(since), Some(until)) if since > until => Err(format!(
+                "invalid time window: since ({since}) is later than until ({until})",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Relationship between a user and a dog.
 #[derive(Clone, Debug)]
 pub enum UserDogRelationship {
     /// User is a friend of the dog.
     Friend,
+    /// User is a guardian of the dog.
+    ///
+    /// A guardian is also a friend.
+    Guardian,
 }
 
 /// Error related to table operations.
@@ -227,6 +907,13 @@ pub enum TableError {
     /// Item parsing error.
     #[error("item error: {0}")]
     ItemError(String),
+    /// Broken continuation token.
+    #[error("invalid continuation token: {0}")]
+    InvalidContinuationToken(String),
+    /// Condition of a write operation is not satisfied; e.g., the item
+    /// already exists.
+    #[error("condition failed: {0}")]
+    ConditionFailed(Box<dyn std::error::Error + Send + Sync>),
     /// Rate limited.
     #[error("rate limited: {0}")]
     RateLimited(Box<dyn std::error::Error + Send + Sync>),
@@ -263,12 +950,16 @@ where
 }
 
 /// Macro to facilitate implementation of `From` for DynamoDB service errors.
+///
+/// Additional `Variant => TableErrorVariant` pairs map specific service errors
+/// to specific variants of [`TableError`].
 macro_rules! impl_from_dynamodb_service_error {
-    ($error_type:ty) => {
+    ($error_type:ty $(, $variant:ident => $table_error:ident)* $(,)?) => {
         impl From<$error_type> for TableError {
             fn from(e: $error_type) -> Self {
                 use $error_type::*;
                 match e {
+                    $($variant(_) => TableError::$table_error(e.into()),)*
                     ProvisionedThroughputExceededException(_) |
                     RequestLimitExceeded(_) |
                     ThrottlingException(_) => TableError::RateLimited(e.into()),
@@ -283,6 +974,50 @@ macro_rules! impl_from_dynamodb_service_error {
 
 impl_from_dynamodb_service_error!(GetItemError);
 impl_from_dynamodb_service_error!(QueryError);
+impl_from_dynamodb_service_error!(
+    UpdateItemError,
+    ConditionalCheckFailedException => ConditionFailed,
+);
+
+impl From<TransactWriteItemsError> for TableError {
+    fn from(e: TransactWriteItemsError) -> Self {
+        use TransactWriteItemsError::*;
+        match e {
+            TransactionCanceledException(ref ex) => {
+                let has_reason = |code: &str| {
+                    ex.cancellation_reasons().iter().any(|r| r.code() == Some(code))
+                };
+                if has_reason("ConditionalCheckFailed") {
+                    TableError::ConditionFailed(e.into())
+                } else if has_reason("ThrottlingError") ||
+                    has_reason("ProvisionedThroughputExceeded")
+                {
+                    TableError::RateLimited(e.into())
+                } else {
+                    TableError::InternalError(e.into())
+                }
+            }
+            ProvisionedThroughputExceededException(_) |
+            RequestLimitExceeded(_) |
+            ThrottlingException(_) => TableError::RateLimited(e.into()),
+            InvalidEndpointException(_) |
+            ResourceNotFoundException(_) => TableError::BadConfiguration(e.into()),
+            _ => TableError::InternalError(e.into()),
+        }
+    }
+}
+
+/// Returns if the `i`-th item of a cancelled transaction failed its
+/// condition.
+fn is_condition_check_failed_at<R>(e: &SdkError<TransactWriteItemsError, R>, i: usize) -> bool {
+    match e.as_service_error() {
+        Some(TransactWriteItemsError::TransactionCanceledException(ex)) => ex
+            .cancellation_reasons()
+            .get(i)
+            .is_some_and(|r| r.code() == Some("ConditionalCheckFailed")),
+        _ => false,
+    }
+}
 
 #[pin_project]
 struct PaginationStreamExt<T>(PaginationStream<T>);
@@ -296,3 +1031,182 @@ impl<T> Stream for PaginationStreamExt<T> {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_business_record() -> NewBusinessRecord {
+        NewBusinessRecordBuilder::default()
+            .record_id("record")
+            .dog_id("0123456789abcdef")
+            .business_type(BusinessType::Pee)
+            .location(GeolocationCoordinates {
+                longitude: 139.7671,
+                latitude: 35.6812,
+            })
+            .timestamp(1_700_000_000)
+            .advocacy_setting(AdvocacySetting::PublishAnonymized)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_new_business_record_private_item() {
+        let item = new_business_record().private_item();
+        assert_eq!(item["pk"], AttributeValue::S("record".into()));
+        assert_eq!(item["sk"], AttributeValue::S("private".into()));
+        assert_eq!(item["dogId"], AttributeValue::S("0123456789abcdef".into()));
+        assert_eq!(item["businessType"], AttributeValue::S("pee".into()));
+        assert_eq!(item["longitude"], AttributeValue::N("139.7671000000".into()));
+        assert_eq!(item["latitude"], AttributeValue::N("35.6812000000".into()));
+        assert_eq!(item["timestamp"], AttributeValue::N("1700000000".into()));
+        assert_eq!(item["tileAtZ0"], AttributeValue::S("dog#0123456789abcdef#0/0".into()));
+        assert_eq!(item["tileAtZ10"], AttributeValue::S("dog#0123456789abcdef#909/403".into()));
+        assert!(item.contains_key("tileAtZ22"));
+        assert!(!item.contains_key("maskedDogId"));
+        assert!(!item.contains_key("isAdvocated"));
+    }
+
+    #[test]
+    fn test_new_business_record_public_item() {
+        let item = new_business_record().public_item().unwrap();
+        assert_eq!(item["pk"], AttributeValue::S("record".into()));
+        assert_eq!(item["sk"], AttributeValue::S("public".into()));
+        assert_eq!(item["maskedDogId"], AttributeValue::S("89abcdef".into()));
+        assert_eq!(item["isAdvocated"], AttributeValue::Bool(false));
+        assert_eq!(item["timestamp"], AttributeValue::N("472222".into()));
+        assert_eq!(item["tileAtZ0"], AttributeValue::S("public#0/0".into()));
+        assert_eq!(item["tileAtZ10"], AttributeValue::S("public#909/403".into()));
+        assert!(item.contains_key("tileAtZ22"));
+        assert!(!item.contains_key("dogId"));
+    }
+
+    #[test]
+    fn test_new_business_record_public_item_advocacy_setting() {
+        let mut record = new_business_record();
+        record.advocacy_setting = AdvocacySetting::Publish;
+        assert_eq!(record.public_item().unwrap()["isAdvocated"], AttributeValue::Bool(true));
+
+        record.advocacy_setting = AdvocacySetting::DoNotPublish;
+        assert!(record.public_item().is_none());
+    }
+
+    #[test]
+    fn test_resource_table_parse_advocacy_setting() {
+        let item = HashMap::from([
+            ("advocacySetting".to_string(), AttributeValue::S("do-not-publish".into())),
+        ]);
+        assert_eq!(
+            ResourceTable::parse_advocacy_setting(&item).unwrap(),
+            AdvocacySetting::DoNotPublish,
+        );
+
+        // missing setting falls back to the default
+        assert_eq!(
+            ResourceTable::parse_advocacy_setting(&HashMap::new()).unwrap(),
+            AdvocacySetting::PublishAnonymized,
+        );
+
+        let item = HashMap::from([
+            ("advocacySetting".to_string(), AttributeValue::S("everyone".into())),
+        ]);
+        assert!(matches!(
+            ResourceTable::parse_advocacy_setting(&item),
+            Err(TableError::ItemError(_)),
+        ));
+    }
+
+    #[test]
+    fn test_continuation_token_key_round_trip() {
+        let key = HashMap::from([
+            ("pk".to_string(), AttributeValue::S("record".into())),
+            ("sk".to_string(), AttributeValue::S("private".into())),
+            ("dogId".to_string(), AttributeValue::S("dog".into())),
+            ("timestamp".to_string(), AttributeValue::N("1700000000".into())),
+        ]);
+        let token = ContinuationToken::from_key(&key).unwrap();
+        assert!(!token.as_str().contains(['+', '/', '=', '#']));
+        assert_eq!(token.to_key("dog").unwrap(), key);
+
+        assert!(matches!(
+            ContinuationToken::from_key(&HashMap::new()),
+            Err(TableError::ItemError(_)),
+        ));
+    }
+
+    #[test]
+    fn test_continuation_token_from_business_record() {
+        let record = BusinessRecordBuilder::default()
+            .record_id("record")
+            .dog_id(Some("dog".to_string()))
+            .business_type(BusinessType::Pee)
+            .location(GeolocationCoordinates {
+                longitude: 0.0,
+                latitude: 0.0,
+            })
+            .timestamp(1_700_000_000)
+            .build()
+            .unwrap();
+        let key = ContinuationToken::from(&record).to_key("dog").unwrap();
+        assert_eq!(key["pk"], AttributeValue::S("record".into()));
+        assert_eq!(key["timestamp"], AttributeValue::N("1700000000".into()));
+    }
+
+    #[test]
+    fn test_continuation_token_invalid() {
+        for token in ["", "!!!", "bm8tc2VwYXJhdG9y", "eDEyMyNyZWNvcmQ"] {
+            let token: ContinuationToken = serde_json::from_value(token.into()).unwrap();
+            assert!(matches!(
+                token.to_key("dog"),
+                Err(TableError::InvalidContinuationToken(_)),
+            ));
+        }
+    }
+
+    #[test]
+    fn test_record_filter_validate() {
+        let filter = |since, until| RecordFilter {
+            since,
+            until,
+            business_type: None,
+        };
+        assert!(filter(None, None).validate().is_ok());
+        assert!(filter(Some(1_700_000_000), None).validate().is_ok());
+        assert!(filter(None, Some(1_700_000_000)).validate().is_ok());
+        assert!(filter(Some(1_700_000_000), Some(1_700_000_000)).validate().is_ok());
+        assert!(filter(Some(1_700_000_000), Some(1_700_003_600)).validate().is_ok());
+        let message = filter(Some(1_700_003_600), Some(1_700_000_000)).validate().unwrap_err();
+        assert!(message.contains("invalid time window"));
+    }
+
+    #[test]
+    fn test_new_business_record_from_private_record() {
+        let private_record = BusinessRecordBuilder::default()
+            .record_id("record")
+            .dog_id(Some("0123456789abcdef".to_string()))
+            .business_type(BusinessType::Poo)
+            .location(GeolocationCoordinates {
+                longitude: 139.7671,
+                latitude: 35.6812,
+            })
+            .timestamp(1_700_000_000)
+            .build()
+            .unwrap();
+        let record = NewBusinessRecord::from_private_record(
+            &private_record,
+            AdvocacySetting::Publish,
+        ).unwrap();
+        assert_eq!(record.record_id, "record");
+        assert_eq!(record.dog_id, "0123456789abcdef");
+        assert_eq!(record.timestamp, 1_700_000_000);
+        assert_eq!(record.advocacy_setting, AdvocacySetting::Publish);
+
+        let mut public_record = private_record;
+        public_record.dog_id = None;
+        assert!(matches!(
+            NewBusinessRecord::from_private_record(&public_record, AdvocacySetting::Publish),
+            Err(TableError::ItemError(_)),
+        ));
+    }
+}
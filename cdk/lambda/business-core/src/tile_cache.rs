@@ -0,0 +1,531 @@
+//! Cache of rendered map tiles.
+//!
+//! A rendered tile is cached under the tile coordinates and a variant that
+//! distinguishes representations of the same tile; e.g., filters, format, and
+//! content encoding.
+//!
+//! Invalidating a tile stamps the tile with the time of the invalidation
+//! instead of removing the variants of the tile. A cached tile is valid only
+//! if it started rendering after the last invalidation of the tile, so a
+//! render that read business records before an update never revives a stale
+//! tile even if it finishes after the invalidation.
+//! A cached tile also expires after [`MAX_TILE_AGE_MILLIS`] regardless of
+//! invalidations, so that a lost invalidation does not last long.
+
+use aws_sdk_s3::{error::SdkError, primitives::ByteStream};
+use futures::future::try_join_all;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mvt::TileCoordinates;
+use crate::types::GeolocationCoordinates;
+use crate::web_mercator::MAX_ZOOM;
+
+/// Margin around a tile to determine affected tiles.
+///
+/// Ratio to the edge length of a tile. Must cover the buffer of tiles so that
+/// a new record within the buffer of a neighboring tile also invalidates the
+/// neighboring tile.
+pub const INVALIDATION_MARGIN: f64 = 0.125;
+
+/// Default prefix of S3 object keys of cached tiles.
+pub const DEFAULT_KEY_PREFIX: &str = "tiles/";
+
+/// Maximum age of a cached tile in milliseconds.
+pub const MAX_TILE_AGE_MILLIS: i64 = 15 * 60 * 1000;
+
+/// Period in milliseconds after an invalidation during which rendered tiles
+/// are still regarded as stale.
+///
+/// Absorbs the clock skew between Lambda functions and the propagation delay
+/// of global secondary indexes, which may return records before the update
+/// for a moment.
+pub const INVALIDATION_GRACE_PERIOD_MILLIS: i64 = 2_000;
+
+/// Name of the invalidation marker of a tile.
+///
+/// Reserved in the namespace of variants.
+pub const INVALIDATION_MARKER_NAME: &str = "invalidated";
+
+/// Name of the S3 object metadata that stores the ETag of a tile.
+const ETAG_METADATA_NAME: &str = "tile-etag";
+
+/// Name of the S3 object metadata that stores the time when a tile started
+/// rendering.
+const RENDERED_AT_METADATA_NAME: &str = "tile-rendered-at";
+
+/// Name of the S3 object metadata that stores the time of the last
+/// invalidation of a tile.
+const INVALIDATED_AT_METADATA_NAME: &str = "tile-invalidated-at";
+
+/// Maximum number of tiles invalidated concurrently.
+const MAX_CONCURRENT_INVALIDATIONS: usize = 32;
+
+/// Cache of rendered map tiles.
+pub trait TileCache {
+    /// Returns the tile cached under a given key.
+    ///
+    /// Returns `None` if no tile is cached, or the cached tile is stale; i.e.,
+    /// it started rendering before the last invalidation of the tile, or it
+    /// is older than [`MAX_TILE_AGE_MILLIS`].
+    fn get(
+        &self,
+        key: &TileCacheKey,
+    ) -> impl Future<Output = Result<Option<CachedTile>, TileCacheError>> + Send;
+
+    /// Caches a tile under a given key.
+    fn put(
+        &self,
+        key: &TileCacheKey,
+        tile: CachedTile,
+    ) -> impl Future<Output = Result<(), TileCacheError>> + Send;
+
+    /// Invalidates all the variants of given tiles.
+    ///
+    /// Must be called after the business records in the tiles are updated.
+    fn invalidate(
+        &self,
+        tiles: &[TileCoordinates],
+    ) -> impl Future<Output = Result<(), TileCacheError>> + Send;
+}
+
+/// Key of a cached tile.
+#[derive(Clone, Debug)]
+pub struct TileCacheKey {
+    /// Coordinates of the tile.
+    pub coordinates: TileCoordinates,
+
+    /// Variant of the tile.
+    ///
+    /// Must not contain slashes (`/`) nor be [`INVALIDATION_MARKER_NAME`].
+    pub variant: String,
+}
+
+impl TileCacheKey {
+    /// Creates a new [`TileCacheKey`].
+    pub fn new(coordinates: TileCoordinates, variant: impl Into<String>) -> Self {
+        Self {
+            coordinates,
+            variant: variant.into(),
+        }
+    }
+
+    /// Returns the path of the cached tile; i.e., "{z}/{x}/{y}/{variant}".
+    pub fn path(&self) -> String {
+        format!("{}{}", tile_path_prefix(&self.coordinates), self.variant)
+    }
+}
+
+/// Returns the path prefix shared by all the variants of a given tile; i.e.,
+/// "{z}/{x}/{y}/".
+pub fn tile_path_prefix(coordinates: &TileCoordinates) -> String {
+    format!("{}/{}/{}/", coordinates.zoom(), coordinates.x(), coordinates.y())
+}
+
+/// Cached tile.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CachedTile {
+    /// ETag of the tile.
+    pub etag: String,
+
+    /// Rendered tile. May be empty.
+    pub data: Vec<u8>,
+
+    /// Time when the tile started rendering; i.e., before querying business
+    /// records.
+    ///
+    /// Represented as the number of milliseconds elapsed since 00:00:00 on
+    /// January 1, 1970 UTC.
+    pub rendered_at: i64,
+}
+
+impl CachedTile {
+    /// Returns if the tile is still valid at `now` after the last
+    /// invalidation at `invalidated_at`.
+    ///
+    /// Times are in milliseconds since the Unix epoch.
+    pub fn is_valid_at(&self, now: i64, invalidated_at: Option<i64>) -> bool {
+        if now - self.rendered_at > MAX_TILE_AGE_MILLIS {
+            return false;
+        }
+        match invalidated_at {
+            Some(invalidated_at) => {
+                self.rendered_at > invalidated_at + INVALIDATION_GRACE_PERIOD_MILLIS
+            }
+            None => true,
+        }
+    }
+}
+
+/// Returns the current time in milliseconds since the Unix epoch.
+pub fn current_time_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// Returns the tiles at all the zoom levels that a business record at a given
+/// location may affect.
+///
+/// Includes neighboring tiles if the location is within `margin` around them.
+/// `margin` is the ratio to the edge length of a tile and must be less than
+/// 1.0.
+pub fn tiles_affected_by(
+    location: &GeolocationCoordinates,
+    margin: f64,
+) -> Vec<TileCoordinates> {
+    (0..=MAX_ZOOM)
+        .flat_map(|zoom| {
+            TileCoordinates::from_location(location, zoom)
+                // tiles next to the tile
+                .overlapping_tiles_at(zoom, 1.0)
+                .into_iter()
+                .filter(|tile| tile.contains_location_with_margin(location, margin))
+        })
+        .collect()
+}
+
+/// Error on tile cache operations.
+#[derive(Debug, thiserror::Error)]
+pub enum TileCacheError {
+    /// Error in the underlying storage.
+    #[error("storage error: {0}")]
+    StorageError(Box<dyn std::error::Error + Send + Sync>),
+    /// Cached tile is broken.
+    #[error("invalid cache entry: {0}")]
+    InvalidEntry(String),
+}
+
+impl<E, R> From<SdkError<E, R>> for TileCacheError
+where
+    E: std::error::Error + Send + Sync + 'static,
+    R: std::fmt::Debug + Send + Sync + 'static,
+{
+    fn from(e: SdkError<E, R>) -> Self {
+        TileCacheError::StorageError(e.into())
+    }
+}
+
+/// Tile cache backed by an S3 bucket.
+///
+/// A tile is stored as an object at "{key_prefix}{z}/{x}/{y}/{variant}".
+/// The time of the last invalidation of a tile is stored as an empty object at
+/// "{key_prefix}{z}/{x}/{y}/{INVALIDATION_MARKER_NAME}".
+#[derive(Clone, Debug)]
+pub struct S3TileCache {
+    /// S3 client.
+    client: aws_sdk_s3::Client,
+
+    /// Name of the bucket.
+    bucket_name: String,
+
+    /// Prefix of object keys.
+    key_prefix: String,
+}
+
+impl S3TileCache {
+    /// Creates a new [`S3TileCache`].
+    pub fn new(
+        client: aws_sdk_s3::Client,
+        bucket_name: impl Into<String>,
+        key_prefix: impl Into<String>,
+    ) -> Self {
+        Self {
+            client,
+            bucket_name: bucket_name.into(),
+            key_prefix: key_prefix.into(),
+        }
+    }
+
+    /// Returns the S3 object key of a given path.
+    fn object_key(&self, path: &str) -> String {
+        format!("{}{}", self.key_prefix, path)
+    }
+
+    /// Returns the time of the last invalidation of a given tile.
+    async fn get_invalidated_at(
+        &self,
+        coordinates: &TileCoordinates,
+    ) -> Result<Option<i64>, TileCacheError> {
+        let res = self.client
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(self.object_key(&invalidation_marker_path(coordinates)))
+            .send()
+            .await;
+        let res = match res {
+            Ok(res) => res,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) =>
+                return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let invalidated_at = parse_time_metadata(res.metadata(), INVALIDATED_AT_METADATA_NAME)?;
+        Ok(Some(invalidated_at))
+    }
+
+    /// Stamps a given tile with the time of an invalidation.
+    async fn put_invalidated_at(
+        &self,
+        coordinates: &TileCoordinates,
+        invalidated_at: i64,
+    ) -> Result<(), TileCacheError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(self.object_key(&invalidation_marker_path(coordinates)))
+            .metadata(INVALIDATED_AT_METADATA_NAME, invalidated_at.to_string())
+            .send()
+            .await?;
+        Ok(())
+    }
+}
+
+impl TileCache for S3TileCache {
+    async fn get(&self, key: &TileCacheKey) -> Result<Option<CachedTile>, TileCacheError> {
+        let (res, invalidated_at) = futures::join!(
+            self.client
+                .get_object()
+                .bucket(&self.bucket_name)
+                .key(self.object_key(&key.path()))
+                .send(),
+            self.get_invalidated_at(&key.coordinates),
+        );
+        let res = match res {
+            Ok(res) => res,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) =>
+                return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let invalidated_at = invalidated_at?;
+        let etag = res
+            .metadata()
+            .and_then(|metadata| metadata.get(ETAG_METADATA_NAME))
+            .cloned()
+            .ok_or_else(|| TileCacheError::InvalidEntry("ETag is missing".to_string()))?;
+        let rendered_at = parse_time_metadata(res.metadata(), RENDERED_AT_METADATA_NAME)?;
+        let data = res.body
+            .collect()
+            .await
+            .map_err(|e| TileCacheError::StorageError(e.into()))?
+            .to_vec();
+        let tile = CachedTile { etag, data, rendered_at };
+        Ok(Some(tile).filter(|tile| tile.is_valid_at(current_time_millis(), invalidated_at)))
+    }
+
+    async fn put(&self, key: &TileCacheKey, tile: CachedTile) -> Result<(), TileCacheError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(self.object_key(&key.path()))
+            .metadata(ETAG_METADATA_NAME, tile.etag)
+            .metadata(RENDERED_AT_METADATA_NAME, tile.rendered_at.to_string())
+            .body(ByteStream::from(tile.data))
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn invalidate(&self, tiles: &[TileCoordinates]) -> Result<(), TileCacheError> {
+        // a single small object per tile; stale variants expire by the
+        // lifecycle of the bucket
+        let invalidated_at = current_time_millis();
+        for chunk in tiles.chunks(MAX_CONCURRENT_INVALIDATIONS) {
+            try_join_all(chunk.iter().map(|tile| self.put_invalidated_at(tile, invalidated_at)))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the path of the invalidation marker of a given tile; i.e.,
+/// "{z}/{x}/{y}/{INVALIDATION_MARKER_NAME}".
+fn invalidation_marker_path(coordinates: &TileCoordinates) -> String {
+    format!("{}{}", tile_path_prefix(coordinates), INVALIDATION_MARKER_NAME)
+}
+
+/// Parses a time in S3 object metadata.
+fn parse_time_metadata(
+    metadata: Option<&HashMap<String, String>>,
+    name: &str,
+) -> Result<i64, TileCacheError> {
+    metadata
+        .and_then(|metadata| metadata.get(name))
+        .ok_or_else(|| TileCacheError::InvalidEntry(format!("{name} is missing")))?
+        .parse()
+        .map_err(|e| TileCacheError::InvalidEntry(format!("invalid {name}: {e}")))
+}
+
+/// Tile cache in memory.
+///
+/// Intended for tests and local runs.
+#[derive(Debug, Default)]
+pub struct InMemoryTileCache {
+    /// Cached tiles associated with their paths.
+    tiles: Mutex<HashMap<String, CachedTile>>,
+
+    /// Times of the last invalidations associated with the path prefixes of
+    /// tiles.
+    invalidations: Mutex<HashMap<String, i64>>,
+}
+
+impl InMemoryTileCache {
+    /// Creates an empty [`InMemoryTileCache`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of cached tiles.
+    pub fn len(&self) -> usize {
+        self.tiles.lock().unwrap().len()
+    }
+
+    /// Returns if no tile is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl TileCache for InMemoryTileCache {
+    async fn get(&self, key: &TileCacheKey) -> Result<Option<CachedTile>, TileCacheError> {
+        let invalidated_at = self.invalidations
+            .lock()
+            .unwrap()
+            .get(&tile_path_prefix(&key.coordinates))
+            .copied();
+        Ok(self.tiles
+            .lock()
+            .unwrap()
+            .get(&key.path())
+            .filter(|tile| tile.is_valid_at(current_time_millis(), invalidated_at))
+            .cloned())
+    }
+
+    async fn put(&self, key: &TileCacheKey, tile: CachedTile) -> Result<(), TileCacheError> {
+        self.tiles.lock().unwrap().insert(key.path(), tile);
+        Ok(())
+    }
+
+    async fn invalidate(&self, tiles: &[TileCoordinates]) -> Result<(), TileCacheError> {
+        let invalidated_at = current_time_millis();
+        let mut invalidations = self.invalidations.lock().unwrap();
+        for tile in tiles {
+            invalidations.insert(tile_path_prefix(tile), invalidated_at);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    const TOKYO_STATION: GeolocationCoordinates = GeolocationCoordinates {
+        longitude: 139.7670506677,
+        latitude: 35.6814709332,
+    };
+
+    fn tile(zoom: u32, x: u32, y: u32) -> TileCoordinates {
+        TileCoordinates::new(zoom, x, y).unwrap()
+    }
+
+    fn cached_tile(etag: &str, rendered_at: i64) -> CachedTile {
+        CachedTile {
+            etag: etag.to_string(),
+            data: vec![1, 2, 3],
+            rendered_at,
+        }
+    }
+
+    #[test]
+    fn test_tile_cache_key_path() {
+        let key = TileCacheKey::new(tile(16, 58211, 25806), "mvt.br");
+        assert_eq!(key.path(), "16/58211/25806/mvt.br");
+        assert_eq!(tile_path_prefix(&tile(0, 0, 0)), "0/0/0/");
+    }
+
+    #[test]
+    fn test_tiles_affected_by() {
+        // without margin, one tile per zoom level
+        let tiles = tiles_affected_by(&TOKYO_STATION, 0.0);
+        assert_eq!(tiles.len(), (MAX_ZOOM + 1) as usize);
+        assert!(tiles.iter().all(|t| t.contains_location(&TOKYO_STATION)));
+
+        // with margin, neighboring tiles are included at fine zoom levels
+        let tiles = tiles_affected_by(&TOKYO_STATION, INVALIDATION_MARGIN);
+        assert!(tiles.len() > (MAX_ZOOM + 1) as usize);
+        assert!(tiles.iter().all(|t| t.contains_location_with_margin(&TOKYO_STATION, INVALIDATION_MARGIN)));
+        for zoom in 0..=MAX_ZOOM {
+            let n = tiles.iter().filter(|t| t.zoom() == zoom).count();
+            assert!((1..=4).contains(&n), "zoom {zoom}: {n} tiles");
+        }
+        assert!(tiles.iter().any(|t| (t.zoom(), t.x(), t.y()) == (16, 58211, 25806)));
+    }
+
+    #[test]
+    fn test_cached_tile_is_valid_at() {
+        let rendered_at = 1_755_317_141_000;
+        let tile = cached_tile("etag", rendered_at);
+        assert!(tile.is_valid_at(rendered_at + 1_000, None));
+        // expired
+        assert!(tile.is_valid_at(rendered_at + MAX_TILE_AGE_MILLIS, None));
+        assert!(!tile.is_valid_at(rendered_at + MAX_TILE_AGE_MILLIS + 1, None));
+        // rendering started before the invalidation
+        assert!(!tile.is_valid_at(rendered_at + 1_000, Some(rendered_at + 500)));
+        // rendering started within the grace period after the invalidation
+        assert!(!tile.is_valid_at(
+            rendered_at + 1_000,
+            Some(rendered_at - INVALIDATION_GRACE_PERIOD_MILLIS),
+        ));
+        // rendering started after the grace period
+        assert!(tile.is_valid_at(
+            rendered_at + 1_000,
+            Some(rendered_at - INVALIDATION_GRACE_PERIOD_MILLIS - 1),
+        ));
+    }
+
+    #[test]
+    fn test_in_memory_tile_cache() {
+        let cache = InMemoryTileCache::new();
+        let key_1 = TileCacheKey::new(tile(16, 58211, 25806), "mvt.br");
+        let key_2 = TileCacheKey::new(tile(16, 58211, 25806), "geojson.identity");
+        let key_3 = TileCacheKey::new(tile(16, 58212, 25806), "mvt.br");
+
+        let now = current_time_millis();
+        assert_eq!(block_on(cache.get(&key_1)).unwrap(), None);
+        block_on(cache.put(&key_1, cached_tile("etag_1", now))).unwrap();
+        block_on(cache.put(&key_2, cached_tile("etag_2", now))).unwrap();
+        block_on(cache.put(&key_3, cached_tile("etag_3", now))).unwrap();
+        assert_eq!(cache.len(), 3);
+        assert_eq!(block_on(cache.get(&key_1)).unwrap(), Some(cached_tile("etag_1", now)));
+
+        // invalidates all the variants of the tile
+        block_on(cache.invalidate(&[tile(16, 58211, 25806)])).unwrap();
+        assert_eq!(block_on(cache.get(&key_1)).unwrap(), None);
+        assert_eq!(block_on(cache.get(&key_2)).unwrap(), None);
+        assert_eq!(block_on(cache.get(&key_3)).unwrap(), Some(cached_tile("etag_3", now)));
+
+        // a render that started before the invalidation does not revive the
+        // stale tile even if it is cached after the invalidation
+        block_on(cache.put(&key_1, cached_tile("stale", now))).unwrap();
+        assert_eq!(block_on(cache.get(&key_1)).unwrap(), None);
+
+        // a render that started after the invalidation is cached
+        let later = current_time_millis() + INVALIDATION_GRACE_PERIOD_MILLIS + 1;
+        block_on(cache.put(&key_1, cached_tile("fresh", later))).unwrap();
+        assert_eq!(block_on(cache.get(&key_1)).unwrap(), Some(cached_tile("fresh", later)));
+    }
+
+    #[test]
+    fn test_in_memory_tile_cache_expiration() {
+        let cache = InMemoryTileCache::new();
+        let key = TileCacheKey::new(tile(16, 58211, 25806), "mvt.br");
+        let old = current_time_millis() - MAX_TILE_AGE_MILLIS - 1_000;
+        block_on(cache.put(&key, cached_tile("etag", old))).unwrap();
+        assert_eq!(block_on(cache.get(&key)).unwrap(), None);
+    }
+}
@@ -16,6 +16,19 @@ pub struct BusinessRecord {
     /// Missing in public records.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dog_id: Option<String>,
+    /// Semi-unique ID of the dog who carried out the business.
+    ///
+    /// Missing in private records. Never serialized, because it is only
+    /// intended to calculate the anonymity level.
+    #[serde(skip)]
+    #[builder(default)]
+    pub masked_dog_id: Option<String>,
+    /// Whether the dog who carried out the business is an advocate of the app.
+    ///
+    /// Missing in private records. Never serialized.
+    #[serde(skip)]
+    #[builder(default)]
+    pub is_advocated: Option<bool>,
     /// Type of the business.
     pub business_type: BusinessType,
     /// Location of the business.
@@ -47,6 +60,75 @@ impl Display for BusinessType {
     }
 }
 
+impl std::str::FromStr for BusinessType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pee" => Ok(BusinessType::Pee),
+            "poo" => Ok(BusinessType::Poo),
+            _ => Err(format!("invalid business type: {s}")),
+        }
+    }
+}
+
+/// Advocacy setting of a dog.
+///
+/// Determines how business records of the dog appear on the public map.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum AdvocacySetting {
+    /// Publishes business records as an advocate of the app.
+    ///
+    /// The records are visible at any anonymity level.
+    #[serde(rename = "publish")]
+    Publish,
+    /// Publishes business records anonymously.
+    ///
+    /// The records are visible only if the anonymity level is high enough.
+    /// Default because being an advocate is opt-in.
+    #[default]
+    #[serde(rename = "publish-anonymized")]
+    PublishAnonymized,
+    /// Never publishes business records.
+    #[serde(rename = "do-not-publish")]
+    DoNotPublish,
+}
+
+impl AdvocacySetting {
+    /// Returns if business records are published.
+    pub fn is_published(&self) -> bool {
+        !matches!(self, AdvocacySetting::DoNotPublish)
+    }
+
+    /// Returns if published business records are advocated.
+    pub fn is_advocated(&self) -> bool {
+        matches!(self, AdvocacySetting::Publish)
+    }
+}
+
+impl Display for AdvocacySetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            AdvocacySetting::Publish => write!(f, "publish"),
+            AdvocacySetting::PublishAnonymized => write!(f, "publish-anonymized"),
+            AdvocacySetting::DoNotPublish => write!(f, "do-not-publish"),
+        }
+    }
+}
+
+impl std::str::FromStr for AdvocacySetting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "publish" => Ok(AdvocacySetting::Publish),
+            "publish-anonymized" => Ok(AdvocacySetting::PublishAnonymized),
+            "do-not-publish" => Ok(AdvocacySetting::DoNotPublish),
+            _ => Err(format!("invalid advocacy setting: {s}")),
+        }
+    }
+}
+
 /// Coordinates of a geographic location.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct GeolocationCoordinates {
@@ -91,6 +173,31 @@ mod tests {
         assert!(matches!(business_type, BusinessType::Poo));
     }
 
+    #[test]
+    fn test_business_type_from_str() {
+        assert!(matches!("pee".parse::<BusinessType>(), Ok(BusinessType::Pee)));
+        assert!(matches!("poo".parse::<BusinessType>(), Ok(BusinessType::Poo)));
+        assert!("Pee".parse::<BusinessType>().is_err());
+        assert!("".parse::<BusinessType>().is_err());
+    }
+
+    #[test]
+    fn test_advocacy_setting_from_str() {
+        for setting in [
+            AdvocacySetting::Publish,
+            AdvocacySetting::PublishAnonymized,
+            AdvocacySetting::DoNotPublish,
+        ] {
+            assert_eq!(setting.to_string().parse::<AdvocacySetting>(), Ok(setting));
+            assert_eq!(
+                serde_json::to_string(&setting).unwrap(),
+                format!("\"{setting}\""),
+            );
+        }
+        assert!("Publish".parse::<AdvocacySetting>().is_err());
+        assert!("".parse::<AdvocacySetting>().is_err());
+    }
+
     #[test]
     fn test_deserialize_geolocation_coordinates() {
         const EPSILON: f64 = 1e-11; // guarantees 10-digit precision
@@ -124,6 +231,8 @@ mod tests {
         let input = BusinessRecord {
             record_id: "012345678901234567890".to_string(),
             dog_id: Some("0123456789abcdefghijk".to_string()),
+            masked_dog_id: None,
+            is_advocated: None,
             business_type: BusinessType::Pee,
             location: GeolocationCoordinates {
                 longitude: 139.7650506677,
@@ -166,6 +275,8 @@ mod tests {
         let input = BusinessRecord {
             record_id: "abcdefghij01234567890".to_string(),
             dog_id: None,
+            masked_dog_id: Some("klmnopqrstu".to_string()),
+            is_advocated: Some(true),
             business_type: BusinessType::Poo,
             location: GeolocationCoordinates {
                 longitude: -58.381645119,
@@ -181,6 +292,8 @@ mod tests {
             "abcdefghij01234567890",
         );
         assert!(properties.get("dogId").is_none());
+        assert!(properties.get("maskedDogId").is_none());
+        assert!(properties.get("isAdvocated").is_none());
         assert_eq!(
             properties.get("businessType").unwrap().as_str().unwrap(),
             "poo",
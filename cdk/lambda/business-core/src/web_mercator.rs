@@ -121,7 +121,7 @@ pub const fn two_pi_div_tiles_per_edge_at_zoom(z: u32) -> f64 {
 /// digits in `f64`.
 #[inline]
 pub const fn power_of_two(n: u32) -> f64 {
-    assert!(n + 1 < std::f64::MANTISSA_DIGITS);
+    assert!(n + 1 < f64::MANTISSA_DIGITS);
     (1u64 << n) as f64
 }
 
@@ -42,7 +42,7 @@ fn two_pi_div_tiles_per_edge_at_zoom_open(z: u32) -> f64 {
 
 #[inline]
 const fn power_of_two(n: u32) -> f64 {
-    assert!(n + 1 < std::f64::MANTISSA_DIGITS);
+    assert!(n + 1 < f64::MANTISSA_DIGITS);
     (1 << n) as f64
 }
 
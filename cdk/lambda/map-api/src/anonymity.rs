@@ -0,0 +1,289 @@
+//! Anonymity of public business records.
+//!
+//! Public business records are anonymized by k-anonymity: a business record
+//! in a map tile is visible only if at least k distinct dogs carried out
+//! businesses in the same cell. A cell is a square region in a tile.
+//!
+//! Cells are evaluated from the finest to the coarsest:
+//! - if a cell at the finest level satisfies the anonymity level, the records
+//!   in the cell keep their exact locations
+//! - if a cell at a coarser level satisfies the anonymity level, the records
+//!   in the cell are generalized; i.e., moved to the center of the cell
+//! - if even the whole tile does not satisfy the anonymity level, the records
+//!   are dropped
+//!
+//! Records carried out by advocates of the app are visible at any anonymity
+//! level.
+
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
+
+use business_core::{
+    mvt::TileCoordinates,
+    types::{BusinessRecord, GeolocationCoordinates},
+    web_mercator::{normalized_x_from_longitude, normalized_y_from_latitude},
+};
+
+/// Filter that applies k-anonymity to business records.
+#[derive(Clone, Debug)]
+pub struct AnonymityFilter {
+    /// Minimum number of distinct dogs in a cell; i.e., "k".
+    k: usize,
+
+    /// Level of the finest cells.
+    ///
+    /// A tile is divided into `2^max_cell_level` × `2^max_cell_level` cells
+    /// at the finest level.
+    max_cell_level: u32,
+}
+
+impl AnonymityFilter {
+    /// Maximum value of `max_cell_level`.
+    ///
+    /// Cells finer than this are meaningless in a tile of
+    /// [`TILE_EXTENT`][crate::mvt::TILE_EXTENT].
+    pub const MAX_CELL_LEVEL: u32 = 12;
+
+    /// Creates a new [`AnonymityFilter`].
+    ///
+    /// `k` is the minimum number of distinct dogs in a cell.
+    ///
+    /// `max_cell_level` is clamped to [`AnonymityFilter::MAX_CELL_LEVEL`].
+    pub fn new(k: usize, max_cell_level: u32) -> Self {
+        Self {
+            k,
+            max_cell_level: max_cell_level.min(Self::MAX_CELL_LEVEL),
+        }
+    }
+
+    /// Filters business records to be shown in a tile at given coordinates.
+    ///
+    /// Records may come from a tile at a lower zoom level that covers the
+    /// tile at `coordinates`. Records outside of the tile are evaluated in
+    /// cells outside of the tile.
+    ///
+    /// Preserves the order of the remaining records.
+    pub fn filter(
+        &self,
+        coordinates: &TileCoordinates,
+        records: Vec<BusinessRecord>,
+    ) -> Vec<BusinessRecord> {
+        let mut outcomes: Vec<Outcome> = records
+            .iter()
+            .map(|record| if record.is_advocated == Some(true) {
+                Outcome::Keep
+            } else {
+                Outcome::Pending
+            })
+            .collect();
+
+        for level in (0..=self.max_cell_level).rev() {
+            let cell_zoom = coordinates.zoom() + level;
+            // groups pending records by cell
+            let mut cells: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+            for (i, record) in records.iter().enumerate() {
+                if matches!(outcomes[i], Outcome::Pending) {
+                    cells
+                        .entry(cell_at_zoom(&record.location, cell_zoom))
+                        .or_default()
+                        .push(i);
+                }
+            }
+            // resolves cells that satisfy the anonymity level
+            for (cell, indices) in cells {
+                let num_dogs = indices
+                    .iter()
+                    .filter_map(|&i| anonymity_key(&records[i]))
+                    .collect::<HashSet<_>>()
+                    .len();
+                if num_dogs >= self.k {
+                    let outcome = if level == self.max_cell_level {
+                        Outcome::Keep
+                    } else {
+                        Outcome::Generalize(cell_center_at_zoom(cell, cell_zoom))
+                    };
+                    for i in indices {
+                        outcomes[i] = outcome.clone();
+                    }
+                }
+            }
+        }
+
+        records
+            .into_iter()
+            .zip(outcomes)
+            .filter_map(|(mut record, outcome)| match outcome {
+                Outcome::Keep => Some(record),
+                Outcome::Generalize(location) => {
+                    record.location = location;
+                    Some(record)
+                }
+                Outcome::Pending => None,
+            })
+            .collect()
+    }
+}
+
+/// Outcome of the filter for a business record.
+#[derive(Clone, Debug)]
+enum Outcome {
+    /// Not determined yet. Dropped if it remains at the end.
+    Pending,
+    /// Kept at the exact location.
+    Keep,
+    /// Moved to a given location.
+    Generalize(GeolocationCoordinates),
+}
+
+/// Returns the key to distinguish the dog who carried out a business record.
+///
+/// Prefers the masked dog ID, and falls back to the dog ID.
+/// Returns `None` if neither is available, then the record does not
+/// contribute to the anonymity level.
+#[inline]
+fn anonymity_key(record: &BusinessRecord) -> Option<&String> {
+    record.masked_dog_id.as_ref().or(record.dog_id.as_ref())
+}
+
+/// Returns the coordinates of the cell which contains a given location at a
+/// given zoom level.
+#[inline]
+fn cell_at_zoom(location: &GeolocationCoordinates, zoom: u32) -> (u64, u64) {
+    let cells_per_edge = cells_per_edge_at_zoom(zoom);
+    let x = (cells_per_edge * normalized_x_from_longitude(location.longitude)).floor();
+    let y = (cells_per_edge * normalized_y_from_latitude(location.latitude)).floor();
+    (x as u64, y as u64)
+}
+
+/// Returns the location at the center of a given cell at a given zoom level.
+#[inline]
+fn cell_center_at_zoom((x, y): (u64, u64), zoom: u32) -> GeolocationCoordinates {
+    let cells_per_edge = cells_per_edge_at_zoom(zoom);
+    let normalized_x = (x as f64 + 0.5) / cells_per_edge;
+    let normalized_y = (y as f64 + 0.5) / cells_per_edge;
+    GeolocationCoordinates {
+        longitude: 360.0 * normalized_x - 180.0,
+        latitude: (PI * (1.0 - 2.0 * normalized_y)).sinh().atan().to_degrees(),
+    }
+}
+
+/// Number of cells per edge at a given zoom level.
+#[inline]
+fn cells_per_edge_at_zoom(zoom: u32) -> f64 {
+    (1u64 << zoom) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use business_core::types::{BusinessRecordBuilder, BusinessType};
+
+    const TOKYO_STATION: GeolocationCoordinates = GeolocationCoordinates {
+        longitude: 139.7670506677,
+        latitude: 35.6814709332,
+    };
+    const TOKYO_STATION_MARUNOUCHI: GeolocationCoordinates = GeolocationCoordinates {
+        longitude: 139.7680,
+        latitude: 35.6815,
+    };
+    const SHINJUKU_STATION: GeolocationCoordinates = GeolocationCoordinates {
+        longitude: 139.7005541230,
+        latitude: 35.6898188583,
+    };
+
+    // tile at zoom level 10 that contains all the locations above
+    fn tile_coordinates() -> TileCoordinates {
+        TileCoordinates::new(10, 909, 403).unwrap()
+    }
+
+    fn make_record(
+        record_id: &str,
+        masked_dog_id: &str,
+        location: &GeolocationCoordinates,
+        is_advocated: bool,
+    ) -> BusinessRecord {
+        BusinessRecordBuilder::default()
+            .record_id(record_id)
+            .dog_id(None)
+            .masked_dog_id(Some(masked_dog_id.to_string()))
+            .is_advocated(Some(is_advocated))
+            .business_type(BusinessType::Pee)
+            .location(location.clone())
+            .timestamp(487_588)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_anonymity_filter_keeps_records_in_anonymous_cell() {
+        let filter = AnonymityFilter::new(2, 4);
+        let records = vec![
+            make_record("record_1", "dog_1", &TOKYO_STATION, false),
+            make_record("record_2", "dog_2", &TOKYO_STATION_MARUNOUCHI, false),
+        ];
+        let filtered = filter.filter(&tile_coordinates(), records);
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(filtered[0].record_id, "record_1");
+        assert_eq!(filtered[0].location.longitude, TOKYO_STATION.longitude);
+        assert_eq!(filtered[0].location.latitude, TOKYO_STATION.latitude);
+        assert_eq!(filtered[1].record_id, "record_2");
+        assert_eq!(filtered[1].location.longitude, TOKYO_STATION_MARUNOUCHI.longitude);
+        assert_eq!(filtered[1].location.latitude, TOKYO_STATION_MARUNOUCHI.latitude);
+    }
+
+    #[test]
+    fn test_anonymity_filter_generalizes_records_in_coarser_cell() {
+        let filter = AnonymityFilter::new(2, 4);
+        let records = vec![
+            make_record("record_1", "dog_1", &TOKYO_STATION, false),
+            make_record("record_2", "dog_2", &SHINJUKU_STATION, false),
+        ];
+        let filtered = filter.filter(&tile_coordinates(), records);
+        assert_eq!(filtered.len(), 2);
+        // both records are moved to the same cell center in the tile
+        assert_eq!(filtered[0].location.longitude, filtered[1].location.longitude);
+        assert_eq!(filtered[0].location.latitude, filtered[1].location.latitude);
+        let cell = cell_at_zoom(&filtered[0].location, tile_coordinates().zoom());
+        assert_eq!(cell, (tile_coordinates().x() as u64, tile_coordinates().y() as u64));
+    }
+
+    #[test]
+    fn test_anonymity_filter_drops_records_of_single_dog() {
+        let filter = AnonymityFilter::new(2, 4);
+        let records = vec![
+            make_record("record_1", "dog_1", &TOKYO_STATION, false),
+            make_record("record_2", "dog_1", &TOKYO_STATION_MARUNOUCHI, false),
+            make_record("record_3", "dog_1", &SHINJUKU_STATION, false),
+        ];
+        let filtered = filter.filter(&tile_coordinates(), records);
+        assert!(filtered.is_empty());
+    }
+
+    #[test]
+    fn test_anonymity_filter_keeps_advocated_records() {
+        let filter = AnonymityFilter::new(2, 4);
+        let records = vec![
+            make_record("record_1", "dog_1", &TOKYO_STATION, true),
+            make_record("record_2", "dog_2", &SHINJUKU_STATION, false),
+        ];
+        let filtered = filter.filter(&tile_coordinates(), records);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].record_id, "record_1");
+        assert_eq!(filtered[0].location.longitude, TOKYO_STATION.longitude);
+        assert_eq!(filtered[0].location.latitude, TOKYO_STATION.latitude);
+    }
+
+    #[test]
+    fn test_cell_center_at_zoom() {
+        const EPSILON: f64 = 1e-9;
+        let center = cell_center_at_zoom((0, 0), 0);
+        assert!(center.longitude.abs() < EPSILON);
+        assert!(center.latitude.abs() < EPSILON);
+
+        let center = cell_center_at_zoom((1, 0), 1);
+        assert!((center.longitude - 90.0).abs() < EPSILON);
+        assert!(center.latitude > 0.0);
+        assert_eq!(cell_at_zoom(&center, 1), (1, 0));
+    }
+}
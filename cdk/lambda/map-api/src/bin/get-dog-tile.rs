@@ -0,0 +1,289 @@
+//! Obtains the private map tile of a given dog at given coordinates.
+//!
+//! Unlike `get-tile`, the tile contains all the business records carried out
+//! by the dog with exact locations and timestamps.
+//!
+//! ## Environment variables
+//!
+//! You have to configure the following environment varialbes:
+//! - `RESOURCE_TABLE_NAME`: name of the DynamoDB table that stores dogs, users,
+//!   and their relationships
+//! - `BUSINESS_RECORD_TABLE_NAME`: name of the DynamoDB table that stores
+//!   business records
+//! - `INDEXED_ZOOM_LEVELS`: comma-separated zoom levels that are indexed. The
+//!   zoom level 0 must be included.
+//! - `TILE_INDEX_NAME_PREFIX`: prefix of the name of the global secondary
+//!   index for tiles at specific zoom levels.
+//!
+//! ## Input
+//!
+//! Input must be a JSON object with the following fields:
+//! - `userId`: (string) ID of the user who requests the tile. Must be a friend
+//!   of the dog.
+//! - `dogId`: (string) ID of the dog whose business records are in the tile
+//! - `zoom`: (number) zoom level of the tile
+//! - `x`: (number) x coordinate of the tile
+//! - `y`: (number) y coordinate of the tile
+//! - `since`: (number, optional) earliest timestamp of business records in
+//!   the tile. number of seconds elapsed since 00:00:00 on January 1, 1970
+//!   UTC.
+//! - `until`: (number, optional) latest timestamp of business records in the
+//!   tile. number of seconds elapsed since 00:00:00 on January 1, 1970 UTC.
+//! - `businessType`: (string, optional) type of business records in the tile.
+//!   "pee" or "poo".
+//!
+//! Fails with an error message that contains "invalid tile coordinates" if
+//! the tile coordinates are out of range, and with an error message that
+//! contains "invalid time window" if `since` is later than `until`.
+//!
+//! ## Output
+//!
+//! Output is Base64-encoded Mapbox vector tile (mvt) data.
+//! The API Gateway has to convert it to binary.
+//!
+//! The tile has the `business_records` layer that contains the business
+//! records as points, including those within [`TILE_BUFFER`] around the tile.
+
+use base64::engine::{general_purpose::STANDARD as base64_engine, Engine as _};
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use protobuf::Message as _;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use business_core::{
+    mvt::TileCoordinates,
+    tables::{BusinessRecordTableBuilder, RecordFilter, ResourceTable, TableError},
+    types::BusinessType,
+};
+use map_api::mvt::{MvtError, symbol::BusinessRecordBuffer};
+use map_api::protos::vector_tile::Tile;
+use map_api::tile_query::{collect_records_in_tile, plan_tile_query};
+
+/// Maximum number of business records per tile.
+pub const MAX_RECORDS_PER_TILE: usize = 200;
+
+/// Maximum number of business records to scan per queried tile.
+///
+/// Bounds the cost of queries when a queried tile at a coarser zoom level
+/// contains a lot of records outside of the requested tile.
+pub const MAX_SCANNED_RECORDS_PER_TILE: usize = 5000;
+
+/// Maximum number of tiles at a finer indexed zoom level to query instead of
+/// a tile at a coarser indexed zoom level.
+///
+/// Tiles that overlap [`TILE_BUFFER`] are also counted.
+pub const MAX_TILE_FAN_OUT: usize = 16;
+
+/// Buffer around a tile in extent units.
+///
+/// Business records within the buffer are also included in the
+/// `business_records` layer so that symbols on tile edges are not clipped.
+pub const TILE_BUFFER: u32 = 64;
+
+/// Shared state.
+struct SharedState {
+    /// DynamoDB client.
+    dynamodb_client: aws_sdk_dynamodb::Client,
+    /// Name of the resource table.
+    resource_table_name: String,
+    /// Name of the DynamoDB table that stores business records.
+    business_record_table_name: String,
+    /// Indexed zoom levels.
+    indexed_zoom_levels: Vec<u32>,
+    /// Prefix of the name of the GSI for tiles at specific zoom levels.
+    tile_index_name_prefix: String,
+}
+
+impl SharedState {
+    async fn new() -> Result<Self, Error> {
+        // caches the table names
+        let resource_table_name = std::env::var("RESOURCE_TABLE_NAME")
+            .map_err(|_| "RESOURCE_TABLE_NAME env is not set")?;
+        let business_record_table_name = std::env::var("BUSINESS_RECORD_TABLE_NAME")
+            .map_err(|_| "BUSINESS_RECORD_TABLE_NAME env is not set")?;
+
+        // parses and caches the indexed zoom levels
+        let indexed_zoom_levels = std::env::var("INDEXED_ZOOM_LEVELS")
+            .map_err(|_| "INDEXED_ZOOM_LEVELS env is not set")?;
+        let mut indexed_zoom_levels: Vec<u32> = indexed_zoom_levels
+            .split(',')
+            .map(|s| s.trim().parse::<u32>())
+            .collect::<Result<_, _>>()?;
+        indexed_zoom_levels.sort_unstable();
+        indexed_zoom_levels
+            .first()
+            .ok_or("no zoom level is indexed")
+            .and_then(|&z| if z == 0 {
+                Ok(())
+            } else {
+                Err("zoom level 0 must be indexed")
+            })?;
+
+        // caches the index prefix
+        let tile_index_name_prefix = std::env::var("TILE_INDEX_NAME_PREFIX")
+            .map_err(|_| "TILE_INDEX_NAME_PREFIX env is not set")?;
+
+        // caches the DynamoDB client
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
+
+        Ok(Self {
+            dynamodb_client,
+            resource_table_name,
+            business_record_table_name,
+            indexed_zoom_levels,
+            tile_index_name_prefix,
+        })
+    }
+}
+
+/// Dog tile request.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DogTileRequest {
+    /// ID of the user who requests the tile. Must be a friend of the dog.
+    user_id: String,
+    /// ID of the dog whose business records are in the tile.
+    dog_id: String,
+    /// Coordinates of the tile.
+    #[serde(flatten)]
+    coordinates: TileCoordinates,
+    /// Earliest timestamp (inclusive) of business records in the tile.
+    #[serde(default)]
+    since: Option<i64>,
+    /// Latest timestamp (inclusive) of business records in the tile.
+    #[serde(default)]
+    until: Option<i64>,
+    /// Type of business records in the tile.
+    #[serde(default)]
+    business_type: Option<BusinessType>,
+}
+
+async fn function_handler(
+    shared_state: Arc<SharedState>,
+    event: LambdaEvent<DogTileRequest>,
+) -> Result<String, Error> {
+    let DogTileRequest {
+        user_id,
+        dog_id,
+        coordinates,
+        since,
+        until,
+        business_type,
+    } = event.payload;
+
+    tracing::info!("getting dog tile: dog={dog_id}, user={user_id}");
+    tracing::info!("z: {}, x: {}, y: {}", coordinates.zoom(), coordinates.x(), coordinates.y());
+    tracing::info!("since: {since:?}, until: {until:?}, business type: {business_type:?}");
+    let filter = RecordFilter {
+        since,
+        until,
+        business_type,
+    };
+    filter.validate()?;
+
+    // makes sure that the user is a friend of the dog
+    tracing::info!("checking user-dog relationship");
+    let resource_table = ResourceTable::new(
+        shared_state.dynamodb_client.clone(),
+        &shared_state.resource_table_name,
+    );
+    let relationship = resource_table
+        .get_user_dog_relationship(&user_id, &dog_id)
+        .await?;
+    if relationship.is_none() {
+        // TODO: return 403 error
+        return Err("only friend dog can be requested".into());
+    }
+
+    // plans the tiles to query at indexed zoom levels
+    // should not panic because zoom level 0 is always indexed
+    let query_tiles = plan_tile_query(
+        &coordinates,
+        &shared_state.indexed_zoom_levels,
+        MAX_TILE_FAN_OUT,
+        TILE_BUFFER,
+    );
+    tracing::info!(
+        "querying {} tile(s) at zoom level {}",
+        query_tiles.len(),
+        query_tiles[0].coordinates.zoom(),
+    );
+
+    // fetches records of the dog
+    let record_table = BusinessRecordTableBuilder::default()
+        .client(shared_state.dynamodb_client.clone())
+        .table_name(&shared_state.business_record_table_name)
+        .tile_index_name_prefix(Some(shared_state.tile_index_name_prefix.clone()))
+        .build()?;
+    let queries = query_tiles
+        .iter()
+        .map(|query| Ok((
+            query,
+            record_table.query_by_dog_tile(
+                &dog_id,
+                &query.coordinates,
+                &filter,
+                query.split(MAX_RECORDS_PER_TILE),
+            )?,
+        )))
+        .collect::<Result<Vec<_>, TableError>>()?;
+    let records = collect_records_in_tile(
+        &coordinates,
+        queries,
+        MAX_RECORDS_PER_TILE,
+        MAX_SCANNED_RECORDS_PER_TILE,
+        TILE_BUFFER,
+    ).await?;
+
+    let mut mvt_buffer = BusinessRecordBuffer::with_buffer(coordinates, TILE_BUFFER);
+    let mut num_symbols = 0;
+    for record in records.into_iter() {
+        if num_symbols >= MAX_RECORDS_PER_TILE {
+            tracing::info!("too many business records in the tile");
+            break;
+        }
+        match mvt_buffer.append_business_record(record) {
+            Ok(_) => num_symbols += 1,
+            Err(MvtError::OutsideOfTile) =>
+                tracing::info!("business record is outside of the tile"),
+            Err(MvtError::DuplicateRecordId(record_id)) => {
+                tracing::error!("duplicate record ID: {record_id}");
+                return Err("internal error".into());
+            }
+            Err(e) => {
+                tracing::error!("failed to append business record: {e}");
+                return Err("internal error".into());
+            }
+        }
+    }
+    tracing::info!("# of business records in the tile: {num_symbols}");
+
+    let tile: Tile = mvt_buffer.into();
+    let tile_bytes = tile
+        .write_to_bytes()
+        .map_err(|e| {
+            tracing::error!("failed to serialize map tile vector tile: {e}");
+            "internal error"
+        })?;
+    tracing::info!("map tile size: {} bytes", tile_bytes.len());
+    let tile_b64 = base64_engine.encode(tile_bytes);
+
+    Ok(tile_b64)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        // disable printing the name of the module in every log line.
+        .with_target(false)
+        // disabling time is handy because CloudWatch will add the ingestion time.
+        .without_time()
+        .init();
+
+    let shared_state = Arc::new(SharedState::new().await?);
+    run(service_fn(|req| async {
+        function_handler(shared_state.clone(), req).await
+    })).await
+}
@@ -9,38 +9,189 @@
 //!   zoom level 0 must be included.
 //! - `TILE_INDEX_NAME_PREFIX`: prefix of the name of the global secondary
 //!   index for tiles at specific zoom levels.
+//! - `TILE_CACHE_BUCKET_NAME`: name of the S3 bucket that caches rendered
+//!   tiles
 //!
 //! ## Input
 //!
-//! Input must be a JSON object with the following fields:
-//! - `zoom`: (number) zoom level of the tile
-//! - `x`: (number) x coordinate of the tile
-//! - `y`: (number) y coordinate of the tile
+//! Input must be an API Gateway Lambda proxy integration event with the
+//! following parameters:
+//! - path parameters:
+//!     - `z`: (number) zoom level of the tile
+//!     - `x`: (number) x coordinate of the tile
+//!     - `y`: (number) y coordinate of the tile
+//! - query parameters:
+//!     - `since`: (number, optional) earliest timestamp of business records in
+//!       the tile. number of seconds elapsed since 00:00:00 on January 1, 1970
+//!       UTC. rounded down to hours.
+//!     - `until`: (number, optional) latest timestamp of business records in
+//!       the tile. number of seconds elapsed since 00:00:00 on January 1, 1970
+//!       UTC. rounded down to hours.
+//!     - `businessType`: (string, optional) type of business records in the
+//!       tile. "pee" or "poo".
+//!     - `format`: (string, optional) output format. "mvt" or "geojson".
+//!       defaults to "geojson" if the resource path ends with ".geojson",
+//!       otherwise "mvt".
+//!     - `layers`: (string, optional) layers of business records in the mvt
+//!       tile. "combined" (default) or "by-type". ignored if `format` is
+//!       "geojson".
+//! - headers:
+//!     - `Accept-Encoding`: (optional) the mvt data is compressed with "br" or
+//!       "gzip" if acceptable.
+//!     - `If-None-Match`: (optional) ETags of the tile that the client has.
 //!
 //! ## Output
 //!
-//! Output is Base64-encoded Mapbox vector tile (mvt) data.
-//! The API Gateway has to convert it to binary.
+//! Output is an API Gateway Lambda proxy integration response with one of
+//! the following status codes:
+//! - 200: the tile. has `Cache-Control` and `ETag` headers.
+//! - 204: the mvt tile has no features. has `Cache-Control` and `ETag`
+//!   headers.
+//! - 304: the tile matches `If-None-Match`. has `Cache-Control` and `ETag`
+//!   headers.
+//! - 400: the coordinates or query parameters are invalid; e.g., `since` is
+//!   later than `until`.
+//! - 429: DynamoDB throttled the queries.
+//! - 500: any other error.
+//!
+//! If `format` is "mvt", the body is Mapbox vector tile (mvt) data compressed
+//! with the encoding in the `Content-Encoding` header.
+//! The body is Base64-encoded, and API Gateway has to convert it to binary;
+//! i.e., the `Content-Type` "application/vnd.mapbox-vector-tile" must be one
+//! of the binary media types of the API.
+//! If `format` is "geojson", the body is plain text with the `Content-Type`
+//! "application/geo+json", which must not be a binary media type.
 //!
 //! Please note that a Lambda function behind API Gateway cannot return raw
 //! binary data.
+//!
+//! The ETag is determined by a digest of the business records in the tile
+//! before encoding the tile. See [`map_api::etag`] for
+//! more details.
+//!
+//! Rendered tiles are cached in the S3 bucket until an update of business
+//! records invalidates them, or they expire in minutes. See [`business_core::tile_cache`] for more details.
+//!
+//! The tile has the following layers:
+//! - `business_records`: business records as points. including those within
+//!   [`TILE_BUFFER`] around the tile. thinned to a spatially uniform sample
+//!   if the tile would exceed [`MAX_TILE_BYTES`], and then every feature has
+//!   the `truncated` property with `true`.
+//!   if `layers` is "by-type", replaced with `business_records_pee` and
+//!   `business_records_poo`, each of which contains business records of the
+//!   type and has a half of the size budget.
+//! - `business_clusters`: clusters of business records as points. only at
+//!   zoom levels up to [`MAX_CLUSTERED_ZOOM`].
+//! - `business_density`: density grid of business records as polygons. only
+//!   at zoom levels up to [`MAX_CLUSTERED_ZOOM`].
+//!
+//! If `format` is "geojson", the body is a GeoJSON `FeatureCollection` that
+//! contains the same features and properties as the `business_records` layer.
+//! Locations are not quantized, and there are no clusters or density grid.
+//! An empty `FeatureCollection` is returned with 200 if there are no
+//! features.
 
-use base64::engine::{general_purpose::STANDARD as base64_engine, Engine as _};
-use futures::stream::TryStreamExt as _;
+use aws_lambda_events::{
+    apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse},
+    encodings::Body,
+    http::{HeaderMap, HeaderValue, header},
+};
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
 use protobuf::Message as _;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use business_core::{
     mvt::TileCoordinates,
-    tables::BusinessRecordTableBuilder,
-    types::BusinessRecord,
+    tile_cache::{
+        CachedTile,
+        DEFAULT_KEY_PREFIX,
+        INVALIDATION_MARGIN,
+        S3TileCache,
+        TileCache,
+        TileCacheKey,
+        current_time_millis,
+    },
+    tables::{BusinessRecordTableBuilder, RecordFilter, TableError},
+    types::{BusinessRecord, BusinessType},
 };
-use map_api::mvt::{MvtError, symbol::BusinessRecordBuffer};
-use map_api::protos::vector_tile::Tile;
+use map_api::anonymity::AnonymityFilter;
+use map_api::compression::ContentEncoding;
+use map_api::etag::{self, TileVersion};
+use map_api::geojson::FeatureCollection;
+use map_api::mvt::{
+    MvtError,
+    TILE_EXTENT,
+    budget::{LayerBudget, ThinningStrategy},
+    cluster::{self, BusinessClusterBuffer},
+    density::{self, DensityGridBuilder},
+    symbol::{self, BusinessRecordBuffer, BusinessRecordBuffersByType},
+    tile_builder::{LayerProducer, TileBuilder},
+};
+use map_api::protos::vector_tile::tile::Layer;
+use map_api::tile_query::{collect_records_in_tile, plan_tile_query};
 
 /// Maximum number of business records per tile.
-pub const MAX_RECORDS_PER_TILE: usize = 200;
+///
+/// The `business_records` layer is thinned if it exceeds
+/// [`MAX_TILE_BYTES`].
+pub const MAX_RECORDS_PER_TILE: usize = 1000;
+
+/// Maximum size of a mvt tile in bytes before compression.
+pub const MAX_TILE_BYTES: usize = 500 * 1024;
+
+/// Number of grid cells per tile edge to thin the `business_records` layer
+/// spatially uniformly.
+pub const THINNING_GRID_SIZE: u32 = 16;
+
+/// Maximum zoom level at which business records are clustered.
+///
+/// Tiles at this or lower zoom levels have the `business_clusters` and
+/// `business_density` layers in addition to the `business_records` layer.
+pub const MAX_CLUSTERED_ZOOM: u32 = 10;
+
+/// Maximum number of business records to be clustered per tile.
+pub const MAX_CLUSTERED_RECORDS_PER_TILE: usize = 2000;
+
+/// Maximum number of business records to scan per queried tile.
+///
+/// Bounds the cost of queries when a queried tile at a coarser zoom level
+/// contains a lot of records outside of the requested tile.
+pub const MAX_SCANNED_RECORDS_PER_TILE: usize = 5000;
+
+/// Maximum number of tiles at a finer indexed zoom level to query instead of
+/// a tile at a coarser indexed zoom level.
+///
+/// Tiles that overlap [`TILE_BUFFER`] are also counted.
+pub const MAX_TILE_FAN_OUT: usize = 16;
+
+/// Buffer around a tile in extent units.
+///
+/// Business records within the buffer are also included in the
+/// `business_records` layer so that symbols on tile edges are not clipped.
+pub const TILE_BUFFER: u32 = 64;
+
+// a new business record within the buffer must invalidate the cached tile
+const _: () = assert!(TILE_BUFFER as f64 / TILE_EXTENT as f64 <= INVALIDATION_MARGIN);
+
+/// Maximum age of a tile in caches in seconds.
+pub const CACHE_MAX_AGE: u32 = 300;
+
+/// Content type of Mapbox vector tiles.
+pub const MVT_CONTENT_TYPE: &str = "application/vnd.mapbox-vector-tile";
+
+/// Content type of GeoJSON.
+pub const GEOJSON_CONTENT_TYPE: &str = "application/geo+json";
+
+/// Minimum number of distinct dogs in a cell to show public business records
+/// in the cell; i.e., "k" of k-anonymity.
+pub const MIN_ANONYMITY_LEVEL: usize = 3;
+
+/// Level of the finest cells to evaluate the anonymity level.
+///
+/// A tile is divided into `2^MAX_ANONYMITY_CELL_LEVEL` ×
+/// `2^MAX_ANONYMITY_CELL_LEVEL` cells at the finest level.
+pub const MAX_ANONYMITY_CELL_LEVEL: u32 = 4;
 
 /// Shared state.
 struct SharedState {
@@ -52,6 +203,8 @@ struct SharedState {
     indexed_zoom_levels: Vec<u32>,
     /// Prefix of the name of the GSI for tiles at specific zoom levels.
     tile_index_name_prefix: String,
+    /// Cache of rendered tiles.
+    tile_cache: S3TileCache,
 }
 
 impl SharedState {
@@ -81,35 +234,435 @@ impl SharedState {
         let tile_index_name_prefix = std::env::var("TILE_INDEX_NAME_PREFIX")
             .map_err(|_| "TILE_INDEX_NAME_PREFIX env is not set")?;
 
-        // caches the DynamoDB client
+        // caches the tile cache bucket name
+        let tile_cache_bucket_name = std::env::var("TILE_CACHE_BUCKET_NAME")
+            .map_err(|_| "TILE_CACHE_BUCKET_NAME env is not set")?;
+
+        // caches the DynamoDB client and tile cache
         let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
         let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
+        let tile_cache = S3TileCache::new(
+            aws_sdk_s3::Client::new(&config),
+            tile_cache_bucket_name,
+            DEFAULT_KEY_PREFIX,
+        );
 
         Ok(Self {
             dynamodb_client,
             business_record_table_name,
             indexed_zoom_levels,
             tile_index_name_prefix,
+            tile_cache,
+        })
+    }
+}
+
+/// Tile request.
+#[derive(Clone, Debug)]
+struct TileRequest {
+    /// Coordinates of the tile.
+    coordinates: TileCoordinates,
+    /// Earliest timestamp (inclusive) of business records in the tile.
+    since: Option<i64>,
+    /// Latest timestamp (inclusive) of business records in the tile.
+    until: Option<i64>,
+    /// Type of business records in the tile.
+    business_type: Option<BusinessType>,
+    /// Output format.
+    format: TileFormat,
+    /// Layers of business records.
+    layers: RecordLayers,
+    /// Value of the `Accept-Encoding` header.
+    accept_encoding: Option<String>,
+    /// Value of the `If-None-Match` header.
+    if_none_match: Option<String>,
+}
+
+impl TryFrom<&ApiGatewayProxyRequest> for TileRequest {
+    /// Message for a bad request.
+    type Error = String;
+
+    fn try_from(request: &ApiGatewayProxyRequest) -> Result<Self, Self::Error> {
+        let zoom: u32 = path_parameter(request, "z")?;
+        let x: u32 = path_parameter(request, "x")?;
+        let y: u32 = path_parameter(request, "y")?;
+        let coordinates = TileCoordinates::new(zoom, x, y).map_err(|e| e.to_string())?;
+        let format = match query_parameter::<TileFormat>(request, "format")? {
+            Some(format) => format,
+            None if request.resource.as_deref().is_some_and(|r| r.ends_with(".geojson")) =>
+                TileFormat::GeoJson,
+            None => TileFormat::Mvt,
+        };
+        // GeoJSON has no layers
+        let layers = match format {
+            TileFormat::Mvt => query_parameter(request, "layers")?.unwrap_or_default(),
+            TileFormat::GeoJson => RecordLayers::Combined,
+        };
+        let since = query_parameter(request, "since")?;
+        let until = query_parameter(request, "until")?;
+        RecordFilter {
+            since,
+            until,
+            business_type: None,
+        }.validate()?;
+        let header_value = |name| request.headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        Ok(Self {
+            coordinates,
+            since,
+            until,
+            business_type: query_parameter(request, "businessType")?,
+            format,
+            layers,
+            accept_encoding: header_value(header::ACCEPT_ENCODING),
+            if_none_match: header_value(header::IF_NONE_MATCH),
+        })
+    }
+}
+
+/// Parses a path parameter.
+fn path_parameter<T>(request: &ApiGatewayProxyRequest, name: &str) -> Result<T, String>
+where
+    T: FromStr,
+{
+    request.path_parameters
+        .get(name)
+        .ok_or_else(|| format!("{name} is missing"))?
+        .parse()
+        .map_err(|_| format!("invalid {name}"))
+}
+
+/// Parses an optional query parameter.
+fn query_parameter<T>(request: &ApiGatewayProxyRequest, name: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+{
+    request.query_string_parameters
+        .first(name)
+        .map(|value| value.parse().map_err(|_| format!("invalid {name}")))
+        .transpose()
+}
+
+/// Output format of a tile.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+enum TileFormat {
+    /// Mapbox vector tile.
+    #[default]
+    Mvt,
+    /// GeoJSON `FeatureCollection`.
+    GeoJson,
+}
+
+impl FromStr for TileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mvt" => Ok(Self::Mvt),
+            "geojson" => Ok(Self::GeoJson),
+            _ => Err(format!("invalid format: {s}")),
+        }
+    }
+}
+
+impl TileFormat {
+    /// Returns the name of the format used in query parameters.
+    const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Mvt => "mvt",
+            Self::GeoJson => "geojson",
+        }
+    }
+}
+
+/// Layers of business records in a mvt tile.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+enum RecordLayers {
+    /// Single `business_records` layer.
+    #[default]
+    Combined,
+    /// Layer per business type.
+    ByType,
+}
+
+impl FromStr for RecordLayers {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "combined" => Ok(Self::Combined),
+            "by-type" => Ok(Self::ByType),
+            _ => Err(format!("invalid layers: {s}")),
+        }
+    }
+}
+
+impl RecordLayers {
+    /// Returns the name of the layers used in query parameters.
+    const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Combined => "combined",
+            Self::ByType => "by-type",
+        }
+    }
+}
+
+/// Buffer for the layers of business records.
+// a single instance per request does not deserve boxing
+#[allow(clippy::large_enum_variant)]
+enum RecordLayerBuffer {
+    /// Single `business_records` layer.
+    Combined(BusinessRecordBuffer),
+    /// Layer per business type.
+    ByType(BusinessRecordBuffersByType),
+}
+
+impl RecordLayerBuffer {
+    /// Appends a given business record to the buffer.
+    fn append_business_record(&mut self, record: BusinessRecord) -> Result<(), MvtError> {
+        match self {
+            Self::Combined(buffer) => buffer.append_business_record(record),
+            Self::ByType(buffers) => buffers.append_business_record(record),
+        }
+    }
+}
+
+impl LayerProducer for RecordLayerBuffer {
+    fn into_layers(self) -> Vec<Layer> {
+        match self {
+            Self::Combined(buffer) => buffer.into_layers(),
+            Self::ByType(buffers) => buffers.into_layers(),
+        }
+    }
+}
+
+/// Contents of a tile.
+enum TileContent {
+    /// Mapbox vector tile.
+    Mvt {
+        /// Mapbox vector tile compressed with `content_encoding`.
+        data: Vec<u8>,
+        /// Encoding of the Mapbox vector tile.
+        content_encoding: ContentEncoding,
+    },
+    /// Serialized GeoJSON `FeatureCollection`.
+    GeoJson(String),
+    /// Mapbox vector tile without any features.
+    Empty,
+    /// Tile that the client already has.
+    NotModified,
+}
+
+impl TileContent {
+    /// Converts the contents with a given ETag into a proxy response.
+    fn into_response(self, etag: &str) -> Result<ApiGatewayProxyResponse, Error> {
+        let mut headers = common_headers();
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_str(&format!("public, max-age={CACHE_MAX_AGE}"))?,
+        );
+        headers.insert(header::ETAG, HeaderValue::from_str(etag)?);
+        let (status_code, body, is_base64_encoded) = match self {
+            Self::Mvt { data, content_encoding } => {
+                headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(MVT_CONTENT_TYPE));
+                headers.insert(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+                if content_encoding != ContentEncoding::Identity {
+                    headers.insert(
+                        header::CONTENT_ENCODING,
+                        HeaderValue::from_static(content_encoding.as_str()),
+                    );
+                }
+                (200, Body::Binary(data), true)
+            }
+            Self::GeoJson(json) => {
+                headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(GEOJSON_CONTENT_TYPE));
+                (200, Body::Text(json), false)
+            }
+            Self::Empty => (204, Body::Empty, false),
+            Self::NotModified => (304, Body::Empty, false),
+        };
+        Ok(ApiGatewayProxyResponse {
+            status_code,
+            headers,
+            body: Some(body),
+            is_base64_encoded,
+            ..Default::default()
         })
     }
 }
 
+/// Makes an error response with a given status code and message.
+fn error_response(status_code: i64, message: &str) -> ApiGatewayProxyResponse {
+    let mut headers = common_headers();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+    ApiGatewayProxyResponse {
+        status_code,
+        headers,
+        body: Some(Body::Text(message.to_string())),
+        ..Default::default()
+    }
+}
+
+/// Returns headers common to all the responses.
+fn common_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    headers
+}
+
 async fn function_handler(
     shared_state: Arc<SharedState>,
-    event: LambdaEvent<TileCoordinates>,
-) -> Result<String, Error> {
-    let coordinates = event.payload;
+    event: LambdaEvent<ApiGatewayProxyRequest>,
+) -> Result<ApiGatewayProxyResponse, Error> {
+    let request = match TileRequest::try_from(&event.payload) {
+        Ok(request) => request,
+        Err(message) => {
+            tracing::warn!("bad request: {message}");
+            return Ok(error_response(400, &message));
+        }
+    };
+    match get_tile(shared_state, request).await {
+        Ok((etag, content)) => content.into_response(&etag),
+        Err(e) => match e.downcast_ref::<TableError>() {
+            Some(TableError::RateLimited(e)) => {
+                tracing::warn!("rate limited: {e}");
+                Ok(error_response(429, "too many requests"))
+            }
+            _ => {
+                tracing::error!("failed to get tile: {e}");
+                Ok(error_response(500, "internal error"))
+            }
+        },
+    }
+}
 
-    tracing::info!("z: {}, x: {}, y: {}", coordinates.zoom, coordinates.x, coordinates.y);
+async fn get_tile(
+    shared_state: Arc<SharedState>,
+    request: TileRequest,
+) -> Result<(String, TileContent), Error> {
+    tracing::info!(
+        "z: {}, x: {}, y: {}",
+        request.coordinates.zoom(),
+        request.coordinates.x(),
+        request.coordinates.y(),
+    );
+    tracing::info!(
+        "since: {:?}, until: {:?}, business type: {:?}",
+        request.since,
+        request.until,
+        request.business_type,
+    );
+    tracing::info!(
+        "format: {:?}, accept encoding: {:?}",
+        request.format,
+        request.accept_encoding,
+    );
 
-    // finds the largest indexed zoom level that covers the requested zoom level
-    let zoom_index = shared_state
-        .indexed_zoom_levels
-        .partition_point(|&z| z <= coordinates.zoom)
-        .checked_sub(1)
-        .unwrap(); // should not panic because zoom level 0 is always indexed
-    let indexed_zoom = shared_state.indexed_zoom_levels[zoom_index];
-    let indexed_coordinates = coordinates.zoom_out_to(indexed_zoom).unwrap();
+    let content_encoding = match request.format {
+        TileFormat::Mvt => request.accept_encoding
+            .as_deref()
+            .map(ContentEncoding::negotiate)
+            .unwrap_or_default(),
+        TileFormat::GeoJson => ContentEncoding::Identity,
+    };
+
+    // looks up the tile cache before querying records
+    let cache_key = TileCacheKey::new(
+        request.coordinates.clone(),
+        cache_variant(&request, content_encoding),
+    );
+    match shared_state.tile_cache.get(&cache_key).await {
+        Ok(Some(cached)) => {
+            tracing::info!("tile cache hit: {}", cache_key.path());
+            let CachedTile { etag, data, .. } = cached;
+            if request.if_none_match.as_deref().is_some_and(|v| etag::if_none_match(v, &etag)) {
+                tracing::info!("tile not modified");
+                return Ok((etag, TileContent::NotModified));
+            }
+            let content = match request.format {
+                TileFormat::Mvt if data.is_empty() => TileContent::Empty,
+                TileFormat::Mvt => TileContent::Mvt { data, content_encoding },
+                TileFormat::GeoJson => TileContent::GeoJson(String::from_utf8(data)?),
+            };
+            return Ok((etag, content));
+        }
+        Ok(None) => tracing::info!("tile cache miss: {}", cache_key.path()),
+        // a broken cache should not fail the request
+        Err(e) => tracing::warn!("failed to get cached tile: {e}"),
+    }
+
+    // the time must be taken before querying records so that the cached tile
+    // is invalidated by any update during the render
+    let rendered_at = current_time_millis();
+    let (etag, content) = render_tile(&shared_state, request, content_encoding).await?;
+
+    let data = match &content {
+        TileContent::Mvt { data, .. } => Some(data.clone()),
+        TileContent::GeoJson(json) => Some(json.clone().into_bytes()),
+        TileContent::Empty => Some(Vec::new()),
+        // the tile was not rendered
+        TileContent::NotModified => None,
+    };
+    if let Some(data) = data {
+        let cached = CachedTile { etag: etag.clone(), data, rendered_at };
+        if let Err(e) = shared_state.tile_cache.put(&cache_key, cached).await {
+            tracing::warn!("failed to cache tile: {e}");
+        }
+    }
+
+    Ok((etag, content))
+}
+
+/// Returns the variant of a tile in the tile cache.
+///
+/// Distinguishes the release, filters, format, layers, and content encoding.
+fn cache_variant(request: &TileRequest, content_encoding: ContentEncoding) -> String {
+    let or_dash = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+    format!(
+        "{}.{}.{}.{}.{}.{}.{}",
+        // encoding may change between releases
+        env!("CARGO_PKG_VERSION"),
+        request.format.as_str(),
+        request.layers.as_str(),
+        content_encoding.as_str(),
+        or_dash(request.since.map(|t| t.to_string())),
+        or_dash(request.until.map(|t| t.to_string())),
+        or_dash(request.business_type.as_ref().map(ToString::to_string)),
+    )
+}
+
+/// Renders a tile from business records.
+async fn render_tile(
+    shared_state: &SharedState,
+    request: TileRequest,
+    content_encoding: ContentEncoding,
+) -> Result<(String, TileContent), Error> {
+    let TileRequest {
+        coordinates,
+        since,
+        until,
+        business_type,
+        format,
+        layers,
+        accept_encoding: _,
+        if_none_match,
+    } = request;
+
+    // plans the tiles to query at indexed zoom levels
+    // should not panic because zoom level 0 is always indexed
+    let query_tiles = plan_tile_query(
+        &coordinates,
+        &shared_state.indexed_zoom_levels,
+        MAX_TILE_FAN_OUT,
+        TILE_BUFFER,
+    );
+    tracing::info!(
+        "querying {} tile(s) at zoom level {}",
+        query_tiles.len(),
+        query_tiles[0].coordinates.zoom(),
+    );
 
     // fetches records
     let record_table = BusinessRecordTableBuilder::default()
@@ -117,74 +670,147 @@ async fn function_handler(
         .table_name(&shared_state.business_record_table_name)
         .tile_index_name_prefix(Some(shared_state.tile_index_name_prefix.clone()))
         .build()?;
-    let records: Vec<BusinessRecord> = record_table.query_by_tile(&indexed_coordinates, MAX_RECORDS_PER_TILE)?
-        .try_collect()
-        .await?;
+    let is_clustered = coordinates.zoom() <= MAX_CLUSTERED_ZOOM;
+    let max_records = if is_clustered {
+        MAX_CLUSTERED_RECORDS_PER_TILE
+    } else {
+        MAX_RECORDS_PER_TILE
+    };
+    let filter = RecordFilter {
+        since,
+        until,
+        business_type,
+    };
+    let queries = query_tiles
+        .iter()
+        .map(|query| Ok((
+            query,
+            record_table.query_by_tile(&query.coordinates, &filter, query.split(max_records))?,
+        )))
+        .collect::<Result<Vec<_>, TableError>>()?;
+    let records = collect_records_in_tile(
+        &coordinates,
+        queries,
+        max_records,
+        MAX_SCANNED_RECORDS_PER_TILE,
+        TILE_BUFFER,
+    ).await?;
+
+    // determines the ETag before encoding the tile
+    let etag = TileVersion::from_records(&records).make_etag(&(
+        // encoding may change between releases
+        env!("CARGO_PKG_VERSION"),
+        (coordinates.zoom(), coordinates.x(), coordinates.y()),
+        (filter.since, filter.until, filter.business_type.as_ref().map(ToString::to_string)),
+        format,
+        layers,
+        content_encoding,
+    ));
+    tracing::info!("ETag: {etag}");
+    if if_none_match.as_deref().is_some_and(|v| etag::if_none_match(v, &etag)) {
+        tracing::info!("tile not modified");
+        return Ok((etag, TileContent::NotModified));
+    }
 
-    // TODO: calculate the anonimity level and filter non-advocated records
+    // filters records that do not satisfy the anonymity level
+    let num_records = records.len();
+    let records = AnonymityFilter::new(MIN_ANONYMITY_LEVEL, MAX_ANONYMITY_CELL_LEVEL)
+        .filter(&coordinates, records);
+    tracing::info!("anonymity filter dropped {} records", num_records - records.len());
 
-    let mut mvt_buffer = BusinessRecordBuffer::new(coordinates);
+    // clusters all the records and calculates the density at low zoom levels
+    // GeoJSON has no clusters or density grid
+    let summary_layers = if is_clustered && format == TileFormat::Mvt {
+        let mut cluster_buffer = BusinessClusterBuffer::new(
+            coordinates.clone(),
+            cluster::DEFAULT_GRID_SIZE,
+        );
+        let mut density_builder = DensityGridBuilder::new(
+            coordinates.clone(),
+            density::DEFAULT_GRID_SIZE,
+        );
+        for record in records.iter() {
+            // records outside of the tile are simply ignored
+            let _ = cluster_buffer.append_business_record(record);
+            let _ = density_builder.add_business_record(record);
+        }
+        tracing::info!("# of clusters: {}", cluster_buffer.len());
+        tracing::info!("# of density cells: {}", density_builder.len());
+        vec![cluster_buffer.into(), density_builder.into()]
+    } else {
+        Vec::new()
+    };
+
+    // the business records layer takes the rest of the size budget
+    let summary_bytes: usize = summary_layers
+        .iter()
+        .map(|layer: &Layer| layer.compute_size() as usize)
+        .sum();
+    let budget = LayerBudget {
+        max_features: None,
+        max_bytes: Some(MAX_TILE_BYTES.saturating_sub(summary_bytes)),
+        strategy: ThinningStrategy::SpatiallyUniform {
+            grid_size: THINNING_GRID_SIZE,
+        },
+    };
+    let mut mvt_buffer = match layers {
+        RecordLayers::Combined => RecordLayerBuffer::Combined(
+            BusinessRecordBuffer::with_buffer(coordinates, TILE_BUFFER).with_budget(budget),
+        ),
+        RecordLayers::ByType => RecordLayerBuffer::ByType(
+            BusinessRecordBuffersByType::with_buffer(coordinates, TILE_BUFFER).with_budget(budget),
+        ),
+    };
+    let mut num_symbols = 0;
     for (i, record) in records.into_iter().enumerate() {
+        if num_symbols >= MAX_RECORDS_PER_TILE {
+            tracing::info!("too many business records in the tile");
+            break;
+        }
         match mvt_buffer.append_business_record(record) {
-            Ok(_) => tracing::info!("added business record: {i}"),
+            Ok(_) => {
+                tracing::info!("added business record: {i}");
+                num_symbols += 1;
+            }
             Err(MvtError::OutsideOfTile) =>
                 tracing::info!("business record is outside of the tile"),
             Err(MvtError::DuplicateRecordId(record_id)) => {
                 tracing::error!("duplicate record ID: {record_id}");
                 return Err("internal error".into());
             }
+            Err(e) => {
+                tracing::error!("failed to append business record: {e}");
+                return Err("internal error".into());
+            }
         }
     }
 
-    let tile: Tile = mvt_buffer.into();
-
-    // prints the layer for a debugging purpose
-    let layer = &tile.layers[0];
-    tracing::info!("layer version: {:?}", layer.version);
-    tracing::info!("layer name: {:?}", layer.name);
-    tracing::info!("layer # of features: {}", layer.features.len());
-    tracing::info!("layer # of keys: {}", layer.keys.len());
-    tracing::info!("layer # of values: {}", layer.values.len());
-    tracing::info!("layer extent: {:?}", layer.extent);
-    for (i, key) in layer.keys.iter().enumerate() {
-        tracing::info!("key[{i}]: {key}");
-    }
-    for (i, value) in layer.values.iter().enumerate() {
-        if let Some(s) = value.string_value.as_ref() {
-            tracing::info!("value[{i}]: {s}");
-        } else if let Some(i) = value.int_value.as_ref() {
-            tracing::info!("value[{i}]: {i}");
-        } else {
-            tracing::info!("value[{i}]: other!");
+    // layers are always combined for GeoJSON
+    let mvt_buffer = match mvt_buffer {
+        RecordLayerBuffer::Combined(buffer) if format == TileFormat::GeoJson => {
+            let collection: FeatureCollection = buffer.into();
+            tracing::info!("# of GeoJSON features: {}", collection.features.len());
+            let json = serde_json::to_string(&collection)?;
+            return Ok((etag, TileContent::GeoJson(json)));
         }
+        mvt_buffer => mvt_buffer,
+    };
+
+    let mut tile_builder = TileBuilder::new();
+    tile_builder.add_layers(mvt_buffer)?;
+    for layer in summary_layers {
+        tile_builder.add_layers(layer)?;
     }
-    for (i, feature) in layer.features.iter().enumerate() {
-        tracing::info!("feature[{}] id: {:?}", i, feature.id);
-        tracing::info!("feature[{}] # of tags: {}", i, feature.tags.len());
-        tracing::info!("feature[{}] type: {:?}", i, feature.type_);
-        tracing::info!("feature[{}] # of geometry: {}", i, feature.geometry.len());
-        for (j, geometry) in feature.geometry.iter().enumerate() {
-            tracing::info!("feature[{}] geometry[{}]: {}", i, j, geometry);
-        }
-        for (j, key_value) in feature.tags.chunks(2).enumerate() {
-            let key_i = key_value[0];
-            let value_i = key_value[1];
-            tracing::info!(
-                "feature[{}] tag[{}]: {} → {}",
-                i,
-                j,
-                layer.keys[key_i as usize],
-                {
-                    let value = &layer.values[value_i as usize];
-                    if let Some(s) = value.string_value.as_ref() {
-                        s.clone()
-                    } else if let Some(i) = value.int_value.as_ref() {
-                        i.to_string()
-                    } else {
-                        "other".to_string()
-                    }
-                },
-            );
+    if tile_builder.has_no_features() {
+        return Ok((etag, TileContent::Empty));
+    }
+    let tile = tile_builder.build();
+
+    // use the `inspect-tile` binary to inspect the contents of the tile
+    for layer in tile.layers.iter() {
+        tracing::info!("layer {:?}: {} features", layer.name(), layer.features.len());
+        if layer.keys.iter().any(|key| key == symbol::PROPERTY_KEY_TRUNCATED) {
+            tracing::warn!("layer {:?} is truncated", layer.name());
         }
     }
 
@@ -195,9 +821,23 @@ async fn function_handler(
             "internal error"
         })?;
     tracing::info!("map tile size: {} bytes", tile_bytes.len());
-    let tile_b64 = base64_engine.encode(tile_bytes);
 
-    Ok(tile_b64)
+    let tile_bytes = content_encoding
+        .compress(&tile_bytes)
+        .map_err(|e| {
+            tracing::error!("failed to compress map tile: {e}");
+            "internal error"
+        })?;
+    tracing::info!(
+        "compressed map tile size: {} bytes ({})",
+        tile_bytes.len(),
+        content_encoding.as_str(),
+    );
+
+    Ok((etag, TileContent::Mvt {
+        data: tile_bytes,
+        content_encoding,
+    }))
 }
 
 #[tokio::main]
@@ -0,0 +1,180 @@
+//! Inspects a map tile.
+//!
+//! Reads a Mapbox vector tile (mvt) and prints its layers, keys, values, and
+//! decoded features.
+//!
+//! ## Usage
+//!
+//! ```sh
+//! inspect-tile --tile <z>/<x>/<y> [--format <json|geojson>] [--encoding <identity|gzip|br>] [<input>]
+//! ```
+//!
+//! - `--tile`: coordinates of the tile. necessary to decode geometries into
+//!   longitudes and latitudes.
+//! - `--format`: output format. `json` (default) prints layers including keys
+//!   and values. `geojson` prints a GeoJSON `FeatureCollection` per layer.
+//! - `--encoding`: compression of the tile; e.g., the `Content-Encoding` of a
+//!   tile downloaded from the Map API. detects gzip by its magic bytes if
+//!   omitted. `br` has no magic bytes, so it has to be specified.
+//! - `<input>`: path to a file that contains the tile. reads the standard
+//!   input if omitted or `-`. The tile may be raw binary or Base64-encoded as
+//!   returned by the `get-tile` Lambda function.
+
+use base64::engine::{general_purpose::STANDARD as base64_engine, Engine as _};
+use protobuf::Message as _;
+use std::io::Read as _;
+
+use map_api::compression::ContentEncoding;
+use map_api::geojson::FeatureCollection;
+use map_api::mvt::{TileCoordinates, decoder::decode_tile};
+use map_api::protos::vector_tile::Tile;
+
+type Error = Box<dyn std::error::Error>;
+
+const USAGE: &str = "usage: inspect-tile --tile <z>/<x>/<y> [--format <json|geojson>] [--encoding <identity|gzip|br>] [<input>]";
+
+/// Magic bytes at the beginning of gzip data.
+const GZIP_MAGIC_BYTES: [u8; 2] = [0x1f, 0x8b];
+
+/// Output format.
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    /// Layers including keys and values.
+    Json,
+    /// GeoJSON `FeatureCollection` per layer.
+    GeoJson,
+}
+
+/// Command line arguments.
+struct Args {
+    /// Coordinates of the tile.
+    coordinates: TileCoordinates,
+    /// Output format.
+    format: Format,
+    /// Compression of the tile. `None` to detect gzip.
+    encoding: Option<ContentEncoding>,
+    /// Path to the input. `None` for the standard input.
+    input: Option<String>,
+}
+
+impl Args {
+    /// Parses given command line arguments.
+    ///
+    /// Returns `None` if the help is requested.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, Error> {
+        let mut coordinates = None;
+        let mut format = Format::Json;
+        let mut encoding = None;
+        let mut input = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--tile" => {
+                    let tile = args.next().ok_or("--tile requires a value")?;
+                    coordinates = Some(parse_tile_coordinates(&tile)?);
+                }
+                "--format" => {
+                    format = match args.next().as_deref() {
+                        Some("json") => Format::Json,
+                        Some("geojson") => Format::GeoJson,
+                        _ => return Err("--format must be json or geojson".into()),
+                    };
+                }
+                "--encoding" => {
+                    encoding = match args.next().as_deref() {
+                        Some("identity") => Some(ContentEncoding::Identity),
+                        Some("gzip") => Some(ContentEncoding::Gzip),
+                        Some("br") => Some(ContentEncoding::Br),
+                        _ => return Err("--encoding must be identity, gzip, or br".into()),
+                    };
+                }
+                "-h" | "--help" => return Ok(None),
+                "-" => input = None,
+                _ if arg.starts_with("--") => return Err(format!("unknown option: {arg}").into()),
+                _ => input = Some(arg),
+            }
+        }
+        Ok(Some(Self {
+            coordinates: coordinates.ok_or("--tile is required")?,
+            format,
+            encoding,
+            input,
+        }))
+    }
+}
+
+/// Parses tile coordinates in the form of "z/x/y".
+fn parse_tile_coordinates(s: &str) -> Result<TileCoordinates, Error> {
+    let parts: Vec<u32> = s
+        .split('/')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("invalid tile coordinates: {s}: {e}"))?;
+    match parts.as_slice() {
+        &[zoom, x, y] => Ok(TileCoordinates::new(zoom, x, y)?),
+        _ => Err(format!("tile coordinates must be z/x/y: {s}").into()),
+    }
+}
+
+/// Reads the tile bytes from a given input.
+///
+/// Decodes the contents if they are Base64-encoded.
+/// Surrounding whitespace and double quotes are ignored, so that a JSON
+/// string returned by the `get-tile` Lambda function can be read as it is.
+///
+/// Then decompresses the contents with `encoding`, or with gzip if `encoding`
+/// is `None` and the contents start with the gzip magic bytes.
+fn read_tile_bytes(
+    input: Option<&str>,
+    encoding: Option<ContentEncoding>,
+) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    match input {
+        Some(path) => {
+            std::fs::File::open(path)?.read_to_end(&mut bytes)?;
+        }
+        None => {
+            std::io::stdin().read_to_end(&mut bytes)?;
+        }
+    }
+    let trimmed = bytes
+        .trim_ascii()
+        .strip_prefix(b"\"")
+        .and_then(|b| b.strip_suffix(b"\""))
+        .unwrap_or(bytes.trim_ascii());
+    let bytes = match base64_engine.decode(trimmed) {
+        Ok(decoded) => decoded,
+        Err(_) => bytes,
+    };
+    let encoding = encoding.unwrap_or(if bytes.starts_with(&GZIP_MAGIC_BYTES) {
+        ContentEncoding::Gzip
+    } else {
+        ContentEncoding::Identity
+    });
+    Ok(encoding.decompress(&bytes)?)
+}
+
+fn main() -> Result<(), Error> {
+    let Some(args) = Args::parse(std::env::args().skip(1)).inspect_err(|_| eprintln!("{USAGE}"))? else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+
+    let bytes = read_tile_bytes(args.input.as_deref(), args.encoding)?;
+    let tile = Tile::parse_from_bytes(&bytes)?;
+    let layers = decode_tile(&tile, &args.coordinates)?;
+
+    let output = match args.format {
+        Format::Json => serde_json::json!({ "layers": layers }),
+        Format::GeoJson => layers
+            .iter()
+            .map(|layer| {
+                let collection = FeatureCollection::from(layer);
+                Ok((layer.name.clone(), serde_json::to_value(collection)?))
+            })
+            .collect::<Result<serde_json::Map<_, _>, serde_json::Error>>()?
+            .into(),
+    };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Compression of map tiles.
+//!
+//! A client tells acceptable encodings by the `Accept-Encoding` header, and
+//! the server compresses the tile with one of them and tells the encoding by
+//! the `Content-Encoding` header.
+//!
+//! https://developer.mozilla.org/en-US/docs/Web/HTTP/Reference/Headers/Accept-Encoding
+
+use serde::Serialize;
+use std::io::{Read as _, Write as _};
+
+/// Compression level of gzip.
+pub const GZIP_LEVEL: u32 = 6;
+
+/// Quality of Brotli compression.
+///
+/// Brotli at the highest quality (11) is too slow for on-the-fly compression.
+pub const BROTLI_QUALITY: u32 = 5;
+
+/// Base-2 logarithm of the sliding window size of Brotli compression.
+pub const BROTLI_LG_WINDOW_SIZE: u32 = 22;
+
+/// Content encoding.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncoding {
+    /// No compression.
+    #[default]
+    Identity,
+    /// gzip.
+    Gzip,
+    /// Brotli.
+    Br,
+}
+
+impl ContentEncoding {
+    /// Encodings supported by the server in order of preference.
+    const PREFERENCE: [Self; 3] = [Self::Br, Self::Gzip, Self::Identity];
+
+    /// Chooses the encoding from a given `Accept-Encoding` header value.
+    ///
+    /// Chooses the encoding with the highest quality value, and prefers
+    /// Brotli, gzip, and identity in this order if quality values tie.
+    /// Falls back to [`ContentEncoding::Identity`] if no supported encoding is
+    /// acceptable.
+    pub fn negotiate(accept_encoding: &str) -> Self {
+        let accepted: Vec<(&str, f32)> = accept_encoding
+            .split(',')
+            .filter_map(parse_encoding_with_quality)
+            .collect();
+        let quality_of = |encoding: Self| -> f32 {
+            let find = |name: &str| accepted
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|&(_, q)| q);
+            find(encoding.as_str())
+                .or_else(|| if encoding == Self::Gzip { find("x-gzip") } else { None })
+                .or_else(|| find("*"))
+                // identity is always acceptable unless explicitly refused
+                .unwrap_or(if encoding == Self::Identity { f32::MIN_POSITIVE } else { 0.0 })
+        };
+        let mut best = (Self::Identity, 0.0);
+        for encoding in Self::PREFERENCE {
+            let q = quality_of(encoding);
+            if q > best.1 {
+                best = (encoding, q);
+            }
+        }
+        best.0
+    }
+
+    /// Returns the name of the encoding used in HTTP headers.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Identity => "identity",
+            Self::Gzip => "gzip",
+            Self::Br => "br",
+        }
+    }
+
+    /// Compresses given data with the encoding.
+    ///
+    /// Returns a copy of the data as it is for
+    /// [`ContentEncoding::Identity`].
+    pub fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Identity => Ok(data.to_vec()),
+            Self::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(
+                    Vec::new(),
+                    flate2::Compression::new(GZIP_LEVEL),
+                );
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Self::Br => {
+                let mut compressed = Vec::new();
+                {
+                    let mut encoder = brotli::CompressorWriter::new(
+                        &mut compressed,
+                        4096,
+                        BROTLI_QUALITY,
+                        BROTLI_LG_WINDOW_SIZE,
+                    );
+                    encoder.write_all(data)?;
+                    encoder.flush()?;
+                }
+                Ok(compressed)
+            }
+        }
+    }
+
+    /// Decompresses given data compressed with the encoding.
+    ///
+    /// Returns a copy of the data as it is for
+    /// [`ContentEncoding::Identity`].
+    pub fn decompress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        match self {
+            Self::Identity => decompressed.extend_from_slice(data),
+            Self::Gzip => {
+                flate2::read::GzDecoder::new(data).read_to_end(&mut decompressed)?;
+            }
+            Self::Br => {
+                brotli::Decompressor::new(data, 4096).read_to_end(&mut decompressed)?;
+            }
+        }
+        Ok(decompressed)
+    }
+}
+
+/// Parses an encoding with an optional quality value; e.g., "gzip;q=0.8".
+///
+/// The quality value defaults to 1.0.
+/// Returns `None` if the encoding is empty or the quality value is malformed.
+fn parse_encoding_with_quality(s: &str) -> Option<(&str, f32)> {
+    let mut parts = s.split(';').map(str::trim);
+    let name = parts.next().filter(|name| !name.is_empty())?;
+    let mut quality = 1.0;
+    for param in parts {
+        if let Some((key, value)) = param.split_once('=') {
+            if key.trim().eq_ignore_ascii_case("q") {
+                quality = value.trim().parse().ok()?;
+            }
+        }
+    }
+    Some((name, quality))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_encoding_negotiate() {
+        assert_eq!(ContentEncoding::negotiate(""), ContentEncoding::Identity);
+        assert_eq!(ContentEncoding::negotiate("gzip"), ContentEncoding::Gzip);
+        assert_eq!(ContentEncoding::negotiate("x-gzip"), ContentEncoding::Gzip);
+        assert_eq!(ContentEncoding::negotiate("gzip, deflate, br"), ContentEncoding::Br);
+        assert_eq!(ContentEncoding::negotiate("br;q=0.5, gzip;q=0.8"), ContentEncoding::Gzip);
+        assert_eq!(ContentEncoding::negotiate("br;q=0, gzip;q=0"), ContentEncoding::Identity);
+        assert_eq!(ContentEncoding::negotiate("deflate"), ContentEncoding::Identity);
+        assert_eq!(ContentEncoding::negotiate("*"), ContentEncoding::Br);
+        assert_eq!(ContentEncoding::negotiate("br;q=0, *;q=0.5"), ContentEncoding::Gzip);
+        assert_eq!(ContentEncoding::negotiate("GZIP"), ContentEncoding::Gzip);
+        assert_eq!(ContentEncoding::negotiate("br;q=abc, gzip"), ContentEncoding::Gzip);
+    }
+
+    #[test]
+    fn test_content_encoding_compress_round_trip() {
+        let data = b"dog's business ".repeat(100);
+
+        let compressed = ContentEncoding::Identity.compress(&data).unwrap();
+        assert_eq!(compressed, data);
+
+        let compressed = ContentEncoding::Gzip.compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+
+        let compressed = ContentEncoding::Br.compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        let mut decompressed = Vec::new();
+        brotli::Decompressor::new(compressed.as_slice(), 4096)
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    fn test_content_encoding_decompress() {
+        let data = b"dog's business ".repeat(100);
+        for encoding in [ContentEncoding::Identity, ContentEncoding::Gzip, ContentEncoding::Br] {
+            let compressed = encoding.compress(&data).unwrap();
+            assert_eq!(encoding.decompress(&compressed).unwrap(), data);
+        }
+        assert!(ContentEncoding::Gzip.decompress(&data).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Entity tags (ETags) of map tiles.
+//!
+//! A map tile is versioned by a digest of the business records in it, so that
+//! the version can be determined before encoding the tile. The digest covers
+//! the ID and every mutable field of each record; i.e., the business type,
+//! location, timestamp, advocacy flag, and masked dog ID. So adding, deleting,
+//! or editing a record, or flipping the advocacy setting changes the version.
+//!
+//! ETags are weak, because the same version may be encoded into different
+//! bytes; e.g., by another release of the compression library.
+//!
+//! https://developer.mozilla.org/en-US/docs/Web/HTTP/Reference/Headers/ETag
+
+use std::hash::{DefaultHasher, Hash, Hasher as _};
+
+use business_core::types::BusinessRecord;
+
+/// Version of a tile.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TileVersion {
+    /// Digest of the business records in the tile.
+    ///
+    /// Independent of the order of the records.
+    pub digest: u64,
+
+    /// Number of business records in the tile.
+    pub num_records: usize,
+}
+
+impl TileVersion {
+    /// Determines the version of a tile from given business records.
+    pub fn from_records(records: &[BusinessRecord]) -> Self {
+        // the order of records may vary between queries
+        let mut record_digests: Vec<u64> = records.iter().map(record_digest).collect();
+        record_digests.sort_unstable();
+        let mut hasher = DefaultHasher::new();
+        record_digests.hash(&mut hasher);
+        Self {
+            digest: hasher.finish(),
+            num_records: records.len(),
+        }
+    }
+
+    /// Makes a weak ETag of the tile.
+    ///
+    /// `variant` must distinguish representations of the same tile; e.g.,
+    /// request parameters, format, and content encoding.
+    pub fn make_etag(&self, variant: &impl Hash) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        variant.hash(&mut hasher);
+        format!("W/\"{:016x}\"", hasher.finish())
+    }
+}
+
+/// Calculates the digest of the ID and mutable fields of a business record.
+fn record_digest(record: &BusinessRecord) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    record.record_id.hash(&mut hasher);
+    record.business_type.to_string().hash(&mut hasher);
+    record.location.longitude.to_bits().hash(&mut hasher);
+    record.location.latitude.to_bits().hash(&mut hasher);
+    record.timestamp.hash(&mut hasher);
+    record.is_advocated.hash(&mut hasher);
+    record.masked_dog_id.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns if a given `If-None-Match` header value matches a given ETag.
+///
+/// ETags are compared by the weak comparison; i.e., ignoring the `W/`
+/// prefixes.
+pub fn if_none_match(header_value: &str, etag: &str) -> bool {
+    let etag = strip_weak_prefix(etag);
+    header_value
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || strip_weak_prefix(tag) == etag)
+}
+
+/// Removes the `W/` prefix from an ETag.
+#[inline]
+fn strip_weak_prefix(etag: &str) -> &str {
+    etag.strip_prefix("W/").unwrap_or(etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use business_core::types::{
+        BusinessRecordBuilder,
+        BusinessType,
+        GeolocationCoordinates,
+    };
+
+    fn record(record_id: &str, timestamp: i64) -> BusinessRecord {
+        BusinessRecordBuilder::default()
+            .record_id(record_id)
+            .dog_id(None)
+            .business_type(BusinessType::Pee)
+            .location(GeolocationCoordinates {
+                longitude: 139.7670506677,
+                latitude: 35.6814709332,
+            })
+            .timestamp(timestamp)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_tile_version_from_records() {
+        let version = TileVersion::from_records(&[
+            record("record_1", 1_755_317_141),
+            record("record_2", 1_755_320_741),
+        ]);
+        assert_eq!(version.num_records, 2);
+
+        // independent of the order of records
+        let reversed = TileVersion::from_records(&[
+            record("record_2", 1_755_320_741),
+            record("record_1", 1_755_317_141),
+        ]);
+        assert_eq!(version, reversed);
+
+        let version = TileVersion::from_records(&[]);
+        assert_eq!(version.num_records, 0);
+    }
+
+    #[test]
+    fn test_tile_version_make_etag() {
+        let records = [record("record_1", 1_755_317_141)];
+        let version = TileVersion::from_records(&records);
+        let etag = version.make_etag(&"mvt");
+        assert!(etag.starts_with("W/\"") && etag.ends_with('"'));
+        assert_eq!(etag, TileVersion::from_records(&records).make_etag(&"mvt"));
+        assert_ne!(etag, version.make_etag(&"geojson"));
+
+        // a new record changes the ETag
+        let newer = TileVersion::from_records(&[
+            record("record_1", 1_755_317_141),
+            record("record_2", 1_755_320_741),
+        ]);
+        assert_ne!(etag, newer.make_etag(&"mvt"));
+
+        // replacing a record with another one of the same timestamp changes
+        // the ETag
+        let replaced = TileVersion::from_records(&[record("record_2", 1_755_317_141)]);
+        assert_ne!(etag, replaced.make_etag(&"mvt"));
+    }
+
+    #[test]
+    fn test_tile_version_make_etag_with_edited_record() {
+        let original = record("record_1", 1_755_317_141);
+        let etag = TileVersion::from_records(std::slice::from_ref(&original)).make_etag(&"mvt");
+
+        // editing the business type changes the ETag
+        let mut edited = original.clone();
+        edited.business_type = BusinessType::Poo;
+        assert_ne!(etag, TileVersion::from_records(&[edited]).make_etag(&"mvt"));
+
+        // moving the record changes the ETag
+        let mut edited = original.clone();
+        edited.location.longitude += 0.0001;
+        assert_ne!(etag, TileVersion::from_records(&[edited]).make_etag(&"mvt"));
+    }
+
+    #[test]
+    fn test_tile_version_make_etag_with_advocacy_flip() {
+        let mut original = record("record_1", 1_755_317_141);
+        original.masked_dog_id = Some("masked_dog_1".to_string());
+        original.is_advocated = Some(false);
+        let etag = TileVersion::from_records(std::slice::from_ref(&original)).make_etag(&"mvt");
+
+        let mut flipped = original.clone();
+        flipped.is_advocated = Some(true);
+        assert_ne!(etag, TileVersion::from_records(&[flipped]).make_etag(&"mvt"));
+
+        // re-masking the dog ID changes the ETag
+        let mut remasked = original.clone();
+        remasked.masked_dog_id = Some("masked_dog_2".to_string());
+        assert_ne!(etag, TileVersion::from_records(&[remasked]).make_etag(&"mvt"));
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let etag = "W/\"0123456789abcdef\"";
+        assert!(if_none_match("W/\"0123456789abcdef\"", etag));
+        assert!(if_none_match("\"0123456789abcdef\"", etag));
+        assert!(if_none_match("\"fedcba9876543210\", W/\"0123456789abcdef\"", etag));
+        assert!(if_none_match("*", etag));
+        assert!(!if_none_match("W/\"fedcba9876543210\"", etag));
+        assert!(!if_none_match("", etag));
+    }
+}
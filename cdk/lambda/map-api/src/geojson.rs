@@ -0,0 +1,241 @@
+//! GeoJSON representation of map tiles.
+//!
+//! https://datatracker.ietf.org/doc/html/rfc7946
+
+use serde::Serialize;
+
+use business_core::types::GeolocationCoordinates;
+
+use crate::mvt::decoder::{DecodedFeature, DecodedGeometry, DecodedLayer};
+
+/// Position; i.e., longitude and latitude in this order.
+pub type Position = [f64; 2];
+
+/// GeoJSON `FeatureCollection`.
+#[derive(Clone, Debug, Serialize)]
+pub struct FeatureCollection {
+    /// Always "FeatureCollection".
+    #[serde(rename = "type")]
+    type_: &'static str,
+
+    /// Features.
+    pub features: Vec<Feature>,
+}
+
+impl FeatureCollection {
+    /// Creates a new [`FeatureCollection`] with given features.
+    pub fn new(features: Vec<Feature>) -> Self {
+        Self {
+            type_: "FeatureCollection",
+            features,
+        }
+    }
+}
+
+impl From<&DecodedLayer> for FeatureCollection {
+    fn from(layer: &DecodedLayer) -> Self {
+        Self::new(layer.features.iter().map(Into::into).collect())
+    }
+}
+
+/// GeoJSON `Feature`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Feature {
+    /// Always "Feature".
+    #[serde(rename = "type")]
+    type_: &'static str,
+
+    /// ID of the feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+
+    /// Geometry of the feature. `None` is serialized as `null`.
+    pub geometry: Option<Geometry>,
+
+    /// Properties of the feature.
+    pub properties: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Feature {
+    /// Creates a new [`Feature`].
+    pub fn new(
+        id: Option<u64>,
+        geometry: Option<Geometry>,
+        properties: serde_json::Map<String, serde_json::Value>,
+    ) -> Self {
+        Self {
+            type_: "Feature",
+            id,
+            geometry,
+            properties,
+        }
+    }
+}
+
+impl From<&DecodedFeature> for Feature {
+    fn from(feature: &DecodedFeature) -> Self {
+        let properties = feature.properties
+            .iter()
+            .map(|(key, value)| {
+                // serialization of a property value never fails
+                (key.clone(), serde_json::to_value(value).unwrap())
+            })
+            .collect();
+        Self::new(feature.id, (&feature.geometry).into(), properties)
+    }
+}
+
+/// GeoJSON geometry.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", content = "coordinates")]
+pub enum Geometry {
+    /// `Point`.
+    Point(Position),
+    /// `MultiPoint`.
+    MultiPoint(Vec<Position>),
+    /// `LineString`.
+    LineString(Vec<Position>),
+    /// `MultiLineString`.
+    MultiLineString(Vec<Vec<Position>>),
+    /// `Polygon`.
+    ///
+    /// Each ring must repeat the first position at the end.
+    Polygon(Vec<Vec<Position>>),
+    /// `MultiPolygon`.
+    ///
+    /// Each ring must repeat the first position at the end.
+    MultiPolygon(Vec<Vec<Vec<Position>>>),
+}
+
+impl From<&DecodedGeometry> for Option<Geometry> {
+    /// Converts a decoded geometry into a GeoJSON geometry.
+    ///
+    /// Single parts become `Point`, `LineString`, or `Polygon`, and multiple
+    /// parts become their `Multi*` counterparts.
+    /// Returns `None` if the geometry is unknown or empty.
+    fn from(geometry: &DecodedGeometry) -> Self {
+        match geometry {
+            DecodedGeometry::Point(points) => match points.as_slice() {
+                [] => None,
+                [point] => Some(Geometry::Point(position_from(point))),
+                points => Some(Geometry::MultiPoint(points.iter().map(position_from).collect())),
+            },
+            DecodedGeometry::LineString(lines) => match lines.as_slice() {
+                [] => None,
+                [line] => Some(Geometry::LineString(line.iter().map(position_from).collect())),
+                lines => Some(Geometry::MultiLineString(
+                    lines
+                        .iter()
+                        .map(|line| line.iter().map(position_from).collect())
+                        .collect(),
+                )),
+            },
+            DecodedGeometry::Polygon(polygons) => match polygons.as_slice() {
+                [] => None,
+                [polygon] => Some(Geometry::Polygon(polygon.iter().map(|r| closed_ring(r)).collect())),
+                polygons => Some(Geometry::MultiPolygon(
+                    polygons
+                        .iter()
+                        .map(|polygon| polygon.iter().map(|r| closed_ring(r)).collect())
+                        .collect(),
+                )),
+            },
+            DecodedGeometry::Unknown => None,
+        }
+    }
+}
+
+/// Converts a geographic location into a position.
+#[inline]
+pub fn position_from(location: &GeolocationCoordinates) -> Position {
+    [location.longitude, location.latitude]
+}
+
+/// Converts a ring into positions that repeat the first one at the end.
+fn closed_ring(ring: &[GeolocationCoordinates]) -> Vec<Position> {
+    ring.iter()
+        .chain(ring.first())
+        .map(position_from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::protos::PropertyValue;
+
+    fn location(longitude: f64, latitude: f64) -> GeolocationCoordinates {
+        GeolocationCoordinates { longitude, latitude }
+    }
+
+    #[test]
+    fn test_feature_from_decoded_point_feature() {
+        let feature = DecodedFeature {
+            id: Some(123),
+            geometry: DecodedGeometry::Point(vec![location(139.0, 35.0)]),
+            properties: vec![
+                ("recordId".to_string(), PropertyValue::String("record_1".to_string())),
+                ("timestamp".to_string(), PropertyValue::I64(1_755_317_141)),
+            ],
+        };
+        let feature: Feature = (&feature).into();
+        assert_eq!(
+            serde_json::to_value(&feature).unwrap(),
+            json!({
+                "type": "Feature",
+                "id": 123,
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [139.0, 35.0],
+                },
+                "properties": {
+                    "recordId": "record_1",
+                    "timestamp": 1_755_317_141,
+                },
+            }),
+        );
+    }
+
+    #[test]
+    fn test_geometry_from_decoded_polygon() {
+        let ring = vec![
+            location(0.0, 1.0),
+            location(1.0, 1.0),
+            location(1.0, 0.0),
+            location(0.0, 0.0),
+        ];
+        let geometry: Option<Geometry> = (&DecodedGeometry::Polygon(vec![vec![ring.clone()]])).into();
+        assert_eq!(
+            serde_json::to_value(&geometry).unwrap(),
+            json!({
+                "type": "Polygon",
+                "coordinates": [[[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0], [0.0, 1.0]]],
+            }),
+        );
+
+        let geometry: Option<Geometry> =
+            (&DecodedGeometry::Polygon(vec![vec![ring.clone()], vec![ring]])).into();
+        assert!(matches!(geometry, Some(Geometry::MultiPolygon(p)) if p.len() == 2));
+    }
+
+    #[test]
+    fn test_feature_collection_serialize() {
+        let collection = FeatureCollection::new(vec![
+            Feature::new(None, None, serde_json::Map::new()),
+        ]);
+        assert_eq!(
+            serde_json::to_value(&collection).unwrap(),
+            json!({
+                "type": "FeatureCollection",
+                "features": [{
+                    "type": "Feature",
+                    "geometry": null,
+                    "properties": {},
+                }],
+            }),
+        );
+    }
+}
@@ -4,8 +4,13 @@ use derive_builder::Builder;
 
 use business_core::types::BusinessType;
 
+pub mod anonymity;
+pub mod compression;
+pub mod etag;
+pub mod geojson;
 pub mod mvt;
 pub mod protos;
+pub mod tile_query;
 pub mod web_mercator;
 
 /// Business record.
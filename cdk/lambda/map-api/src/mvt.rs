@@ -2,42 +2,27 @@
 //!
 //! https://github.com/mapbox/vector-tile-spec
 
-use serde::Deserialize;
+use std::collections::HashMap;
 
-pub mod symbol;
+pub use business_core::mvt::TileCoordinates;
 
-/// Tile coordinates.
-#[derive(Clone, Debug, Deserialize)]
-pub struct TileCoordinates {
-    /// Zoom level of the tile.
-    pub zoom: u32,
+use crate::protos::PropertyValue;
+use crate::web_mercator::{tiles_per_edge_at_zoom, MAX_ZOOM};
 
-    /// X coordinate of the tile.
-    pub x: u32,
+pub mod budget;
+pub mod cluster;
+pub mod decoder;
+pub mod density;
+pub mod geometry;
+pub(crate) mod grid;
+pub mod symbol;
+pub mod tile_builder;
 
-    /// Y coordinate of the tile.
-    pub y: u32,
-}
+/// Vector tile version.
+pub const VECTOR_TILE_VERSION: u32 = 2;
 
-impl TileCoordinates {
-    /// Creates a new `TileCoordinates` that zooms out to a given level.
-    ///
-    /// Returns `None` if `new_zoom` is larger than the current zoom level.
-    pub fn zoom_out_to(&self, new_zoom: u32) -> Option<Self> {
-        if new_zoom > self.zoom {
-            return None;
-        }
-        let mut x = self.x;
-        let mut y = self.y;
-        let mut zoom = self.zoom;
-        while zoom > new_zoom {
-            x /= 2;
-            y /= 2;
-            zoom -= 1;
-        }
-        Some(Self { zoom, x, y })
-    }
-}
+/// Tile extent.
+pub const TILE_EXTENT: u32 = 4096;
 
 /// Error related to Mapbox vector tile (mvt) processing.
 #[derive(Debug, thiserror::Error)]
@@ -48,6 +33,18 @@ pub enum MvtError {
     /// Duplicate record ID.
     #[error("duplicate business record ID: {0}")]
     DuplicateRecordId(String),
+    /// Invalid geometry.
+    #[error("invalid geometry: {0}")]
+    InvalidGeometry(String),
+    /// Invalid feature tags.
+    #[error("invalid tags: {0}")]
+    InvalidTags(String),
+    /// Unsupported property value.
+    #[error("unsupported property value: {0}")]
+    UnsupportedValue(String),
+    /// Duplicate layer name in a tile.
+    #[error("duplicate layer name: {0}")]
+    DuplicateLayerName(String),
 }
 
 /// Zigzag-encodes a given number.
@@ -58,6 +55,131 @@ pub const fn zigzag(n: u32) -> u32 {
     (n << 1) ^ (n >> 31)
 }
 
+/// Zigzag-encodes a given signed number.
+///
+/// Use this function to encode relative coordinates which may be negative.
+///
+/// See https://github.com/mapbox/vector-tile-spec/tree/master/2.1#432-parameter-integers
+#[inline]
+pub const fn zigzag_signed(n: i32) -> u32 {
+    ((n << 1) ^ (n >> 31)) as u32
+}
+
+/// Decodes a zigzag-encoded number.
+///
+/// Inverse of [`zigzag_signed`].
+#[inline]
+pub const fn zigzag_decode(n: u32) -> i32 {
+    ((n >> 1) as i32) ^ -((n & 1) as i32)
+}
+
+/// Frequencies of property values in a layer.
+///
+/// More frequent values shall be assigned lower value indices.
+/// Values with the same frequency are ordered by their natural order, so
+/// that the same values always produce the same layer.
+#[derive(Clone, Debug, Default)]
+pub struct PropertyValueFrequencies(HashMap<PropertyValue, u64>);
+
+impl PropertyValueFrequencies {
+    /// Creates an empty [`PropertyValueFrequencies`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a given value.
+    #[inline]
+    pub fn add(&mut self, value: impl Into<PropertyValue>) {
+        self.0
+            .entry(value.into())
+            .and_modify(|freq| *freq += 1)
+            .or_insert(1);
+    }
+
+    /// Returns the frequency of a given value.
+    #[inline]
+    pub fn get(&self, value: &PropertyValue) -> Option<u64> {
+        self.0.get(value).copied()
+    }
+
+    /// Returns the number of distinct values.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns if there is no value.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Sorts the values by frequency in descending order.
+    ///
+    /// Ties are broken by the natural order of values.
+    ///
+    /// Returns the sorted values and the value → index map.
+    pub fn into_sorted_values(self) -> (Vec<PropertyValue>, HashMap<PropertyValue, u32>) {
+        let mut value_freqs: Vec<(PropertyValue, u64)> = self.0.into_iter().collect();
+        value_freqs.sort_unstable_by(|(a, m), (b, n)| n.cmp(m).then_with(|| a.cmp(b)));
+        let values: Vec<PropertyValue> = value_freqs
+            .into_iter()
+            .map(|(value, _)| value)
+            .collect();
+        let value_to_index: HashMap<PropertyValue, u32> = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (value.clone(), i as u32))
+            .collect();
+        (values, value_to_index)
+    }
+}
+
+/// Calculates the feature ID for a feature at a given index in a tile.
+///
+/// A feature ID bits layout depends on the zoom level:
+/// - z=0 → [i:59bits][00000]
+/// - z=1 → [i:57bits][x][y][00001]
+/// - z=2 → [i:55bits][xx][yy][00010]
+/// - z=3 → [i:53bits][xxx][yyy][00011]
+/// - ...
+/// - z=22 → [i:15bits][x:22bits][y:22bits][10110]
+///
+/// This means the larger the zoom level, the less bits are available for
+/// features in a tile. However, it is reasonable, because the larger the
+/// zoom level, the less features should be present in a tile.
+///
+/// A feature ID does not identify a business record but it guarantees
+/// - no two business records within a tile have the same feature ID
+/// - no two tiles have features with the same feature ID
+///
+/// So you should not depend on feature IDs to identify business records,
+/// but use the `recordId` property instead.
+///
+/// ### Panics
+///
+/// - if the zoom level is greater than [`MAX_ZOOM`]
+/// - if the x coordinate is greater than or equal to the number of tiles
+///   per edge at the zoom level
+/// - if the y coordinate is greater than or equal to the number of tiles
+///   per edge at the zoom level
+/// - if `index` cannot be represented by the available bits for the
+///   index at the zoom level
+#[inline]
+pub fn make_feature_id(coordinates: &TileCoordinates, i: usize) -> u64 {
+    assert!(coordinates.zoom() <= MAX_ZOOM);
+    assert!(coordinates.x() < tiles_per_edge_at_zoom(coordinates.zoom()) as u32);
+    assert!(coordinates.y() < tiles_per_edge_at_zoom(coordinates.zoom()) as u32);
+    const Z_BITS: u32 = 5;
+    let x_bits = coordinates.zoom();
+    let y_bits = coordinates.zoom();
+    assert!((i as u64) < (1 << (u64::BITS - (x_bits + y_bits + Z_BITS))));
+    ((i as u64) << (x_bits + y_bits + Z_BITS)) |
+        ((coordinates.x() as u64) << (y_bits + Z_BITS)) |
+        ((coordinates.y() as u64) << Z_BITS) |
+        coordinates.zoom() as u64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,28 +187,28 @@ mod tests {
     #[test]
     fn test_zoom_out() {
         // z = 1 → 0
-        let coords = TileCoordinates { zoom: 1, x: 1, y: 1 };
+        let coords = TileCoordinates::new(1, 1, 1).unwrap();
         let new_coords = coords.zoom_out_to(0).unwrap();
-        assert_eq!(new_coords.zoom, 0);
-        assert_eq!(new_coords.x, 0);
-        assert_eq!(new_coords.y, 0);
+        assert_eq!(new_coords.zoom(), 0);
+        assert_eq!(new_coords.x(), 0);
+        assert_eq!(new_coords.y(), 0);
 
         // z = 12 → 10
-        let coords = TileCoordinates { zoom: 12, x: 1234, y: 3333 };
+        let coords = TileCoordinates::new(12, 1234, 3333).unwrap();
         let new_coords = coords.zoom_out_to(10).unwrap();
-        assert_eq!(new_coords.zoom, 10);
-        assert_eq!(new_coords.x, 308);
-        assert_eq!(new_coords.y, 833);
+        assert_eq!(new_coords.zoom(), 10);
+        assert_eq!(new_coords.x(), 308);
+        assert_eq!(new_coords.y(), 833);
 
         // z = 16 → 16
-        let coords = TileCoordinates { zoom: 16, x: 58138, y: 25860 };
+        let coords = TileCoordinates::new(16, 58138, 25860).unwrap();
         let new_coords = coords.zoom_out_to(16).unwrap();
-        assert_eq!(new_coords.zoom, 16);
-        assert_eq!(new_coords.x, 58138);
-        assert_eq!(new_coords.y, 25860);
+        assert_eq!(new_coords.zoom(), 16);
+        assert_eq!(new_coords.x(), 58138);
+        assert_eq!(new_coords.y(), 25860);
 
         // z = 0 → 1
-        let coords = TileCoordinates { zoom: 0, x: 0, y: 0 };
+        let coords = TileCoordinates::new(0, 0, 0).unwrap();
         assert!(coords.zoom_out_to(1).is_none());
     }
 
@@ -99,4 +221,75 @@ mod tests {
         assert_eq!(zigzag(0xFFFFFFFE), 0xFFFFFFFD);
         assert_eq!(zigzag(0xFFFFFFFF), 0xFFFFFFFF);
     }
+
+    #[test]
+    fn test_zigzag_signed() {
+        assert_eq!(zigzag_signed(0), 0);
+        assert_eq!(zigzag_signed(-1), 1);
+        assert_eq!(zigzag_signed(1), 2);
+        assert_eq!(zigzag_signed(-2), 3);
+        assert_eq!(zigzag_signed(4095), 8190);
+        assert_eq!(zigzag_signed(-4096), 8191);
+        assert_eq!(zigzag_signed(i32::MAX), 0xFFFFFFFE);
+        assert_eq!(zigzag_signed(i32::MIN), 0xFFFFFFFF);
+    }
+
+    #[test]
+    fn test_zigzag_decode() {
+        for n in [0, -1, 1, -2, 4095, -4096, i32::MAX, i32::MIN] {
+            assert_eq!(zigzag_decode(zigzag_signed(n)), n);
+        }
+    }
+
+    #[test]
+    fn test_property_value_frequencies_into_sorted_values() {
+        let mut freqs = PropertyValueFrequencies::new();
+        assert!(freqs.is_empty());
+        freqs.add("pee".to_string());
+        freqs.add(3);
+        freqs.add("pee".to_string());
+        freqs.add(3);
+        freqs.add(3);
+        freqs.add("poo".to_string());
+        assert_eq!(freqs.len(), 3);
+        assert_eq!(freqs.get(&3.into()), Some(3));
+        assert_eq!(freqs.get(&"pee".to_string().into()), Some(2));
+        assert_eq!(freqs.get(&"dog".to_string().into()), None);
+
+        let (values, value_to_index) = freqs.into_sorted_values();
+        assert_eq!(values, vec![
+            3.into(),
+            "pee".to_string().into(),
+            "poo".to_string().into(),
+        ]);
+        assert_eq!(value_to_index.len(), 3);
+        assert_eq!(*value_to_index.get(&3.into()).unwrap(), 0);
+        assert_eq!(*value_to_index.get(&"pee".to_string().into()).unwrap(), 1);
+        assert_eq!(*value_to_index.get(&"poo".to_string().into()).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_property_value_frequencies_into_sorted_values_ties() {
+        let mut freqs = PropertyValueFrequencies::new();
+        for value in [
+            PropertyValue::from(true),
+            10.into(),
+            "poo".to_string().into(),
+            1.into(),
+            "pee".to_string().into(),
+            0.5.into(),
+        ] {
+            freqs.add(value);
+        }
+        freqs.add(10);
+        let (values, _) = freqs.into_sorted_values();
+        assert_eq!(values, vec![
+            10.into(),
+            "pee".to_string().into(),
+            "poo".to_string().into(),
+            1.into(),
+            0.5.into(),
+            true.into(),
+        ]);
+    }
 }
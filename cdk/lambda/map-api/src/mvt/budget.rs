@@ -0,0 +1,178 @@
+//! Size budget of layers in a vector tile.
+//!
+//! A layer that would exceed its budget is thinned by dropping features
+//! deterministically; i.e., the same features always produce the same
+//! layer.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use crate::mvt::TILE_EXTENT;
+
+/// Budget of a layer.
+///
+/// No limit is imposed by default.
+#[derive(Clone, Debug, Default)]
+pub struct LayerBudget {
+    /// Maximum number of features in the layer.
+    pub max_features: Option<usize>,
+
+    /// Maximum number of bytes of the encoded layer.
+    pub max_bytes: Option<usize>,
+
+    /// How to thin features.
+    pub strategy: ThinningStrategy,
+}
+
+impl LayerBudget {
+    /// Returns if a layer with given numbers of features and bytes is within
+    /// the budget.
+    pub fn allows(&self, num_features: usize, num_bytes: usize) -> bool {
+        self.max_features.is_none_or(|max| num_features <= max) &&
+            self.max_bytes.is_none_or(|max| num_bytes <= max)
+    }
+}
+
+/// Strategy to thin features.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ThinningStrategy {
+    /// Keeps the newest features.
+    #[default]
+    Newest,
+
+    /// Keeps a spatially uniform sample of features.
+    ///
+    /// The tile is divided into `grid_size` × `grid_size` cells, and the
+    /// newest remaining feature is taken from each non-empty cell in turn.
+    SpatiallyUniform {
+        /// Number of grid cells per tile edge.
+        grid_size: u32,
+    },
+}
+
+/// Candidate feature to keep.
+#[derive(Clone, Debug)]
+pub struct ThinningCandidate<'a> {
+    /// Position of the feature in extent units.
+    ///
+    /// May be outside of `[0, TILE_EXTENT)` if the feature is in the buffer
+    /// around the tile.
+    pub position: (i32, i32),
+
+    /// Timestamp of the feature.
+    pub timestamp: i64,
+
+    /// Unique key of the feature to break ties; e.g., record ID.
+    pub key: &'a str,
+}
+
+impl ThinningStrategy {
+    /// Chooses at most `n` candidates to keep.
+    ///
+    /// Returns the indices of the chosen candidates in ascending order, so
+    /// that the kept features retain their original order.
+    pub fn choose(&self, candidates: &[ThinningCandidate], n: usize) -> Vec<usize> {
+        if n >= candidates.len() {
+            return (0..candidates.len()).collect();
+        }
+        let mut newest_first: Vec<usize> = (0..candidates.len()).collect();
+        newest_first.sort_by_key(|&i| (Reverse(candidates[i].timestamp), candidates[i].key));
+        let mut chosen: Vec<usize> = match *self {
+            Self::Newest => newest_first.into_iter().take(n).collect(),
+            Self::SpatiallyUniform { grid_size } => {
+                // bins the candidates into cells in the newest-first order
+                let grid_size = grid_size.clamp(1, TILE_EXTENT) as i32;
+                let cell_size = TILE_EXTENT as i32 / grid_size;
+                let mut cells: BTreeMap<(i32, i32), Vec<usize>> = BTreeMap::new();
+                for i in newest_first {
+                    let (u, v) = candidates[i].position;
+                    let cell = (
+                        v.div_euclid(cell_size).clamp(0, grid_size - 1),
+                        u.div_euclid(cell_size).clamp(0, grid_size - 1),
+                    );
+                    cells.entry(cell).or_default().push(i);
+                }
+                // takes one candidate from each cell in turn
+                let mut chosen = Vec::with_capacity(n);
+                let mut round = 0;
+                while chosen.len() < n {
+                    chosen.extend(
+                        cells
+                            .values()
+                            .filter_map(|cell| cell.get(round))
+                            .take(n - chosen.len()),
+                    );
+                    round += 1;
+                }
+                chosen
+            }
+        };
+        chosen.sort_unstable();
+        chosen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(position: (i32, i32), timestamp: i64, key: &str) -> ThinningCandidate<'_> {
+        ThinningCandidate {
+            position,
+            timestamp,
+            key,
+        }
+    }
+
+    #[test]
+    fn test_layer_budget_allows() {
+        assert!(LayerBudget::default().allows(usize::MAX, usize::MAX));
+
+        let budget = LayerBudget {
+            max_features: Some(10),
+            max_bytes: Some(1000),
+            ..Default::default()
+        };
+        assert!(budget.allows(10, 1000));
+        assert!(!budget.allows(11, 1000));
+        assert!(!budget.allows(10, 1001));
+    }
+
+    #[test]
+    fn test_thinning_strategy_newest() {
+        let candidates = [
+            candidate((0, 0), 100, "a"),
+            candidate((0, 0), 300, "b"),
+            candidate((0, 0), 200, "c"),
+            candidate((0, 0), 300, "d"),
+        ];
+        let strategy = ThinningStrategy::Newest;
+        assert_eq!(strategy.choose(&candidates, 2), vec![1, 3]);
+        assert_eq!(strategy.choose(&candidates, 3), vec![1, 2, 3]);
+        assert_eq!(strategy.choose(&candidates, 5), vec![0, 1, 2, 3]);
+        assert_eq!(strategy.choose(&candidates, 0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_thinning_strategy_spatially_uniform() {
+        // three in the top-left cell and one in the bottom-right cell
+        let candidates = [
+            candidate((10, 10), 300, "a"),
+            candidate((20, 20), 200, "b"),
+            candidate((30, 30), 100, "c"),
+            candidate((4000, 4000), 0, "d"),
+        ];
+        let strategy = ThinningStrategy::SpatiallyUniform { grid_size: 2 };
+        // the oldest one survives because it is alone in the cell
+        assert_eq!(strategy.choose(&candidates, 2), vec![0, 3]);
+        assert_eq!(strategy.choose(&candidates, 3), vec![0, 1, 3]);
+
+        // candidates in the buffer belong to the cells on the edges
+        let candidates = [
+            candidate((-10, -10), 0, "a"),
+            candidate((10, 10), 100, "b"),
+            candidate((4100, 10), 0, "c"),
+        ];
+        assert_eq!(strategy.choose(&candidates, 2), vec![1, 2]);
+    }
+}
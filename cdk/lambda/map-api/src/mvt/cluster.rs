@@ -0,0 +1,311 @@
+//! MVT for clusters of business records.
+//!
+//! Business records in a tile are grouped into clusters on a square grid over
+//! the tile. Each non-empty grid cell becomes a cluster which is located at
+//! the centroid of the business records in the cell.
+
+use std::collections::HashMap;
+
+use business_core::types::{BusinessRecord, BusinessType};
+
+use crate::mvt::{MvtError, TileCoordinates};
+use crate::mvt::geometry::encode_point;
+use crate::mvt::grid::{TileGrid, build_grid_layer};
+use crate::protos::vector_tile::{Tile, tile::{GeomType, Layer}};
+
+/// Layer name.
+pub const LAYER_NAME: &str = "business_clusters";
+
+/// Default number of grid cells per tile edge.
+pub const DEFAULT_GRID_SIZE: u32 = 16;
+
+/// Buffer for clusters of business records in a vector tile.
+///
+/// Use this buffer to build a vector tile which contains clusters of
+/// business records.
+pub struct BusinessClusterBuffer {
+    /// Grid over the tile.
+    grid: TileGrid,
+
+    /// Clusters in the buffer.
+    ///
+    /// Keys are grid cell coordinates.
+    clusters: HashMap<(u32, u32), BusinessCluster>,
+}
+
+impl BusinessClusterBuffer {
+    /// Creates a new [`BusinessClusterBuffer`] for given tile coordinates.
+    ///
+    /// `grid_size` is the number of grid cells per tile edge. It is clamped to
+    /// the range `[1, TILE_EXTENT]`, and then rounded down to a power of two
+    /// so that the grid cells evenly divide [`TILE_EXTENT`].
+    ///
+    /// [`TILE_EXTENT`]: crate::mvt::TILE_EXTENT
+    pub fn new(coordinates: TileCoordinates, grid_size: u32) -> Self {
+        Self {
+            grid: TileGrid::new(coordinates, grid_size),
+            clusters: HashMap::new(),
+        }
+    }
+
+    /// Appends a given business record to the cluster it belongs to.
+    ///
+    /// May return the following error:
+    /// - [`MvtError::OutsideOfTile`]: if the tile does not contain the
+    ///   record's location
+    pub fn append_business_record(&mut self, record: &BusinessRecord) -> Result<(), MvtError> {
+        if !self.grid.contains_location(&record.location) {
+            return Err(MvtError::OutsideOfTile);
+        }
+        let (u, v) = self.grid.uv_from_location(&record.location);
+        self.clusters
+            .entry(self.grid.cell_from_uv((u, v)))
+            .or_default()
+            .add(record, u, v);
+        Ok(())
+    }
+
+    /// Returns the number of clusters in the buffer.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.clusters.len()
+    }
+
+    /// Returns if the buffer has no clusters.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.clusters.is_empty()
+    }
+}
+
+/// Cluster of business records.
+#[derive(Clone, Debug, Default)]
+struct BusinessCluster {
+    /// Number of business records.
+    point_count: u64,
+    /// Number of pee records.
+    pee_count: u64,
+    /// Number of poo records.
+    poo_count: u64,
+    /// Newest timestamp.
+    newest_timestamp: i64,
+    /// Sum of u coordinates to calculate the centroid.
+    sum_u: u64,
+    /// Sum of v coordinates to calculate the centroid.
+    sum_v: u64,
+}
+
+impl BusinessCluster {
+    /// Adds a business record at given u and v coordinates.
+    fn add(&mut self, record: &BusinessRecord, u: u32, v: u32) {
+        if self.point_count == 0 || record.timestamp > self.newest_timestamp {
+            self.newest_timestamp = record.timestamp;
+        }
+        self.point_count += 1;
+        match record.business_type {
+            BusinessType::Pee => self.pee_count += 1,
+            BusinessType::Poo => self.poo_count += 1,
+        }
+        self.sum_u += u as u64;
+        self.sum_v += v as u64;
+    }
+
+    /// Returns the centroid in the tile.
+    fn centroid(&self) -> (u32, u32) {
+        (
+            (self.sum_u / self.point_count) as u32,
+            (self.sum_v / self.point_count) as u32,
+        )
+    }
+
+    /// Returns the property values in the order of the property keys.
+    fn property_values(&self) -> [i64; 4] {
+        [
+            self.point_count as i64,
+            self.pee_count as i64,
+            self.poo_count as i64,
+            self.newest_timestamp,
+        ]
+    }
+}
+
+impl From<BusinessClusterBuffer> for Tile {
+    fn from(buffer: BusinessClusterBuffer) -> Self {
+        let mut tile = Tile::new();
+        tile.layers.push(buffer.into());
+        tile
+    }
+}
+
+impl From<BusinessClusterBuffer> for Layer {
+    fn from(buffer: BusinessClusterBuffer) -> Self {
+        build_grid_layer(
+            LAYER_NAME,
+            &buffer.grid,
+            buffer.clusters,
+            GeomType::POINT,
+            &PROPERTY_KEYS,
+            |_, cluster| {
+                let (u, v) = cluster.centroid();
+                (encode_point((u as i32, v as i32)), cluster.property_values())
+            },
+        )
+    }
+}
+
+/// Key index and name for the `point_count` property.
+const PROPERTY_KEY_POINT_COUNT: (u32, &str) = (0, "point_count");
+/// Key index and name for the `pee_count` property.
+const PROPERTY_KEY_PEE_COUNT: (u32, &str) = (1, "pee_count");
+/// Key index and name for the `poo_count` property.
+const PROPERTY_KEY_POO_COUNT: (u32, &str) = (2, "poo_count");
+/// Key index and name for the `newest_timestamp` property.
+const PROPERTY_KEY_NEWEST_TIMESTAMP: (u32, &str) = (3, "newest_timestamp");
+
+/// Property keys in the order of [`BusinessCluster::property_values`].
+const PROPERTY_KEYS: [(u32, &str); 4] = [
+    PROPERTY_KEY_POINT_COUNT,
+    PROPERTY_KEY_PEE_COUNT,
+    PROPERTY_KEY_POO_COUNT,
+    PROPERTY_KEY_NEWEST_TIMESTAMP,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mvt::grid::fixtures::*;
+
+    #[test]
+    fn test_business_cluster_buffer_append_business_record() {
+        let mut buffer = BusinessClusterBuffer::new(
+            TileCoordinates::new(0, 0, 0).unwrap(),
+            DEFAULT_GRID_SIZE,
+        );
+        assert!(buffer.is_empty());
+        buffer
+            .append_business_record(&make_record("r1", BusinessType::Pee, &TOKYO, 1))
+            .unwrap();
+        buffer
+            .append_business_record(&make_record("r2", BusinessType::Poo, &SHINJUKU_STATION, 2))
+            .unwrap();
+        // Tokyo and Shinjuku are in the same cell at zoom level 0
+        assert_eq!(buffer.len(), 1);
+        buffer
+            .append_business_record(&make_record("r3", BusinessType::Poo, &PITTSBURGH, 3))
+            .unwrap();
+        assert_eq!(buffer.len(), 2);
+    }
+
+    #[test]
+    fn test_business_cluster_buffer_append_business_record_outside_of_tile() {
+        let mut buffer = BusinessClusterBuffer::new(
+            TileCoordinates::new(1, 1, 0).unwrap(),
+            DEFAULT_GRID_SIZE,
+        );
+        assert!(
+            buffer
+                .append_business_record(&make_record("r1", BusinessType::Pee, &TOKYO, 1))
+                .is_ok(),
+        );
+        assert!(
+            matches!(
+                buffer.append_business_record(&make_record("r2", BusinessType::Poo, &PITTSBURGH, 2)),
+                Err(MvtError::OutsideOfTile),
+            ),
+        );
+    }
+
+    #[test]
+    fn test_business_cluster_buffer_append_business_record_non_divisor_grid_size() {
+        let mut buffer = BusinessClusterBuffer::new(
+            TileCoordinates::new(0, 0, 0).unwrap(),
+            3,
+        );
+        buffer
+            .append_business_record(&make_record("r1", BusinessType::Pee, &TOKYO, 1))
+            .unwrap();
+        buffer
+            .append_business_record(&make_record("r2", BusinessType::Poo, &PITTSBURGH, 2))
+            .unwrap();
+        buffer
+            .append_business_record(&make_record("r3", BusinessType::Pee, &SOUTH_PACIFIC, 3))
+            .unwrap();
+        // no clusters beyond the 2×2 grid
+        let mut cells: Vec<(u32, u32)> = buffer.clusters.keys().copied().collect();
+        cells.sort();
+        assert_eq!(cells, vec![(0, 0), (1, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn test_business_cluster_buffer_into_tile() {
+        let mut buffer = BusinessClusterBuffer::new(
+            TileCoordinates::new(0, 0, 0).unwrap(),
+            DEFAULT_GRID_SIZE,
+        );
+        buffer
+            .append_business_record(&make_record("r1", BusinessType::Pee, &TOKYO, 487_588))
+            .unwrap();
+        buffer
+            .append_business_record(&make_record("r2", BusinessType::Poo, &SHINJUKU_STATION, 487_590))
+            .unwrap();
+        buffer
+            .append_business_record(&make_record("r3", BusinessType::Pee, &SHINJUKU_STATION, 487_589))
+            .unwrap();
+        buffer
+            .append_business_record(&make_record("r4", BusinessType::Poo, &PITTSBURGH, 487_590))
+            .unwrap();
+
+        let tile: Tile = buffer.into();
+        assert_eq!(tile.layers.len(), 1);
+
+        let layer = &tile.layers[0];
+        assert_eq!(layer.version.unwrap(), 2);
+        assert_eq!(layer.name.as_ref().unwrap(), "business_clusters");
+        assert_eq!(layer.extent.unwrap(), 4096);
+        assert_eq!(layer.keys, vec!["point_count", "pee_count", "poo_count", "newest_timestamp"]);
+        assert_eq!(layer.features.len(), 2);
+
+        // values are sorted by frequency in descending order, and then by
+        // the natural order of values
+        let values = &layer.values;
+        assert_eq!(values.len(), 5);
+        // - 3 times
+        assert!(expect_int(1)(&values[0]));
+        // - twice
+        assert!(expect_int(487_590)(&values[1]));
+        // - once
+        assert!(expect_int(0)(&values[2]));
+        assert!(expect_int(2)(&values[3]));
+        assert!(expect_int(3)(&values[4]));
+        let (i_1, i_487_590, i_0, i_2, i_3) = (0, 1, 2, 3, 4);
+
+        // features are ordered row by row; Pittsburgh comes first
+        let features = &layer.features;
+        assert_eq!(features[0].id.unwrap(), 0);
+        assert!(matches!(features[0].type_.unwrap().unwrap(), GeomType::POINT));
+        assert_eq!(features[0].tags, vec![0, i_1, 1, i_0, 2, i_1, 3, i_487_590]);
+        assert_eq!(features[0].geometry, vec![9, 2274, 3088]);
+
+        assert_eq!(features[1].id.unwrap(), 0x20);
+        assert!(matches!(features[1].type_.unwrap().unwrap(), GeomType::POINT));
+        assert_eq!(features[1].tags, vec![0, i_3, 1, i_2, 2, i_1, 3, i_487_590]);
+        // centroid of (3638, 1612), (3637, 1612), (3637, 1612)
+        assert_eq!(features[1].geometry, vec![9, 7274, 3224]);
+    }
+
+    #[test]
+    fn test_business_cluster_buffer_into_tile_empty() {
+        let buffer = BusinessClusterBuffer::new(
+            TileCoordinates::new(16, 58138, 25860).unwrap(),
+            DEFAULT_GRID_SIZE,
+        );
+
+        let tile: Tile = buffer.into();
+        let layer = &tile.layers[0];
+        assert_eq!(layer.name.as_ref().unwrap(), "business_clusters");
+        assert_eq!(layer.keys.len(), 0);
+        assert_eq!(layer.values.len(), 0);
+        assert_eq!(layer.features.len(), 0);
+    }
+}
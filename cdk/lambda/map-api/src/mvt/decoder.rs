@@ -0,0 +1,454 @@
+//! Decoder of MVT.
+//!
+//! Turns a [`Tile`] back into typed features:
+//! - geometries are decoded into longitudes and latitudes
+//! - tags are resolved into property keys and values
+//!
+//! Use this module to verify tiles and to inspect them.
+
+use serde::Serialize;
+
+use business_core::types::GeolocationCoordinates;
+
+use crate::mvt::{MvtError, TileCoordinates, TILE_EXTENT};
+use crate::mvt::geometry::{decode_geometry, ring_area, Point};
+use crate::protos::{
+    PropertyValue,
+    vector_tile::{Tile, tile::{Feature, GeomType, Layer, Value}},
+};
+use crate::web_mercator::{
+    latitude_from_fractional_y_at_zoom,
+    longitude_from_fractional_x_at_zoom,
+};
+
+/// Decoded layer.
+#[derive(Clone, Debug, Serialize)]
+pub struct DecodedLayer {
+    /// Name of the layer.
+    pub name: String,
+
+    /// Version of the layer.
+    pub version: u32,
+
+    /// Extent of the layer.
+    pub extent: u32,
+
+    /// Property keys in the layer.
+    pub keys: Vec<String>,
+
+    /// Property values in the layer.
+    pub values: Vec<PropertyValue>,
+
+    /// Features in the layer.
+    pub features: Vec<DecodedFeature>,
+}
+
+/// Decoded feature.
+#[derive(Clone, Debug, Serialize)]
+pub struct DecodedFeature {
+    /// ID of the feature.
+    pub id: Option<u64>,
+
+    /// Geometry of the feature.
+    pub geometry: DecodedGeometry,
+
+    /// Properties of the feature in the order of the tags.
+    #[serde(serialize_with = "serialize_properties")]
+    pub properties: Vec<(String, PropertyValue)>,
+}
+
+impl DecodedFeature {
+    /// Returns the value of a given property.
+    pub fn get_property(&self, key: &str) -> Option<&PropertyValue> {
+        self.properties
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+}
+
+/// Decoded geometry.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", content = "coordinates")]
+pub enum DecodedGeometry {
+    /// Points.
+    Point(Vec<GeolocationCoordinates>),
+    /// Line strings.
+    LineString(Vec<Vec<GeolocationCoordinates>>),
+    /// Polygons.
+    ///
+    /// Each polygon consists of the exterior ring followed by the interior
+    /// rings. Rings do not repeat the first point at the end.
+    Polygon(Vec<Vec<Vec<GeolocationCoordinates>>>),
+    /// Unknown geometry type.
+    Unknown,
+}
+
+/// Decodes a given tile at given coordinates.
+///
+/// Fails if any layer is malformed.
+pub fn decode_tile(
+    tile: &Tile,
+    coordinates: &TileCoordinates,
+) -> Result<Vec<DecodedLayer>, MvtError> {
+    tile.layers
+        .iter()
+        .map(|layer| decode_layer(layer, coordinates))
+        .collect()
+}
+
+/// Decodes a given layer in a tile at given coordinates.
+///
+/// Fails if any feature is malformed:
+/// - [`MvtError::InvalidGeometry`]: if the geometry is malformed
+/// - [`MvtError::InvalidTags`]: if the tags do not match the keys and values
+/// - [`MvtError::UnsupportedValue`]: if a property value is not supported
+pub fn decode_layer(
+    layer: &Layer,
+    coordinates: &TileCoordinates,
+) -> Result<DecodedLayer, MvtError> {
+    let extent = layer.extent.unwrap_or(TILE_EXTENT);
+    let values: Vec<PropertyValue> = layer.values
+        .iter()
+        .map(property_value_from)
+        .collect::<Result<_, _>>()?;
+    let features = layer.features
+        .iter()
+        .map(|feature| decode_feature(feature, layer, &values, coordinates, extent))
+        .collect::<Result<_, _>>()?;
+    Ok(DecodedLayer {
+        name: layer.name().to_string(),
+        version: layer.version(),
+        extent,
+        keys: layer.keys.clone(),
+        values,
+        features,
+    })
+}
+
+fn decode_feature(
+    feature: &Feature,
+    layer: &Layer,
+    values: &[PropertyValue],
+    coordinates: &TileCoordinates,
+    extent: u32,
+) -> Result<DecodedFeature, MvtError> {
+    if !feature.tags.len().is_multiple_of(2) {
+        return Err(MvtError::InvalidTags("odd number of tags".into()));
+    }
+    let properties = feature.tags
+        .chunks(2)
+        .map(|tag| {
+            let key = layer.keys
+                .get(tag[0] as usize)
+                .ok_or_else(|| MvtError::InvalidTags(format!("key index out of range: {}", tag[0])))?;
+            let value = values
+                .get(tag[1] as usize)
+                .ok_or_else(|| MvtError::InvalidTags(format!("value index out of range: {}", tag[1])))?;
+            Ok((key.clone(), value.clone()))
+        })
+        .collect::<Result<_, MvtError>>()?;
+
+    let parts = decode_geometry(&feature.geometry)?;
+    let to_location = |point: &Point| location_from_point(point, coordinates, extent);
+    let to_locations = |part: &Vec<Point>| part.iter().map(to_location).collect();
+    let geometry = match feature.type_() {
+        GeomType::POINT => DecodedGeometry::Point(parts.iter().flatten().map(to_location).collect()),
+        GeomType::LINESTRING => DecodedGeometry::LineString(parts.iter().map(to_locations).collect()),
+        GeomType::POLYGON => DecodedGeometry::Polygon(
+            group_rings(parts)?
+                .iter()
+                .map(|rings| rings.iter().map(to_locations).collect())
+                .collect(),
+        ),
+        GeomType::UNKNOWN => DecodedGeometry::Unknown,
+    };
+
+    Ok(DecodedFeature {
+        id: feature.id,
+        geometry,
+        properties,
+    })
+}
+
+/// Groups rings into polygons.
+///
+/// A clockwise ring; i.e., positive area, starts a new polygon as the
+/// exterior ring. A counter-clockwise ring is an interior ring of the last
+/// polygon. Rings with zero area are invalid.
+fn group_rings(rings: Vec<Vec<Point>>) -> Result<Vec<Vec<Vec<Point>>>, MvtError> {
+    let mut polygons: Vec<Vec<Vec<Point>>> = Vec::new();
+    for ring in rings {
+        let area = ring_area(&ring);
+        if area > 0 {
+            polygons.push(vec![ring]);
+        } else if area < 0 {
+            polygons
+                .last_mut()
+                .ok_or_else(|| MvtError::InvalidGeometry("interior ring before exterior ring".into()))?
+                .push(ring);
+        } else {
+            return Err(MvtError::InvalidGeometry("ring has no area".into()));
+        }
+    }
+    Ok(polygons)
+}
+
+/// Converts a point in a tile into a geographic location.
+#[inline]
+fn location_from_point(
+    &(u, v): &Point,
+    coordinates: &TileCoordinates,
+    extent: u32,
+) -> GeolocationCoordinates {
+    let x = coordinates.x() as f64 + u as f64 / extent as f64;
+    let y = coordinates.y() as f64 + v as f64 / extent as f64;
+    GeolocationCoordinates {
+        longitude: longitude_from_fractional_x_at_zoom(x, coordinates.zoom()),
+        latitude: latitude_from_fractional_y_at_zoom(y, coordinates.zoom()),
+    }
+}
+
+/// Serializes properties as a map.
+fn serialize_properties<S>(
+    properties: &[(String, PropertyValue)],
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_map(properties.iter().map(|(k, v)| (k, v)))
+}
+
+/// Converts a value in a layer into a [`PropertyValue`].
+fn property_value_from(value: &Value) -> Result<PropertyValue, MvtError> {
+    if let Some(s) = value.string_value.as_ref() {
+        Ok(PropertyValue::String(s.clone()))
+    } else if let Some(i) = value.int_value.or(value.sint_value) {
+        Ok(PropertyValue::I64(i))
+    } else if let Some(u) = value.uint_value {
+        Ok(PropertyValue::U64(u))
+    } else if let Some(f) = value.double_value.or(value.float_value.map(f64::from)) {
+        Ok(PropertyValue::F64(f))
+    } else if let Some(b) = value.bool_value {
+        Ok(PropertyValue::Bool(b))
+    } else {
+        Err(MvtError::UnsupportedValue(format!("{value:?}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use protobuf::Message as _;
+
+    use business_core::types::{BusinessRecordBuilder, BusinessType};
+
+    use crate::mvt::density::{self, DensityGridBuilder};
+    use crate::mvt::symbol::{BusinessRecordBuffer, PropertySchema};
+
+    // a unit in the extent at zoom level 10 is about 8.6e-5 degrees
+    const EPSILON: f64 = 1e-4;
+
+    const TOKYO_STATION: GeolocationCoordinates = GeolocationCoordinates {
+        longitude: 139.7670506677,
+        latitude: 35.6814709332,
+    };
+    const SHINJUKU_STATION: GeolocationCoordinates = GeolocationCoordinates {
+        longitude: 139.7005541230,
+        latitude: 35.6898188583,
+    };
+
+    // tile at zoom level 10 that contains all the locations above
+    fn tile_coordinates() -> TileCoordinates {
+        TileCoordinates::new(10, 909, 403).unwrap()
+    }
+
+    fn make_record(
+        record_id: &str,
+        dog_id: Option<&str>,
+        location: &GeolocationCoordinates,
+        timestamp: i64,
+    ) -> business_core::types::BusinessRecord {
+        BusinessRecordBuilder::default()
+            .record_id(record_id)
+            .dog_id(dog_id.map(str::to_string))
+            .business_type(BusinessType::Pee)
+            .location(location.clone())
+            .timestamp(timestamp)
+            .build()
+            .unwrap()
+    }
+
+    fn round_trip(tile: Tile) -> Tile {
+        Tile::parse_from_bytes(&tile.write_to_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_decode_business_record_buffer_with_custom_schema_round_trip() {
+        let schema = PropertySchema::empty()
+            .with_unique_property("recordId", |record| Some(record.record_id.clone().into()))
+            .with_property("dayOfWeek", |record| {
+                Some(PropertyValue::U64(((record.timestamp / 86400 + 4) % 7) as u64))
+            })
+            .with_property("hasDog", |record| Some(record.dog_id.is_some().into()))
+            .with_property("latitude", |record| Some(record.location.latitude.into()));
+        let mut buffer = BusinessRecordBuffer::new(tile_coordinates()).with_schema(schema);
+        buffer
+            .append_business_record(make_record("record_1", Some("dog_1"), &TOKYO_STATION, 1_755_317_141))
+            .unwrap();
+        let tile = round_trip(buffer.into());
+
+        let layers = decode_tile(&tile, &tile_coordinates()).unwrap();
+        assert_eq!(layers[0].keys, vec!["recordId", "dayOfWeek", "hasDog", "latitude"]);
+        assert_eq!(layers[0].features[0].properties, vec![
+            ("recordId".to_string(), "record_1".to_string().into()),
+            // 2025-08-16 was Saturday
+            ("dayOfWeek".to_string(), PropertyValue::U64(6)),
+            ("hasDog".to_string(), true.into()),
+            ("latitude".to_string(), TOKYO_STATION.latitude.into()),
+        ]);
+    }
+
+    #[test]
+    fn test_decode_business_record_buffer_round_trip() {
+        let mut buffer = BusinessRecordBuffer::new(tile_coordinates());
+        buffer
+            .append_business_record(make_record("record_1", Some("dog_1"), &TOKYO_STATION, 1_755_317_141))
+            .unwrap();
+        buffer
+            .append_business_record(make_record("record_2", None, &SHINJUKU_STATION, 1_755_317_142))
+            .unwrap();
+        let tile = round_trip(buffer.into());
+
+        let layers = decode_tile(&tile, &tile_coordinates()).unwrap();
+        assert_eq!(layers.len(), 1);
+        let layer = &layers[0];
+        assert_eq!(layer.name, "business_records");
+        assert_eq!(layer.version, 2);
+        assert_eq!(layer.extent, TILE_EXTENT);
+        assert_eq!(layer.features.len(), 2);
+
+        let feature = &layer.features[0];
+        assert!(feature.id.is_some());
+        match &feature.geometry {
+            DecodedGeometry::Point(points) => {
+                assert_eq!(points.len(), 1);
+                assert!((points[0].longitude - TOKYO_STATION.longitude).abs() < EPSILON);
+                assert!((points[0].latitude - TOKYO_STATION.latitude).abs() < EPSILON);
+            }
+            g => panic!("unexpected geometry: {g:?}"),
+        }
+        assert_eq!(
+            feature.get_property("recordId"),
+            Some(&PropertyValue::String("record_1".to_string())),
+        );
+        assert_eq!(
+            feature.get_property("businessType"),
+            Some(&PropertyValue::String("pee".to_string())),
+        );
+        assert_eq!(
+            feature.get_property("timestamp"),
+            Some(&PropertyValue::I64(1_755_317_141)),
+        );
+        assert_eq!(
+            feature.get_property("dogId"),
+            Some(&PropertyValue::String("dog_1".to_string())),
+        );
+
+        let feature = &layer.features[1];
+        match &feature.geometry {
+            DecodedGeometry::Point(points) => {
+                assert!((points[0].longitude - SHINJUKU_STATION.longitude).abs() < EPSILON);
+                assert!((points[0].latitude - SHINJUKU_STATION.latitude).abs() < EPSILON);
+            }
+            g => panic!("unexpected geometry: {g:?}"),
+        }
+        assert_eq!(
+            feature.get_property("recordId"),
+            Some(&PropertyValue::String("record_2".to_string())),
+        );
+        assert!(feature.get_property("dogId").is_none());
+    }
+
+    #[test]
+    fn test_decode_density_grid_round_trip() {
+        let mut builder = DensityGridBuilder::new(tile_coordinates(), 1);
+        builder
+            .add_business_record(&make_record("record_1", None, &TOKYO_STATION, 0))
+            .unwrap();
+        let tile = round_trip(builder.into());
+
+        let layers = decode_tile(&tile, &tile_coordinates()).unwrap();
+        assert_eq!(layers[0].name, density::LAYER_NAME);
+        let feature = &layers[0].features[0];
+        assert_eq!(feature.get_property("count"), Some(&PropertyValue::I64(1)));
+        match &feature.geometry {
+            DecodedGeometry::Polygon(polygons) => {
+                // the single cell covers the whole tile
+                assert_eq!(polygons.len(), 1);
+                let rings = &polygons[0];
+                assert_eq!(rings.len(), 1);
+                assert_eq!(rings[0].len(), 4);
+                assert!(tile_coordinates().contains_location_with_margin(&rings[0][0], 1e-9));
+                assert!((rings[0][0].longitude - rings[0][3].longitude).abs() < 1e-9);
+                assert!((rings[0][0].latitude - rings[0][1].latitude).abs() < 1e-9);
+                assert!(rings[0][0].longitude < rings[0][1].longitude);
+                assert!(rings[0][0].latitude > rings[0][3].latitude);
+            }
+            g => panic!("unexpected geometry: {g:?}"),
+        }
+    }
+
+    #[test]
+    fn test_decode_layer_invalid_tags() {
+        let mut buffer = BusinessRecordBuffer::new(tile_coordinates());
+        buffer
+            .append_business_record(make_record("record_1", None, &TOKYO_STATION, 0))
+            .unwrap();
+        let mut layer: Layer = buffer.into();
+        layer.features[0].tags.push(0);
+        assert!(matches!(decode_layer(&layer, &tile_coordinates()), Err(MvtError::InvalidTags(_))));
+        layer.features[0].tags.push(1000);
+        assert!(matches!(decode_layer(&layer, &tile_coordinates()), Err(MvtError::InvalidTags(_))));
+    }
+
+    #[test]
+    fn test_group_rings() {
+        // example from the specification
+        let rings = vec![
+            vec![(0, 0), (10, 0), (10, 10), (0, 10)],
+            vec![(11, 11), (20, 11), (20, 20), (11, 20)],
+            vec![(13, 13), (13, 17), (17, 17), (17, 13)],
+        ];
+        let polygons = group_rings(rings.clone()).unwrap();
+        assert_eq!(polygons, vec![
+            vec![rings[0].clone()],
+            vec![rings[1].clone(), rings[2].clone()],
+        ]);
+
+        // interior ring before exterior ring
+        assert!(matches!(
+            group_rings(vec![rings[2].clone()]),
+            Err(MvtError::InvalidGeometry(_)),
+        ));
+    }
+
+    #[test]
+    fn test_decoded_layer_serialize() {
+        let mut buffer = BusinessRecordBuffer::new(tile_coordinates());
+        buffer
+            .append_business_record(make_record("record_1", None, &TOKYO_STATION, 0))
+            .unwrap();
+        let layer = decode_layer(&buffer.into(), &tile_coordinates()).unwrap();
+        let json = serde_json::to_value(&layer).unwrap();
+        assert_eq!(json["name"], "business_records");
+        assert_eq!(json["keys"][0], "recordId");
+        let feature = &json["features"][0];
+        assert_eq!(feature["geometry"]["type"], "Point");
+        assert!(feature["geometry"]["coordinates"][0]["longitude"].is_f64());
+        assert_eq!(feature["properties"]["recordId"], "record_1");
+        assert_eq!(feature["properties"]["timestamp"], 0);
+    }
+}
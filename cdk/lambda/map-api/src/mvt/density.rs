@@ -0,0 +1,267 @@
+//! MVT for the density of business records.
+//!
+//! Business records in a tile are binned into an N×N grid over
+//! [`TILE_EXTENT`]. Each non-empty grid cell becomes a square polygon which
+//! has the number of business records in the cell.
+
+use std::collections::HashMap;
+
+use business_core::types::{BusinessRecord, BusinessType};
+
+use crate::mvt::{MvtError, TileCoordinates};
+use crate::mvt::geometry::encode_polygon;
+use crate::mvt::grid::{TileGrid, build_grid_layer};
+use crate::protos::vector_tile::{Tile, tile::{GeomType, Layer}};
+
+/// Layer name.
+pub const LAYER_NAME: &str = "business_density";
+
+/// Default number of grid cells per tile edge.
+pub const DEFAULT_GRID_SIZE: u32 = 16;
+
+/// Builder of a density grid of business records in a vector tile.
+pub struct DensityGridBuilder {
+    /// Grid over the tile.
+    grid: TileGrid,
+
+    /// Non-empty grid cells.
+    ///
+    /// Keys are grid cell coordinates.
+    cells: HashMap<(u32, u32), DensityCell>,
+}
+
+impl DensityGridBuilder {
+    /// Creates a new [`DensityGridBuilder`] for given tile coordinates.
+    ///
+    /// `grid_size` is the number of grid cells per tile edge. It is clamped to
+    /// the range `[1, TILE_EXTENT]`, and then rounded down to a power of two
+    /// so that the grid cells evenly divide [`TILE_EXTENT`].
+    pub fn new(coordinates: TileCoordinates, grid_size: u32) -> Self {
+        Self {
+            grid: TileGrid::new(coordinates, grid_size),
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Adds a given business record to the grid cell it belongs to.
+    ///
+    /// May return the following error:
+    /// - [`MvtError::OutsideOfTile`]: if the tile does not contain the
+    ///   record's location
+    pub fn add_business_record(&mut self, record: &BusinessRecord) -> Result<(), MvtError> {
+        if !self.grid.contains_location(&record.location) {
+            return Err(MvtError::OutsideOfTile);
+        }
+        self.cells
+            .entry(self.grid.cell_from_location(&record.location))
+            .or_default()
+            .add(&record.business_type);
+        Ok(())
+    }
+
+    /// Returns the number of non-empty grid cells.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns if all the grid cells are empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Returns the polygon geometry of a given grid cell.
+    ///
+    /// The exterior ring is clockwise in the tile coordinates as the
+    /// specification requires.
+    fn cell_geometry(&self, (i, j): (u32, u32)) -> Vec<u32> {
+        let size = self.grid.cell_size() as i32;
+        let (u, v) = (i as i32 * size, j as i32 * size);
+        encode_polygon(&[[(u, v), (u + size, v), (u + size, v + size), (u, v + size)]])
+            .unwrap() // should not fail because the ring is a square
+    }
+}
+
+/// Grid cell in a density grid.
+#[derive(Clone, Debug, Default)]
+struct DensityCell {
+    /// Number of business records.
+    count: u64,
+    /// Number of pee records.
+    pee_count: u64,
+    /// Number of poo records.
+    poo_count: u64,
+}
+
+impl DensityCell {
+    /// Adds a business record of a given type.
+    fn add(&mut self, business_type: &BusinessType) {
+        self.count += 1;
+        match business_type {
+            BusinessType::Pee => self.pee_count += 1,
+            BusinessType::Poo => self.poo_count += 1,
+        }
+    }
+
+    /// Returns the property values in the order of the property keys.
+    fn property_values(&self) -> [i64; 3] {
+        [
+            self.count as i64,
+            self.pee_count as i64,
+            self.poo_count as i64,
+        ]
+    }
+}
+
+impl From<DensityGridBuilder> for Tile {
+    fn from(builder: DensityGridBuilder) -> Self {
+        let mut tile = Tile::new();
+        tile.layers.push(builder.into());
+        tile
+    }
+}
+
+impl From<DensityGridBuilder> for Layer {
+    fn from(builder: DensityGridBuilder) -> Self {
+        build_grid_layer(
+            LAYER_NAME,
+            &builder.grid,
+            builder.cells.iter().map(|(&cell, density)| (cell, density)),
+            GeomType::POLYGON,
+            &PROPERTY_KEYS,
+            |cell, density| (builder.cell_geometry(cell), density.property_values()),
+        )
+    }
+}
+
+/// Key index and name for the `count` property.
+const PROPERTY_KEY_COUNT: (u32, &str) = (0, "count");
+/// Key index and name for the `pee_count` property.
+const PROPERTY_KEY_PEE_COUNT: (u32, &str) = (1, "pee_count");
+/// Key index and name for the `poo_count` property.
+const PROPERTY_KEY_POO_COUNT: (u32, &str) = (2, "poo_count");
+
+/// Property keys in the order of [`DensityCell::property_values`].
+const PROPERTY_KEYS: [(u32, &str); 3] = [
+    PROPERTY_KEY_COUNT,
+    PROPERTY_KEY_PEE_COUNT,
+    PROPERTY_KEY_POO_COUNT,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mvt::grid::fixtures::*;
+
+    #[test]
+    fn test_density_grid_builder_add_business_record() {
+        let mut builder = DensityGridBuilder::new(
+            TileCoordinates::new(10, 909, 403).unwrap(),
+            DEFAULT_GRID_SIZE,
+        );
+        assert!(builder.is_empty());
+        builder
+            .add_business_record(&make_record("r1", BusinessType::Pee, &TOKYO, 487_588))
+            .unwrap();
+        builder
+            .add_business_record(&make_record("r2", BusinessType::Poo, &TOKYO, 487_588))
+            .unwrap();
+        assert_eq!(builder.len(), 1);
+        builder
+            .add_business_record(&make_record("r3", BusinessType::Poo, &SHINJUKU_STATION, 487_588))
+            .unwrap();
+        assert_eq!(builder.len(), 2);
+        assert!(
+            matches!(
+                builder.add_business_record(&make_record("r4", BusinessType::Pee, &PITTSBURGH, 487_588)),
+                Err(MvtError::OutsideOfTile),
+            ),
+        );
+    }
+
+    #[test]
+    fn test_density_grid_builder_non_divisor_grid_size() {
+        // rounded down to 2
+        let builder = DensityGridBuilder::new(TileCoordinates::new(0, 0, 0).unwrap(), 3);
+        // the bottom-right cell reaches the edges of the tile
+        assert_eq!(builder.cell_geometry((1, 1)), vec![
+            9, 4096, 4096, // MoveTo(2048, 2048)
+            26, 4096, 0, 0, 4096, 4095, 0, // LineTo(+2048, 0), (0, +2048), (-2048, 0)
+            15, // ClosePath
+        ]);
+    }
+
+    #[test]
+    fn test_density_grid_builder_into_tile() {
+        let mut builder = DensityGridBuilder::new(
+            TileCoordinates::new(0, 0, 0).unwrap(),
+            DEFAULT_GRID_SIZE,
+        );
+        builder
+            .add_business_record(&make_record("r1", BusinessType::Pee, &TOKYO, 487_588))
+            .unwrap();
+        builder
+            .add_business_record(&make_record("r2", BusinessType::Pee, &SHINJUKU_STATION, 487_588))
+            .unwrap();
+        builder
+            .add_business_record(&make_record("r3", BusinessType::Poo, &PITTSBURGH, 487_588))
+            .unwrap();
+
+        let tile: Tile = builder.into();
+        assert_eq!(tile.layers.len(), 1);
+
+        let layer = &tile.layers[0];
+        assert_eq!(layer.version.unwrap(), 2);
+        assert_eq!(layer.name.as_ref().unwrap(), "business_density");
+        assert_eq!(layer.extent.unwrap(), 4096);
+        assert_eq!(layer.keys, vec!["count", "pee_count", "poo_count"]);
+        assert_eq!(layer.features.len(), 2);
+
+        // values are sorted by frequency in descending order, and then by
+        // the natural order of values
+        let values = &layer.values;
+        assert_eq!(values.len(), 3);
+        // - twice
+        assert!(expect_int(0)(&values[0]));
+        assert!(expect_int(1)(&values[1]));
+        assert!(expect_int(2)(&values[2]));
+        let (i_0, i_1, i_2) = (0, 1, 2);
+
+        // features are ordered row by row; Pittsburgh comes first
+        let features = &layer.features;
+        assert_eq!(features[0].id.unwrap(), 0);
+        assert!(matches!(features[0].type_.unwrap().unwrap(), GeomType::POLYGON));
+        assert_eq!(features[0].tags, vec![0, i_1, 1, i_0, 2, i_1]);
+        assert_eq!(features[0].geometry, vec![
+            9, 2048, 3072, // MoveTo(1024, 1536)
+            26, 512, 0, 0, 512, 511, 0, // LineTo(+256, 0), (0, +256), (-256, 0)
+            15, // ClosePath
+        ]);
+
+        assert_eq!(features[1].id.unwrap(), 0x20);
+        assert!(matches!(features[1].type_.unwrap().unwrap(), GeomType::POLYGON));
+        assert_eq!(features[1].tags, vec![0, i_2, 1, i_2, 2, i_0]);
+        assert_eq!(features[1].geometry, vec![
+            9, 7168, 3072, // MoveTo(3584, 1536)
+            26, 512, 0, 0, 512, 511, 0, // LineTo(+256, 0), (0, +256), (-256, 0)
+            15, // ClosePath
+        ]);
+    }
+
+    #[test]
+    fn test_density_grid_builder_into_tile_empty() {
+        let builder = DensityGridBuilder::new(
+            TileCoordinates::new(16, 58138, 25860).unwrap(),
+            DEFAULT_GRID_SIZE,
+        );
+
+        let tile: Tile = builder.into();
+        let layer = &tile.layers[0];
+        assert_eq!(layer.name.as_ref().unwrap(), "business_density");
+        assert_eq!(layer.keys.len(), 0);
+        assert_eq!(layer.values.len(), 0);
+        assert_eq!(layer.features.len(), 0);
+    }
+}
@@ -0,0 +1,410 @@
+//! Geometry encoding and decoding of MVT features.
+//!
+//! Geometry of a feature is a sequence of commands and parameters:
+//! - `MoveTo` moves the cursor to a point and starts a new part
+//! - `LineTo` draws a line from the cursor to a point
+//! - `ClosePath` closes the current ring
+//!
+//! Parameters are zigzag-encoded deltas from the cursor, so points may be
+//! outside of the tile; e.g., in the buffer.
+//!
+//! See https://github.com/mapbox/vector-tile-spec/tree/master/2.1#43-geometry-encoding
+
+use crate::mvt::{zigzag_decode, zigzag_signed, MvtError};
+
+/// Point in a tile.
+///
+/// `(u, v)` in extent units. The origin is the top-left corner of the tile.
+pub type Point = (i32, i32);
+
+/// `MoveTo` command ID.
+const COMMAND_MOVE_TO: u32 = 1;
+/// `LineTo` command ID.
+const COMMAND_LINE_TO: u32 = 2;
+/// `ClosePath` command ID.
+const COMMAND_CLOSE_PATH: u32 = 7;
+
+/// Maximum count of a command.
+const MAX_COMMAND_COUNT: usize = (1 << 29) - 1;
+
+/// Encoder of the geometry of a feature.
+///
+/// Keeps track of the cursor so that parameters are encoded as deltas.
+/// This is a low-level interface; prefer functions like [`encode_polygon`]
+/// which validate geometries.
+#[derive(Clone, Debug, Default)]
+pub struct GeometryEncoder {
+    /// Encoded geometry.
+    geometry: Vec<u32>,
+
+    /// Current cursor.
+    cursor: Point,
+}
+
+impl GeometryEncoder {
+    /// Creates a new [`GeometryEncoder`] with the cursor at the origin.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a `MoveTo` command with given points.
+    ///
+    /// Does nothing if `points` is empty.
+    ///
+    /// Panics if there are too many points for a single command.
+    pub fn move_to(&mut self, points: &[Point]) -> &mut Self {
+        self.push_command_with_points(COMMAND_MOVE_TO, points);
+        self
+    }
+
+    /// Appends a `LineTo` command with given points.
+    ///
+    /// Does nothing if `points` is empty.
+    ///
+    /// Panics if there are too many points for a single command.
+    pub fn line_to(&mut self, points: &[Point]) -> &mut Self {
+        self.push_command_with_points(COMMAND_LINE_TO, points);
+        self
+    }
+
+    /// Appends a `ClosePath` command.
+    pub fn close_path(&mut self) -> &mut Self {
+        self.geometry.push(command_integer(COMMAND_CLOSE_PATH, 1));
+        self
+    }
+
+    /// Finishes encoding and returns the geometry.
+    pub fn finish(self) -> Vec<u32> {
+        self.geometry
+    }
+
+    fn push_command_with_points(&mut self, id: u32, points: &[Point]) {
+        if points.is_empty() {
+            return;
+        }
+        assert!(points.len() <= MAX_COMMAND_COUNT, "too many points in a command");
+        self.geometry.reserve(1 + 2 * points.len());
+        self.geometry.push(command_integer(id, points.len()));
+        for &(u, v) in points {
+            self.geometry.push(zigzag_signed(u.wrapping_sub(self.cursor.0)));
+            self.geometry.push(zigzag_signed(v.wrapping_sub(self.cursor.1)));
+            self.cursor = (u, v);
+        }
+    }
+}
+
+/// Encodes a `POINT` geometry.
+pub fn encode_point(point: Point) -> Vec<u32> {
+    let mut encoder = GeometryEncoder::new();
+    encoder.move_to(&[point]);
+    encoder.finish()
+}
+
+/// Encodes a multi-point `POINT` geometry.
+///
+/// Fails with [`MvtError::InvalidGeometry`] if `points` is empty.
+pub fn encode_multi_point(points: &[Point]) -> Result<Vec<u32>, MvtError> {
+    if points.is_empty() {
+        return Err(MvtError::InvalidGeometry("no points".into()));
+    }
+    let mut encoder = GeometryEncoder::new();
+    encoder.move_to(points);
+    Ok(encoder.finish())
+}
+
+/// Encodes a `LINESTRING` geometry.
+///
+/// Consecutive duplicate points are removed, because the specification
+/// prohibits zero-length lines.
+///
+/// Fails with [`MvtError::InvalidGeometry`] if the line string has less than
+/// 2 distinct points.
+pub fn encode_line_string(points: &[Point]) -> Result<Vec<u32>, MvtError> {
+    encode_multi_line_string(&[points])
+}
+
+/// Encodes a multi-line `LINESTRING` geometry.
+///
+/// Consecutive duplicate points are removed, because the specification
+/// prohibits zero-length lines.
+///
+/// Fails with [`MvtError::InvalidGeometry`] if there are no line strings, or
+/// any line string has less than 2 distinct points.
+pub fn encode_multi_line_string<L>(lines: &[L]) -> Result<Vec<u32>, MvtError>
+where
+    L: AsRef<[Point]>,
+{
+    if lines.is_empty() {
+        return Err(MvtError::InvalidGeometry("no line strings".into()));
+    }
+    let mut encoder = GeometryEncoder::new();
+    for line in lines {
+        let line = dedup_points(line.as_ref());
+        if line.len() < 2 {
+            return Err(MvtError::InvalidGeometry(
+                "line string must have at least 2 distinct points".into(),
+            ));
+        }
+        encoder.move_to(&line[..1]).line_to(&line[1..]);
+    }
+    Ok(encoder.finish())
+}
+
+/// Encodes a `POLYGON` geometry.
+///
+/// `rings` is a sequence of exterior rings each followed by its interior
+/// rings. The specification requires exterior rings to be clockwise, and
+/// interior rings to be counter-clockwise in the tile coordinates; i.e.,
+/// v-axis pointing down. Multiple exterior rings make a multi-polygon.
+/// This function does not reorder rings or points; use [`ring_area`] to
+/// determine the winding order.
+///
+/// A ring may or may not repeat the first point at the end. Consecutive
+/// duplicate points are removed.
+///
+/// Fails with [`MvtError::InvalidGeometry`] if there are no rings, or any
+/// ring has less than 3 distinct points.
+pub fn encode_polygon<R>(rings: &[R]) -> Result<Vec<u32>, MvtError>
+where
+    R: AsRef<[Point]>,
+{
+    if rings.is_empty() {
+        return Err(MvtError::InvalidGeometry("no rings".into()));
+    }
+    let mut encoder = GeometryEncoder::new();
+    for ring in rings {
+        let mut ring = dedup_points(ring.as_ref());
+        if ring.len() > 1 && ring.first() == ring.last() {
+            // ClosePath returns to the first point
+            ring.pop();
+        }
+        if ring.len() < 3 {
+            return Err(MvtError::InvalidGeometry(
+                "ring must have at least 3 distinct points".into(),
+            ));
+        }
+        encoder.move_to(&ring[..1]).line_to(&ring[1..]).close_path();
+    }
+    Ok(encoder.finish())
+}
+
+/// Calculates the signed area of a ring in the tile coordinates.
+///
+/// The area is positive if the ring is clockwise in the tile coordinates;
+/// i.e., exterior ring, and negative if counter-clockwise; i.e., interior
+/// ring.
+pub fn ring_area(ring: &[Point]) -> i64 {
+    let n = ring.len();
+    let twice_area: i64 = (0..n)
+        .map(|i| {
+            let (u1, v1) = ring[i];
+            let (u2, v2) = ring[(i + 1) % n];
+            u1 as i64 * v2 as i64 - u2 as i64 * v1 as i64
+        })
+        .sum();
+    twice_area / 2
+}
+
+/// Decodes a geometry into parts.
+///
+/// Every point of a `MoveTo` command starts a new part, and points of the
+/// following `LineTo` command are appended to the part. So each part is:
+/// - a single point of a `POINT` geometry
+/// - a line string of a `LINESTRING` geometry
+/// - a ring of a `POLYGON` geometry without the closing point
+///
+/// Fails with [`MvtError::InvalidGeometry`] if the geometry is malformed.
+pub fn decode_geometry(geometry: &[u32]) -> Result<Vec<Vec<Point>>, MvtError> {
+    let mut parts: Vec<Vec<Point>> = Vec::new();
+    let mut cursor: Point = (0, 0);
+    let mut integers = geometry.iter().copied();
+    while let Some(command) = integers.next() {
+        let id = command & 0x7;
+        let count = (command >> 3) as usize;
+        match id {
+            COMMAND_MOVE_TO | COMMAND_LINE_TO => {
+                for _ in 0..count {
+                    let (du, dv) = integers
+                        .next()
+                        .zip(integers.next())
+                        .ok_or_else(|| MvtError::InvalidGeometry("missing parameters".into()))?;
+                    cursor = (
+                        cursor.0.wrapping_add(zigzag_decode(du)),
+                        cursor.1.wrapping_add(zigzag_decode(dv)),
+                    );
+                    if id == COMMAND_MOVE_TO {
+                        parts.push(vec![cursor]);
+                    } else {
+                        parts
+                            .last_mut()
+                            .ok_or_else(|| MvtError::InvalidGeometry("LineTo before MoveTo".into()))?
+                            .push(cursor);
+                    }
+                }
+            }
+            COMMAND_CLOSE_PATH => {
+                if count != 1 {
+                    return Err(MvtError::InvalidGeometry(format!("ClosePath count must be 1: {count}")));
+                }
+                if parts.is_empty() {
+                    return Err(MvtError::InvalidGeometry("ClosePath before MoveTo".into()));
+                }
+            }
+            _ => return Err(MvtError::InvalidGeometry(format!("unknown command: {id}"))),
+        }
+    }
+    Ok(parts)
+}
+
+/// Makes a command integer.
+#[inline]
+const fn command_integer(id: u32, count: usize) -> u32 {
+    (id & 0x7) | ((count as u32) << 3)
+}
+
+/// Removes consecutive duplicate points.
+fn dedup_points(points: &[Point]) -> Vec<Point> {
+    let mut points = points.to_vec();
+    points.dedup();
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_point() {
+        // example from the specification
+        assert_eq!(encode_point((25, 17)), vec![9, 50, 34]);
+        // negative coordinates in the buffer
+        assert_eq!(encode_point((-1, -2)), vec![9, 1, 3]);
+    }
+
+    #[test]
+    fn test_encode_multi_point() {
+        // example from the specification
+        assert_eq!(
+            encode_multi_point(&[(5, 7), (3, 2)]).unwrap(),
+            vec![17, 10, 14, 3, 9],
+        );
+        assert!(matches!(
+            encode_multi_point(&[]),
+            Err(MvtError::InvalidGeometry(_)),
+        ));
+    }
+
+    #[test]
+    fn test_encode_line_string() {
+        // example from the specification
+        assert_eq!(
+            encode_line_string(&[(2, 2), (2, 10), (10, 10)]).unwrap(),
+            vec![9, 4, 4, 18, 0, 16, 16, 0],
+        );
+        // duplicate points are removed
+        assert_eq!(
+            encode_line_string(&[(2, 2), (2, 2), (2, 10), (10, 10)]).unwrap(),
+            vec![9, 4, 4, 18, 0, 16, 16, 0],
+        );
+        assert!(matches!(
+            encode_line_string(&[(2, 2), (2, 2)]),
+            Err(MvtError::InvalidGeometry(_)),
+        ));
+    }
+
+    #[test]
+    fn test_encode_multi_line_string() {
+        // example from the specification
+        assert_eq!(
+            encode_multi_line_string(&[
+                vec![(2, 2), (2, 10), (10, 10)],
+                vec![(1, 1), (3, 5)],
+            ]).unwrap(),
+            vec![9, 4, 4, 18, 0, 16, 16, 0, 9, 17, 17, 10, 4, 8],
+        );
+        assert!(matches!(
+            encode_multi_line_string::<Vec<Point>>(&[]),
+            Err(MvtError::InvalidGeometry(_)),
+        ));
+    }
+
+    #[test]
+    fn test_encode_polygon() {
+        // example from the specification
+        let expected = vec![9, 6, 12, 18, 10, 12, 24, 44, 15];
+        assert_eq!(encode_polygon(&[[(3, 6), (8, 12), (20, 34)]]).unwrap(), expected);
+        // the first point may be repeated at the end
+        assert_eq!(
+            encode_polygon(&[[(3, 6), (8, 12), (20, 34), (3, 6)]]).unwrap(),
+            expected,
+        );
+        assert!(matches!(
+            encode_polygon(&[[(3, 6), (8, 12), (3, 6)]]),
+            Err(MvtError::InvalidGeometry(_)),
+        ));
+    }
+
+    #[test]
+    fn test_encode_multi_polygon() {
+        // example from the specification
+        let rings = vec![
+            vec![(0, 0), (10, 0), (10, 10), (0, 10)],
+            vec![(11, 11), (20, 11), (20, 20), (11, 20)],
+            vec![(13, 13), (13, 17), (17, 17), (17, 13)],
+        ];
+        assert_eq!(
+            encode_polygon(&rings).unwrap(),
+            vec![
+                9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15,
+                9, 22, 2, 26, 18, 0, 0, 18, 17, 0, 15,
+                9, 4, 13, 26, 0, 8, 8, 0, 0, 7, 15,
+            ],
+        );
+        assert!(ring_area(&rings[0]) > 0);
+        assert!(ring_area(&rings[1]) > 0);
+        assert!(ring_area(&rings[2]) < 0);
+    }
+
+    #[test]
+    fn test_decode_geometry() {
+        let points = vec![(5, 7), (3, 2)];
+        let geometry = encode_multi_point(&points).unwrap();
+        assert_eq!(decode_geometry(&geometry).unwrap(), vec![vec![(5, 7)], vec![(3, 2)]]);
+
+        let lines = vec![vec![(2, 2), (2, 10), (10, 10)], vec![(1, 1), (3, 5)]];
+        let geometry = encode_multi_line_string(&lines).unwrap();
+        assert_eq!(decode_geometry(&geometry).unwrap(), lines);
+
+        let rings = vec![
+            vec![(0, 0), (10, 0), (10, 10), (0, 10)],
+            vec![(-11, 11), (20, 11), (20, 20), (-11, 20)],
+        ];
+        let geometry = encode_polygon(&rings).unwrap();
+        assert_eq!(decode_geometry(&geometry).unwrap(), rings);
+    }
+
+    #[test]
+    fn test_decode_geometry_malformed() {
+        // missing parameters
+        assert!(matches!(
+            decode_geometry(&[17, 10, 14, 3]),
+            Err(MvtError::InvalidGeometry(_)),
+        ));
+        // LineTo before MoveTo
+        assert!(matches!(
+            decode_geometry(&[10, 2, 2]),
+            Err(MvtError::InvalidGeometry(_)),
+        ));
+        // unknown command
+        assert!(matches!(
+            decode_geometry(&[12]),
+            Err(MvtError::InvalidGeometry(_)),
+        ));
+    }
+
+    #[test]
+    fn test_ring_area() {
+        assert_eq!(ring_area(&[(0, 0), (10, 0), (10, 10), (0, 10)]), 100);
+        assert_eq!(ring_area(&[(0, 0), (0, 10), (10, 10), (10, 0)]), -100);
+    }
+}
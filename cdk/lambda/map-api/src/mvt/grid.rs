@@ -0,0 +1,309 @@
+//! Square grid over a vector tile.
+//!
+//! Layers that aggregate business records per grid cell, i.e.,
+//! [`cluster`](crate::mvt::cluster) and [`density`](crate::mvt::density),
+//! share the tile-local projection and grid cells here.
+
+use business_core::types::GeolocationCoordinates;
+
+use crate::mvt::{
+    make_feature_id,
+    PropertyValueFrequencies,
+    TileCoordinates,
+    TILE_EXTENT,
+    VECTOR_TILE_VERSION,
+};
+use crate::protos::{
+    PropertyValue,
+    vector_tile::tile::{Feature, GeomType, Layer},
+};
+use crate::web_mercator::{
+    latitude_from_y_at_zoom,
+    longitude_from_x_at_zoom,
+    x_from_longitude_at_zoom,
+    y_from_latitude_at_zoom,
+};
+
+/// Square grid over a vector tile.
+#[derive(Clone, Debug)]
+pub struct TileGrid {
+    /// Tile coordinates.
+    coordinates: TileCoordinates,
+
+    /// Range of longitudes that the tile covers.
+    lon_range: std::ops::Range<f64>,
+
+    /// Range of latitudes that the tile covers.
+    lat_range: std::ops::Range<f64>,
+
+    /// Number of grid cells per tile edge.
+    grid_size: u32,
+}
+
+impl TileGrid {
+    /// Creates a new [`TileGrid`] over given tile coordinates.
+    ///
+    /// `grid_size` is the number of grid cells per tile edge. It is clamped to
+    /// the range `[1, TILE_EXTENT]`, and then rounded down to a power of two
+    /// so that the grid cells evenly divide [`TILE_EXTENT`].
+    pub fn new(coordinates: TileCoordinates, grid_size: u32) -> Self {
+        let min_longitude = longitude_from_x_at_zoom(coordinates.x(), coordinates.zoom());
+        let max_longitude = longitude_from_x_at_zoom(coordinates.x() + 1, coordinates.zoom());
+        let min_latitude = latitude_from_y_at_zoom(coordinates.y() + 1, coordinates.zoom());
+        let max_latitude = latitude_from_y_at_zoom(coordinates.y(), coordinates.zoom());
+        Self {
+            coordinates,
+            lon_range: min_longitude..max_longitude,
+            lat_range: min_latitude..max_latitude,
+            // a power of two divides TILE_EXTENT
+            grid_size: 1 << grid_size.clamp(1, TILE_EXTENT).ilog2(),
+        }
+    }
+
+    /// Returns the tile coordinates.
+    #[inline]
+    pub fn coordinates(&self) -> &TileCoordinates {
+        &self.coordinates
+    }
+
+    /// Returns the size of a grid cell in the tile coordinates.
+    #[inline]
+    pub fn cell_size(&self) -> u32 {
+        TILE_EXTENT / self.grid_size
+    }
+
+    /// Returns if the tile contains a given location.
+    #[inline]
+    pub fn contains_location(&self, location: &GeolocationCoordinates) -> bool {
+        self.lon_range.contains(&location.longitude) &&
+            self.lat_range.contains(&location.latitude)
+    }
+
+    /// Projects a given location to the tile coordinates `(u, v)`.
+    ///
+    /// Undefined if `location` is outside of the tile.
+    #[inline]
+    pub fn uv_from_location(&self, location: &GeolocationCoordinates) -> (u32, u32) {
+        (
+            self.u_from_longitude(location.longitude),
+            self.v_from_latitude(location.latitude),
+        )
+    }
+
+    /// Returns the grid cell that contains given tile coordinates `(u, v)`.
+    #[inline]
+    pub fn cell_from_uv(&self, (u, v): (u32, u32)) -> (u32, u32) {
+        let cell_size = self.cell_size();
+        (
+            (u / cell_size).min(self.grid_size - 1),
+            (v / cell_size).min(self.grid_size - 1),
+        )
+    }
+
+    /// Returns the grid cell that contains a given location.
+    ///
+    /// Undefined if `location` is outside of the tile.
+    #[inline]
+    pub fn cell_from_location(&self, location: &GeolocationCoordinates) -> (u32, u32) {
+        self.cell_from_uv(self.uv_from_location(location))
+    }
+
+    /// Calculates the u coordinate from longitude.
+    ///
+    /// Undefined if `longitude` is outside of the tile.
+    #[inline]
+    fn u_from_longitude(&self, longitude: f64) -> u32 {
+        let x = x_from_longitude_at_zoom(longitude, self.coordinates.zoom());
+        let u = x - self.coordinates.x() as f64;
+        (((TILE_EXTENT as f64) * u).floor() as u32).min(TILE_EXTENT - 1)
+    }
+
+    /// Calculates the v coordinate from latitude.
+    ///
+    /// Undefined if `latitude` is outside of the tile.
+    #[inline]
+    fn v_from_latitude(&self, latitude: f64) -> u32 {
+        let y = y_from_latitude_at_zoom(latitude, self.coordinates.zoom());
+        let v = y - self.coordinates.y() as f64;
+        (((TILE_EXTENT as f64) * v).floor() as u32).min(TILE_EXTENT - 1)
+    }
+}
+
+/// Builds a layer which has a feature for each given grid cell.
+///
+/// Features are ordered row by row. `make_feature` returns the geometry and
+/// property values of the feature for a grid cell. The property values must
+/// be in the order of `property_keys`, and the key indices in
+/// `property_keys` must be sequential from 0.
+pub(crate) fn build_grid_layer<C, const N: usize>(
+    name: &str,
+    grid: &TileGrid,
+    cells: impl IntoIterator<Item = ((u32, u32), C)>,
+    geom_type: GeomType,
+    property_keys: &[(u32, &str); N],
+    make_feature: impl Fn((u32, u32), &C) -> (Vec<u32>, [i64; N]),
+) -> Layer {
+    // sorts cells so that features are ordered row by row
+    let mut cells: Vec<((u32, u32), C)> = cells.into_iter().collect();
+    cells.sort_by_key(|&((i, j), _)| (j, i));
+    let features: Vec<(Vec<u32>, [i64; N])> = cells
+        .iter()
+        .map(|(cell, content)| make_feature(*cell, content))
+        .collect();
+
+    // counts values
+    let mut value_freqs = PropertyValueFrequencies::new();
+    for (_, values) in features.iter() {
+        for &value in values {
+            value_freqs.add(value);
+        }
+    }
+    let (values_sorted_by_freq, value_to_index) = value_freqs.into_sorted_values();
+
+    // builds the layer
+    let mut layer = Layer::new();
+    // - configures the basic parameters
+    layer.set_version(VECTOR_TILE_VERSION);
+    layer.set_name(name.to_string());
+    layer.set_extent(TILE_EXTENT);
+    // - builds features
+    layer.features = features
+        .into_iter()
+        .enumerate()
+        .map(|(n, (geometry, values))| {
+            let mut feature = Feature::new();
+            feature.set_id(make_feature_id(grid.coordinates(), n));
+            feature.set_type(geom_type);
+            feature.geometry = geometry;
+            feature.tags = property_keys
+                .iter()
+                .zip(values)
+                .flat_map(|(key, value)| [key.0, *value_to_index.get(&PropertyValue::from(value)).unwrap()])
+                .collect();
+            feature
+        })
+        .collect();
+    // - copies the keys. but no keys if there are no features
+    if !layer.features.is_empty() {
+        layer.keys = property_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                // makes sure that the property keys are correctly indexed
+                assert_eq!(key.0, i as u32);
+                key.1.to_string()
+            })
+            .collect();
+    }
+    // - finally, moves the values
+    layer.values = values_sorted_by_freq
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    layer
+}
+
+/// Fixtures shared by the tests of the grid layers.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use business_core::types::{
+        BusinessRecord,
+        BusinessRecordBuilder,
+        BusinessType,
+        GeolocationCoordinates,
+    };
+
+    use crate::protos::vector_tile::tile::Value;
+
+    pub(crate) const TOKYO: GeolocationCoordinates = GeolocationCoordinates {
+        longitude: 139.7670506677,
+        latitude: 35.6814709332,
+    };
+    pub(crate) const SHINJUKU_STATION: GeolocationCoordinates = GeolocationCoordinates {
+        longitude: 139.7005541230,
+        latitude: 35.6898188583,
+    };
+    pub(crate) const PITTSBURGH: GeolocationCoordinates = GeolocationCoordinates {
+        longitude: -80.0078430744,
+        latitude: 40.4417106826,
+    };
+    // near the bottom-right corner of the tile at zoom level 0
+    pub(crate) const SOUTH_PACIFIC: GeolocationCoordinates = GeolocationCoordinates {
+        longitude: 179.99,
+        latitude: -85.0,
+    };
+
+    pub(crate) fn make_record(
+        record_id: &str,
+        business_type: BusinessType,
+        location: &GeolocationCoordinates,
+        timestamp: i64,
+    ) -> BusinessRecord {
+        BusinessRecordBuilder::default()
+            .record_id(record_id)
+            .dog_id(None)
+            .business_type(business_type)
+            .location(location.clone())
+            .timestamp(timestamp)
+            .build()
+            .unwrap()
+    }
+
+    /// Returns a closure that expects a `Value` is an int value.
+    #[inline]
+    pub(crate) fn expect_int(i: i64) -> impl Fn(&Value) -> bool {
+        move |v| v.int_value.as_ref().is_some_and(|&v| v == i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::fixtures::*;
+
+    #[test]
+    fn test_tile_grid_grid_size() {
+        let new_grid = |grid_size| TileGrid::new(TileCoordinates::new(0, 0, 0).unwrap(), grid_size);
+        assert_eq!(new_grid(0).grid_size, 1);
+        assert_eq!(new_grid(16).grid_size, 16);
+        assert_eq!(new_grid(4096).grid_size, 4096);
+        assert_eq!(new_grid(5000).grid_size, 4096);
+        // rounded down to a power of two
+        assert_eq!(new_grid(3).grid_size, 2);
+        assert_eq!(new_grid(100).grid_size, 64);
+        assert_eq!(new_grid(100).cell_size(), 64);
+    }
+
+    #[test]
+    fn test_tile_grid_contains_location() {
+        let grid = TileGrid::new(TileCoordinates::new(1, 1, 0).unwrap(), 16);
+        assert!(grid.contains_location(&TOKYO));
+        assert!(!grid.contains_location(&PITTSBURGH));
+    }
+
+    #[test]
+    fn test_tile_grid_uv_from_location() {
+        let grid = TileGrid::new(TileCoordinates::new(0, 0, 0).unwrap(), 16);
+        assert_eq!(grid.uv_from_location(&TOKYO), (3638, 1612));
+        assert_eq!(grid.uv_from_location(&PITTSBURGH), (1137, 1544));
+    }
+
+    #[test]
+    fn test_tile_grid_cell_from_location() {
+        let grid = TileGrid::new(TileCoordinates::new(0, 0, 0).unwrap(), 16);
+        // u = 3638, v = 1612 → (14, 6)
+        assert_eq!(grid.cell_from_location(&TOKYO), (14, 6));
+        // u = 1137, v = 1544 → (4, 6)
+        assert_eq!(grid.cell_from_location(&PITTSBURGH), (4, 6));
+    }
+
+    #[test]
+    fn test_tile_grid_cell_from_location_non_divisor_grid_size() {
+        // rounded down to 2
+        let grid = TileGrid::new(TileCoordinates::new(0, 0, 0).unwrap(), 3);
+        assert_eq!(grid.cell_from_location(&PITTSBURGH), (0, 0));
+        assert_eq!(grid.cell_from_location(&TOKYO), (1, 0));
+        assert_eq!(grid.cell_from_location(&SOUTH_PACIFIC), (1, 1));
+    }
+}
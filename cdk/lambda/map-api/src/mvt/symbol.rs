@@ -1,35 +1,232 @@
 //! MVT for symbols.
-
-use std::collections::{HashMap, HashSet};
+//!
+//! Properties of features are determined by a [`PropertySchema`], which maps
+//! a business record to property values.
+//!
+//! A layer may have a [`LayerBudget`]. If the layer would exceed the budget,
+//! features are thinned and every remaining feature has the
+//! [`PROPERTY_KEY_TRUNCATED`] property, because the vector tile specification
+//! has no layer-level properties.
+
+use std::collections::HashSet;
+use std::sync::Arc;
 
 use business_core::{
     mvt::TileCoordinates,
-    types::{BusinessRecord, GeolocationCoordinates},
+    types::{BusinessRecord, BusinessType, GeolocationCoordinates},
 };
-
-use crate::mvt::{zigzag, MvtError};
+use protobuf::Message as _;
+
+use crate::geojson::{self, FeatureCollection, position_from};
+use crate::mvt::{
+    make_feature_id,
+    MvtError,
+    PropertyValueFrequencies,
+    TILE_EXTENT,
+    VECTOR_TILE_VERSION,
+};
+use crate::mvt::budget::{LayerBudget, ThinningCandidate};
+use crate::mvt::geometry::encode_point;
+use crate::mvt::tile_builder::LayerProducer;
 use crate::protos::{
     PropertyValue,
     vector_tile::{Tile, tile::{Feature, GeomType, Layer}},
 };
-use crate::web_mercator::{
-    latitude_from_y_at_zoom,
-    longitude_from_x_at_zoom,
-    tiles_per_edge_at_zoom,
-    x_from_longitude_at_zoom,
-    y_from_latitude_at_zoom,
-    MAX_ZOOM,
-};
-
-/// Vector tile version.
-pub const VECTOR_TILE_VERSION: u32 = 2;
-
-/// Tile extent.
-pub const TILE_EXTENT: u32 = 4096;
+use crate::web_mercator::{x_from_longitude_at_zoom, y_from_latitude_at_zoom};
 
 /// Layer name.
 pub const LAYER_NAME: &str = "business_records";
 
+/// Key of the `recordId` property.
+pub const PROPERTY_KEY_RECORD_ID: &str = "recordId";
+/// Key of the `businessType` property.
+pub const PROPERTY_KEY_BUSINESS_TYPE: &str = "businessType";
+/// Key of the `timestamp` property.
+pub const PROPERTY_KEY_TIMESTAMP: &str = "timestamp";
+/// Key of the `dogId` property. (optional)
+pub const PROPERTY_KEY_DOG_ID: &str = "dogId";
+/// Key of the `truncated` property.
+///
+/// Every feature in a thinned layer has this property with `true`.
+/// Follows the keys in the [`PropertySchema`].
+pub const PROPERTY_KEY_TRUNCATED: &str = "truncated";
+
+/// Function that extracts the value of a property from a business record.
+///
+/// Returns `None` if the record does not have the property.
+pub type PropertyExtractor =
+    Arc<dyn Fn(&BusinessRecord) -> Option<PropertyValue> + Send + Sync>;
+
+/// Property of business records in a layer.
+#[derive(Clone)]
+struct RecordProperty {
+    /// Key of the property.
+    key: String,
+
+    /// Extracts the value of the property from a business record.
+    extractor: PropertyExtractor,
+
+    /// Whether values of the property are unique among business records.
+    is_unique: bool,
+}
+
+/// Schema of properties of business records in a layer.
+///
+/// Property keys are indexed in the order they are added.
+/// The default schema has the following properties:
+/// - [`PROPERTY_KEY_RECORD_ID`]: ID of the business record
+/// - [`PROPERTY_KEY_BUSINESS_TYPE`]: type of the business
+/// - [`PROPERTY_KEY_TIMESTAMP`]: timestamp of the business record
+/// - [`PROPERTY_KEY_DOG_ID`]: ID of the dog if the record has it
+///
+/// You can add more properties derived from business records; e.g.,
+///
+/// ```
+/// use map_api::mvt::symbol::PropertySchema;
+/// use map_api::protos::PropertyValue;
+///
+/// let schema = PropertySchema::default()
+///     .with_property("dayOfWeek", |record| {
+///         // 1970-01-01 was Thursday
+///         Some(PropertyValue::U64(((record.timestamp / 86400 + 4) % 7) as u64))
+///     });
+/// assert_eq!(schema.len(), 5);
+/// ```
+#[derive(Clone)]
+pub struct PropertySchema {
+    /// Properties in the order of key indices.
+    properties: Vec<RecordProperty>,
+}
+
+impl PropertySchema {
+    /// Creates a [`PropertySchema`] without any properties.
+    pub fn empty() -> Self {
+        Self {
+            properties: Vec::new(),
+        }
+    }
+
+    /// Adds a property.
+    ///
+    /// Values of the property are deduplicated in a layer, and more frequent
+    /// values are assigned lower value indices.
+    ///
+    /// ### Panics
+    ///
+    /// If the schema already has a property with the same key.
+    pub fn with_property<F>(self, key: impl Into<String>, extractor: F) -> Self
+    where
+        F: Fn(&BusinessRecord) -> Option<PropertyValue> + Send + Sync + 'static,
+    {
+        self.with_record_property(key.into(), Arc::new(extractor), false)
+    }
+
+    /// Adds a property whose values are unique among business records; e.g.,
+    /// record IDs.
+    ///
+    /// Values of the property are not deduplicated but appended to the end
+    /// of the values in a layer, because they never repeat.
+    ///
+    /// ### Panics
+    ///
+    /// If the schema already has a property with the same key.
+    pub fn with_unique_property<F>(self, key: impl Into<String>, extractor: F) -> Self
+    where
+        F: Fn(&BusinessRecord) -> Option<PropertyValue> + Send + Sync + 'static,
+    {
+        self.with_record_property(key.into(), Arc::new(extractor), true)
+    }
+
+    fn with_record_property(
+        mut self,
+        key: String,
+        extractor: PropertyExtractor,
+        is_unique: bool,
+    ) -> Self {
+        assert!(
+            self.properties.iter().all(|p| p.key != key),
+            "duplicate property key: {key}",
+        );
+        self.properties.push(RecordProperty {
+            key,
+            extractor,
+            is_unique,
+        });
+        self
+    }
+
+    /// Returns the property keys in the order of key indices.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.properties.iter().map(|p| p.key.as_str())
+    }
+
+    /// Returns the number of properties.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.properties.len()
+    }
+
+    /// Returns if there are no properties.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty()
+    }
+
+    /// Extracts the property values of a given business record.
+    ///
+    /// Returns pairs of a key index and value. Properties that the record
+    /// does not have are omitted.
+    fn values_of(&self, record: &BusinessRecord) -> Vec<(u32, PropertyValue)> {
+        self.properties
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| (p.extractor)(record).map(|value| (i as u32, value)))
+            .collect()
+    }
+
+    /// Returns if values of the property at a given key index are unique.
+    #[inline]
+    fn is_unique(&self, key_index: u32) -> bool {
+        self.properties[key_index as usize].is_unique
+    }
+
+    /// Returns the key at a given key index.
+    #[inline]
+    fn key(&self, key_index: u32) -> &str {
+        &self.properties[key_index as usize].key
+    }
+}
+
+impl Default for PropertySchema {
+    fn default() -> Self {
+        Self::empty()
+            .with_unique_property(PROPERTY_KEY_RECORD_ID, |record| {
+                Some(record.record_id.clone().into())
+            })
+            .with_property(PROPERTY_KEY_BUSINESS_TYPE, |record| {
+                Some(record.business_type.to_string().into())
+            })
+            .with_property(PROPERTY_KEY_TIMESTAMP, |record| Some(record.timestamp.into()))
+            .with_property(PROPERTY_KEY_DOG_ID, |record| {
+                record.dog_id.clone().map(Into::into)
+            })
+    }
+}
+
+impl std::fmt::Debug for PropertySchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PropertySchema")
+            .field("keys", &self.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Returns the name of the layer for business records of a given type; e.g.,
+/// "business_records_pee".
+pub fn layer_name_for_business_type(business_type: &BusinessType) -> String {
+    format!("{LAYER_NAME}_{business_type}")
+}
+
 /// Buffer for business records in a vector tile.
 ///
 /// Use this buffer to build a vector tile which contains business records.
@@ -37,58 +234,121 @@ pub struct BusinessRecordBuffer {
     /// Tile coordinates.
     coordinates: TileCoordinates,
 
-    /// Range of longitudes that the tile covers.
-    lon_range: std::ops::Range<f64>,
+    /// Buffer around the tile in extent units.
+    ///
+    /// Records within the buffer outside of the tile are also included so
+    /// that symbols on tile edges are not clipped.
+    buffer: u32,
+
+    /// Name of the layer.
+    layer_name: String,
 
-    /// Range of latitudes that the tile covers.
-    lat_range: std::ops::Range<f64>,
+    /// Schema of properties.
+    schema: PropertySchema,
+
+    /// Budget of the layer.
+    budget: LayerBudget,
 
     /// Records in the buffer.
     records: Vec<BusinessRecord>,
 
+    /// Property values of the records.
+    ///
+    /// See [`PropertySchema::values_of`].
+    properties: Vec<Vec<(u32, PropertyValue)>>,
+
     /// Unique record IDs within the tile.
     record_ids: HashSet<String>,
 
-    /// String values and frequencies within the tile.
-    string_values: HashMap<String, u64>,
-
-    /// `i64` values and frequencies within the tile.
-    i64_values: HashMap<i64, u64>,
+    /// Frequencies of values of non-unique properties within the tile.
+    value_freqs: PropertyValueFrequencies,
 }
 
 impl BusinessRecordBuffer {
     /// Creates a new [`BusinessRecordBuffer`] for given tile coordinates.
+    ///
+    /// The tile has no buffer.
     pub fn new(coordinates: TileCoordinates) -> Self {
-        let min_longitude = longitude_from_x_at_zoom(coordinates.x, coordinates.zoom);
-        let max_longitude = longitude_from_x_at_zoom(coordinates.x + 1, coordinates.zoom);
-        let min_latitude = latitude_from_y_at_zoom(coordinates.y + 1, coordinates.zoom);
-        let max_latitude = latitude_from_y_at_zoom(coordinates.y, coordinates.zoom);
+        Self::with_buffer(coordinates, 0)
+    }
+
+    /// Creates a new [`BusinessRecordBuffer`] for given tile coordinates with
+    /// a given buffer in extent units.
+    ///
+    /// Records within `buffer` outside of the tile are encoded with
+    /// coordinates below 0 or above [`TILE_EXTENT`].
+    pub fn with_buffer(coordinates: TileCoordinates, buffer: u32) -> Self {
         Self {
             coordinates,
-            lon_range: min_longitude..max_longitude,
-            lat_range: min_latitude..max_latitude,
+            buffer,
+            layer_name: LAYER_NAME.to_string(),
+            schema: PropertySchema::default(),
+            budget: LayerBudget::default(),
             records: Vec::new(),
+            properties: Vec::new(),
             record_ids: HashSet::new(),
-            string_values: HashMap::new(),
-            i64_values: HashMap::new(),
+            value_freqs: PropertyValueFrequencies::new(),
+        }
+    }
+
+    /// Replaces the name of the layer.
+    ///
+    /// The layer is named [`LAYER_NAME`] by default.
+    pub fn with_layer_name(mut self, layer_name: impl Into<String>) -> Self {
+        self.layer_name = layer_name.into();
+        self
+    }
+
+    /// Replaces the schema of properties.
+    ///
+    /// Properties of the records already in the buffer are extracted again.
+    pub fn with_schema(mut self, schema: PropertySchema) -> Self {
+        self.schema = schema;
+        self.value_freqs = PropertyValueFrequencies::new();
+        self.properties = self.records
+            .iter()
+            .map(|record| self.schema.values_of(record))
+            .collect();
+        for properties in self.properties.iter() {
+            Self::count_values(&self.schema, &mut self.value_freqs, properties);
         }
+        self
+    }
+
+    /// Replaces the budget of the layer.
+    ///
+    /// There is no budget by default. The budget applies only to the
+    /// conversion into a [`Layer`].
+    pub fn with_budget(mut self, budget: LayerBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Returns the number of business records in the buffer.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns if the buffer has no business records.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
     }
 
     /// Appends a given business record to the buffer.
     ///
     /// May return the following error:
-    /// - [`MvtError::OutsideOfTile`]: if the tile does not contain the
-    ///   record's location
+    /// - [`MvtError::OutsideOfTile`]: if the tile, including the buffer, does
+    ///   not contain the record's location
     /// - [`MvtError::DuplicateRecordId`]: if the record's ID is already in the
     ///   buffer
     pub fn append_business_record(&mut self, record: BusinessRecord) -> Result<(), MvtError> {
         if self.contains_location(&record.location) {
             self.add_record_id(record.record_id.clone())?;
-            if let Some(dog_id) = record.dog_id.as_ref() {
-                self.add_string_value(dog_id.clone());
-            }
-            self.add_string_value(record.business_type.clone().to_string());
-            self.add_i64_value(record.timestamp);
+            let properties = self.schema.values_of(&record);
+            Self::count_values(&self.schema, &mut self.value_freqs, &properties);
+            self.properties.push(properties);
             self.records.push(record);
             Ok(())
         } else {
@@ -96,11 +356,12 @@ impl BusinessRecordBuffer {
         }
     }
 
-    /// Returns if the tile contains a given location.
+    /// Returns if the tile, including the buffer, contains a given location.
     #[inline]
     fn contains_location(&self, location: &GeolocationCoordinates) -> bool {
-        self.lon_range.contains(&location.longitude) &&
-            self.lat_range.contains(&location.latitude)
+        let range = -(self.buffer as i64)..(TILE_EXTENT + self.buffer) as i64;
+        range.contains(&self.u_from_longitude_unbounded(location.longitude)) &&
+            range.contains(&self.v_from_latitude_unbounded(location.latitude))
     }
 
     /// Adds a record ID to the buffer.
@@ -118,71 +379,28 @@ impl BusinessRecordBuffer {
         }
     }
 
-    /// Adds a string value.
+    /// Counts values of non-unique properties.
     ///
     /// More frequent values shall be assigned lower value indices.
     #[inline]
-    fn add_string_value(&mut self, value: String) {
-        self.string_values
-            .entry(value)
-            .and_modify(|freq| *freq += 1)
-            .or_insert(1);
-    }
-
-    /// Adds an `i64` value to the buffer.
-    ///
-    /// More frequent values shall be assigned lower value indices.
-    #[inline]
-    fn add_i64_value(&mut self, value: i64) {
-        self.i64_values
-            .entry(value)
-            .and_modify(|freq| *freq += 1)
-            .or_insert(1);
+    fn count_values(
+        schema: &PropertySchema,
+        value_freqs: &mut PropertyValueFrequencies,
+        properties: &[(u32, PropertyValue)],
+    ) {
+        for (key_index, value) in properties.iter() {
+            if !schema.is_unique(*key_index) {
+                value_freqs.add(value.clone());
+            }
+        }
     }
 
     /// Calculates the feature ID for a business record at a given index.
     ///
-    /// A feature ID bits layout depends on the zoom level:
-    /// - z=0 → [i:59bits][00000]
-    /// - z=1 → [i:57bits][x][y][00001]
-    /// - z=2 → [i:55bits][xx][yy][00010]
-    /// - z=3 → [i:53bits][xxx][yyy][00011]
-    /// - ...
-    /// - z=22 → [i:15bits][x:22bits][y:22bits][10110]
-    ///
-    /// This means the larger the zoom level, the less bits are available for
-    /// features in a tile. However, it is reasonable, because the larger the
-    /// zoom level, the less features should be present in a tile.
-    ///
-    /// A feature ID does not identify a business record but it guarantees
-    /// - no two business records within a tile have the same feature ID
-    /// - no two tiles have features with the same feature ID
-    ///
-    /// So you should not depend on feature IDs to identify business records,
-    /// but use the `recordId` property instead.
-    ///
-    /// ### Panics
-    ///
-    /// - if the zoom level is greater than [`MAX_ZOOM`]
-    /// - if the x coordinate is greater than or equal to the number of tiles
-    ///   per edge at the zoom level
-    /// - if the y coordinate is greater than or equal to the number of tiles
-    ///   per edge at the zoom level
-    /// - if `index` cannot be represented by the available bits for the
-    ///   index at the zoom level
+    /// See [`make_feature_id`] for more details.
     #[inline]
     fn make_feature_id(&self, i: usize) -> u64 {
-        assert!(self.coordinates.zoom <= MAX_ZOOM);
-        assert!(self.coordinates.x < tiles_per_edge_at_zoom(self.coordinates.zoom) as u32);
-        assert!(self.coordinates.y < tiles_per_edge_at_zoom(self.coordinates.zoom) as u32);
-        const Z_BITS: u32 = 5;
-        let x_bits = self.coordinates.zoom;
-        let y_bits = self.coordinates.zoom;
-        assert!((i as u64) < (1 << (u64::BITS - (x_bits + y_bits + Z_BITS))));
-        ((i as u64) << (x_bits + y_bits + Z_BITS)) |
-            ((self.coordinates.x as u64) << (y_bits + Z_BITS)) |
-            ((self.coordinates.y as u64) << Z_BITS) |
-            self.coordinates.zoom as u64
+        make_feature_id(&self.coordinates, i)
     }
 
     /// Calculates the u coordinate from longitude.
@@ -195,13 +413,22 @@ impl BusinessRecordBuffer {
     ///   an integer in the range `[0, TILE_EXTENT)`; e.g., fractional part of
     ///   the x coordinate multiplied by the extent
     ///
-    /// Undefined if `longitude` is outside of the tile.
+    /// u coordinate may be negative or greater than or equal to
+    /// [`TILE_EXTENT`] if `longitude` is in the buffer.
+    ///
+    /// Undefined if `longitude` is outside of the tile and buffer.
+    #[inline]
+    fn u_from_longitude(&self, longitude: f64) -> i32 {
+        self.u_from_longitude_unbounded(longitude) as i32
+    }
+
+    /// Calculates the u coordinate from longitude without any bounds.
     #[inline]
-    fn u_from_longitude(&self, longitude: f64) -> u32 {
-        let x = x_from_longitude_at_zoom(longitude, self.coordinates.zoom);
-        let u = x - self.coordinates.x as f64;
+    fn u_from_longitude_unbounded(&self, longitude: f64) -> i64 {
+        let x = x_from_longitude_at_zoom(longitude, self.coordinates.zoom());
+        let u = x - self.coordinates.x() as f64;
         let u = ((TILE_EXTENT as f64) * u).floor();
-        u as u32
+        u as i64
     }
 
     /// Calculates the v coordinate from latitude.
@@ -214,131 +441,268 @@ impl BusinessRecordBuffer {
     ///   an integer in the range `[0, TILE_EXTENT)`; e.g., fractional part of
     ///   the y coordinate multiplied by the extent
     ///
-    /// Undefined if `latitude` is outside of the tile.
+    /// v coordinate may be negative or greater than or equal to
+    /// [`TILE_EXTENT`] if `latitude` is in the buffer.
+    ///
+    /// Undefined if `latitude` is outside of the tile and buffer.
+    #[inline]
+    fn v_from_latitude(&self, latitude: f64) -> i32 {
+        self.v_from_latitude_unbounded(latitude) as i32
+    }
+
+    /// Calculates the v coordinate from latitude without any bounds.
     #[inline]
-    fn v_from_latitude(&self, latitude: f64) -> u32 {
-        let y = y_from_latitude_at_zoom(latitude, self.coordinates.zoom);
-        let v = y - self.coordinates.y as f64;
+    fn v_from_latitude_unbounded(&self, latitude: f64) -> i64 {
+        let y = y_from_latitude_at_zoom(latitude, self.coordinates.zoom());
+        let v = y - self.coordinates.y() as f64;
         let v = ((TILE_EXTENT as f64) * v).floor();
-        v as u32
+        v as i64
     }
 }
 
 impl From<BusinessRecordBuffer> for Tile {
-    fn from(mut buffer: BusinessRecordBuffer) -> Self {
-        // drains string values with frequencies
-        let string_value_freqs: Vec<(PropertyValue, u64)> = buffer
-            .string_values
-            .drain()
-            .map(|(value, n)| (value.into(), n))
-            .collect::<>();
-        // drains `i64` values with frequencies
-        let i64_value_freqs: Vec<(PropertyValue, u64)> = buffer
-            .i64_values
-            .drain()
-            .map(|(value, n)| (value.into(), n))
-            .collect::<>();
-        // sorts values by frequency in descending order
-        let mut values_sorted_by_freq =
-            [string_value_freqs, i64_value_freqs].concat();
-        values_sorted_by_freq.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
-        // extracts values in sorted order and appends record IDs to the end
-        let values_sorted_by_freq: Vec<PropertyValue> = values_sorted_by_freq
-            .into_iter()
-            .map(|(value, _)| value)
-            .chain(buffer.record_ids.iter().map(|id| id.clone().into()))
-            .collect::<>();
+    fn from(buffer: BusinessRecordBuffer) -> Self {
+        let mut tile = Tile::new();
+        tile.layers.push(buffer.into());
+        tile
+    }
+}
 
-        // builds value → index maps per type
-        let string_value_to_index: HashMap<&String, u32> = values_sorted_by_freq
-            .iter()
-            .enumerate()
-            .map(|(i, value)| value.get_string().map(|s| (s, i as u32)))
-            .flatten()
-            .collect::<>();
-        let i64_value_to_index: HashMap<i64, u32> = values_sorted_by_freq
+impl From<BusinessRecordBuffer> for Layer {
+    /// Converts the buffer into a layer within the budget.
+    ///
+    /// Thins records with the strategy in the budget while the layer exceeds
+    /// the budget.
+    fn from(buffer: BusinessRecordBuffer) -> Self {
+        let num_records = buffer.records.len();
+        let mut n = buffer.budget.max_features.map_or(num_records, |max| max.min(num_records));
+        loop {
+            let layer = if n < num_records {
+                buffer.encode_layer(&buffer.choose_records(n), true)
+            } else {
+                buffer.encode_layer(&(0..num_records).collect::<Vec<_>>(), false)
+            };
+            let num_bytes = layer.compute_size() as usize;
+            if n == 0 || buffer.budget.allows(n, num_bytes) {
+                return layer;
+            }
+            // shrinks in proportion to the excess, but at least by one
+            let max_bytes = buffer.budget.max_bytes.unwrap_or(num_bytes);
+            n = (n * max_bytes / num_bytes).min(n - 1);
+        }
+    }
+}
+
+impl BusinessRecordBuffer {
+    /// Chooses `n` records to keep with the strategy in the budget.
+    ///
+    /// Returns the indices of the chosen records in ascending order.
+    fn choose_records(&self, n: usize) -> Vec<usize> {
+        let candidates: Vec<ThinningCandidate> = self.records
             .iter()
-            .enumerate()
-            .map(|(i, value)| value.get_i64().map(|n| (n, i as u32)))
-            .flatten()
-            .collect::<>();
+            .map(|record| ThinningCandidate {
+                position: (
+                    self.u_from_longitude(record.location.longitude),
+                    self.v_from_latitude(record.location.latitude),
+                ),
+                timestamp: record.timestamp,
+                key: &record.record_id,
+            })
+            .collect();
+        self.budget.strategy.choose(&candidates, n)
+    }
+
+    /// Encodes the records at given indices into a layer.
+    ///
+    /// Features retain their IDs regardless of dropped records.
+    /// Every feature has the [`PROPERTY_KEY_TRUNCATED`] property if
+    /// `is_truncated` is `true`.
+    fn encode_layer(&self, indices: &[usize], is_truncated: bool) -> Layer {
+        let truncated_key_index = self.schema.len() as u32;
+        let truncated_property = (truncated_key_index, PropertyValue::from(true));
+
+        // sorts values of non-unique properties by frequency in descending
+        // order
+        let value_freqs = if is_truncated {
+            let mut value_freqs = PropertyValueFrequencies::new();
+            for &i in indices {
+                Self::count_values(&self.schema, &mut value_freqs, &self.properties[i]);
+                value_freqs.add(truncated_property.1.clone());
+            }
+            value_freqs
+        } else {
+            self.value_freqs.clone()
+        };
+        let (mut values, value_to_index) = value_freqs.into_sorted_values();
 
         // builds the layer
         let mut layer = Layer::new();
         // - configures the basic parameters
         layer.set_version(VECTOR_TILE_VERSION);
-        layer.set_name(LAYER_NAME.to_string());
+        layer.set_name(self.layer_name.clone());
         layer.set_extent(TILE_EXTENT);
         // - builds features
-        layer.features = buffer
-            .records
-            .iter()
-            .enumerate()
-            .map(|(i, record)| {
-                let mut feature = Feature::new();
-                feature.set_id(buffer.make_feature_id(i));
-                feature.set_type(GeomType::POINT);
-                feature.geometry = vec![
-                    9, // MoveTo(1) | (Count(1) << 3)
-                    zigzag(buffer.u_from_longitude(record.location.longitude)),
-                    zigzag(buffer.v_from_latitude(record.location.latitude)),
-                ];
-                feature.tags = vec![
-                    PROPERTY_KEY_RECORD_ID.0,
-                    *string_value_to_index.get(&record.record_id).unwrap(),
-                    PROPERTY_KEY_BUSINESS_TYPE.0,
-                    *string_value_to_index.get(&record.business_type.to_string()).unwrap(),
-                    PROPERTY_KEY_TIMESTAMP.0,
-                    *i64_value_to_index.get(&record.timestamp).unwrap(),
-                ];
-                if let Some(dog_id) = record.dog_id.as_ref() {
-                    feature.tags.extend_from_slice(&[
-                        PROPERTY_KEY_DOG_ID.0,
-                        *string_value_to_index.get(dog_id).unwrap(),
-                    ]);
-                }
-                feature
-            })
-            .collect::<>();
+        //   values of unique properties are appended to the end of the values
+        for &i in indices {
+            let record = &self.records[i];
+            let mut feature = Feature::new();
+            feature.set_id(self.make_feature_id(i));
+            feature.set_type(GeomType::POINT);
+            feature.geometry = encode_point((
+                self.u_from_longitude(record.location.longitude),
+                self.v_from_latitude(record.location.latitude),
+            ));
+            let properties = self.properties[i]
+                .iter()
+                .chain(is_truncated.then_some(&truncated_property));
+            for (key_index, value) in properties {
+                let value_index = if *key_index < truncated_key_index &&
+                    self.schema.is_unique(*key_index)
+                {
+                    values.push(value.clone());
+                    (values.len() - 1) as u32
+                } else {
+                    // every value of a non-unique property has been counted
+                    *value_to_index.get(value).unwrap()
+                };
+                feature.tags.extend_from_slice(&[*key_index, value_index]);
+            }
+            layer.features.push(feature);
+        }
         // - copies the keys. but no keys if there are no features
-        if layer.features.len() > 0 {
-            layer.keys = [
-                PROPERTY_KEY_RECORD_ID,
-                PROPERTY_KEY_BUSINESS_TYPE,
-                PROPERTY_KEY_TIMESTAMP,
-                PROPERTY_KEY_DOG_ID,
-            ]
-                .into_iter()
-                .enumerate()
-                .map(|(i, key)| {
-                    // makes sure that the property keys are correctly indexed
-                    assert_eq!(key.0, i as u32);
-                    key.1.to_string()
-                })
-                .collect::<>();
+        if !layer.features.is_empty() {
+            layer.keys = self.schema.keys().map(str::to_string).collect();
+            if is_truncated {
+                layer.keys.push(PROPERTY_KEY_TRUNCATED.to_string());
+            }
         }
         // - finally, moves the values
-        layer.values = values_sorted_by_freq
+        layer.values = values
             .into_iter()
             .map(Into::into)
-            .collect::<>();
+            .collect();
 
-        // wraps the layer in a tile
-        let mut tile = Tile::new();
-        tile.layers.push(layer);
+        layer
+    }
+}
 
-        tile
+impl From<BusinessRecordBuffer> for FeatureCollection {
+    /// Converts the buffer into a GeoJSON `FeatureCollection`.
+    ///
+    /// Features have the same IDs and properties as those in the layer, but
+    /// locations are not quantized to the extent.
+    fn from(buffer: BusinessRecordBuffer) -> Self {
+        let features = buffer
+            .records
+            .iter()
+            .zip(buffer.properties.iter())
+            .enumerate()
+            .map(|(i, (record, properties))| {
+                let properties = properties
+                    .iter()
+                    .map(|(key_index, value)| {
+                        // serialization of a property value never fails
+                        let value = serde_json::to_value(value).unwrap();
+                        (buffer.schema.key(*key_index).to_string(), value)
+                    })
+                    .collect();
+                geojson::Feature::new(
+                    Some(buffer.make_feature_id(i)),
+                    Some(geojson::Geometry::Point(position_from(&record.location))),
+                    properties,
+                )
+            })
+            .collect();
+        FeatureCollection::new(features)
     }
 }
 
-/// Key index and name for the `recordId` property.
-const PROPERTY_KEY_RECORD_ID: (u32, &str) = (0, "recordId");
-/// Key index and name for the `businessType` property.
-const PROPERTY_KEY_BUSINESS_TYPE: (u32, &str) = (1, "businessType");
-/// Key index and name for the `timestamp` property.
-const PROPERTY_KEY_TIMESTAMP: (u32, &str) = (2, "timestamp");
-/// Key index and name for the `dogId` property. (optional)
-const PROPERTY_KEY_DOG_ID: (u32, &str) = (3, "dogId");
+/// Buffers that separate business records into layers by business type.
+///
+/// Produces a layer per business type named by
+/// [`layer_name_for_business_type`], so that clients can style each type
+/// without filtering features by the `businessType` property.
+pub struct BusinessRecordBuffersByType {
+    /// Buffer for pee records.
+    pee: BusinessRecordBuffer,
+
+    /// Buffer for poo records.
+    poo: BusinessRecordBuffer,
+}
+
+impl BusinessRecordBuffersByType {
+    /// Creates new [`BusinessRecordBuffersByType`] for given tile coordinates
+    /// with a given buffer in extent units.
+    ///
+    /// See [`BusinessRecordBuffer::with_buffer`] for more details.
+    pub fn with_buffer(coordinates: TileCoordinates, buffer: u32) -> Self {
+        let buffer_for = |business_type| {
+            BusinessRecordBuffer::with_buffer(coordinates.clone(), buffer)
+                .with_layer_name(layer_name_for_business_type(&business_type))
+        };
+        Self {
+            pee: buffer_for(BusinessType::Pee),
+            poo: buffer_for(BusinessType::Poo),
+        }
+    }
+
+    /// Replaces the schema of properties of all the layers.
+    ///
+    /// See [`BusinessRecordBuffer::with_schema`] for more details.
+    pub fn with_schema(self, schema: PropertySchema) -> Self {
+        Self {
+            pee: self.pee.with_schema(schema.clone()),
+            poo: self.poo.with_schema(schema),
+        }
+    }
+
+    /// Replaces the budget of the layers.
+    ///
+    /// Each layer gets a half of `budget.max_features` and `budget.max_bytes`
+    /// so that the layers together stay within `budget`.
+    ///
+    /// See [`BusinessRecordBuffer::with_budget`] for more details.
+    pub fn with_budget(self, budget: LayerBudget) -> Self {
+        let half_budget = LayerBudget {
+            max_features: budget.max_features.map(|n| n / 2),
+            max_bytes: budget.max_bytes.map(|n| n / 2),
+            strategy: budget.strategy,
+        };
+        Self {
+            pee: self.pee.with_budget(half_budget.clone()),
+            poo: self.poo.with_budget(half_budget),
+        }
+    }
+
+    /// Appends a given business record to the buffer for its type.
+    ///
+    /// May return the same errors as
+    /// [`BusinessRecordBuffer::append_business_record`].
+    pub fn append_business_record(&mut self, record: BusinessRecord) -> Result<(), MvtError> {
+        match record.business_type {
+            BusinessType::Pee => self.pee.append_business_record(record),
+            BusinessType::Poo => self.poo.append_business_record(record),
+        }
+    }
+
+    /// Returns the number of business records in all the buffers.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pee.len() + self.poo.len()
+    }
+
+    /// Returns if all the buffers are empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pee.is_empty() && self.poo.is_empty()
+    }
+}
+
+impl LayerProducer for BusinessRecordBuffersByType {
+    fn into_layers(self) -> Vec<Layer> {
+        vec![self.pee.into(), self.poo.into()]
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -367,61 +731,37 @@ mod tests {
 
     #[test]
     fn test_business_record_buffer_contains_location() {
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 0,
-            x: 0,
-            y: 0,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(0, 0, 0).unwrap());
         assert!(buffer.contains_location(&TOKYO));
         assert!(buffer.contains_location(&PITTSBURGH));
         assert!(buffer.contains_location(&BUENOS_AIRES));
         assert!(buffer.contains_location(&CAIRNS));
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 1,
-            x: 1,
-            y: 0,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(1, 1, 0).unwrap());
         assert!(buffer.contains_location(&TOKYO));
         assert!(!buffer.contains_location(&PITTSBURGH));
         assert!(!buffer.contains_location(&BUENOS_AIRES));
         assert!(!buffer.contains_location(&CAIRNS));
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 2,
-            x: 1,
-            y: 2,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(2, 1, 2).unwrap());
         assert!(!buffer.contains_location(&TOKYO));
         assert!(!buffer.contains_location(&PITTSBURGH));
         assert!(buffer.contains_location(&BUENOS_AIRES));
         assert!(!buffer.contains_location(&CAIRNS));
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 10,
-            x: 927,
-            y: 560,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(10, 927, 560).unwrap());
         assert!(!buffer.contains_location(&TOKYO));
         assert!(!buffer.contains_location(&PITTSBURGH));
         assert!(!buffer.contains_location(&BUENOS_AIRES));
         assert!(buffer.contains_location(&CAIRNS));
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 22,
-            x: 1164993,
-            y: 1581136,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(22, 1164993, 1581136).unwrap());
         assert!(!buffer.contains_location(&TOKYO));
         assert!(buffer.contains_location(&PITTSBURGH));
         assert!(!buffer.contains_location(&BUENOS_AIRES));
         assert!(!buffer.contains_location(&CAIRNS));
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 16,
-            x: 32768,
-            y: 32768,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(16, 32768, 32768).unwrap());
         assert!(!buffer.contains_location(&TOKYO));
         assert!(!buffer.contains_location(&PITTSBURGH));
         assert!(!buffer.contains_location(&BUENOS_AIRES));
@@ -430,11 +770,7 @@ mod tests {
 
     #[test]
     fn test_business_record_buffer_append_business_record_ok() {
-        let mut buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 0,
-            x: 0,
-            y: 0,
-        });
+        let mut buffer = BusinessRecordBuffer::new(TileCoordinates::new(0, 0, 0).unwrap());
         assert!(
             buffer
                 .append_business_record(
@@ -467,11 +803,7 @@ mod tests {
 
     #[test]
     fn test_business_record_buffer_append_business_record_outside_of_tile() {
-        let mut buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 1,
-            x: 1,
-            y: 0,
-        });
+        let mut buffer = BusinessRecordBuffer::new(TileCoordinates::new(1, 1, 0).unwrap());
         assert!(
             buffer
                 .append_business_record(
@@ -505,11 +837,7 @@ mod tests {
 
     #[test]
     fn test_business_record_buffer_append_business_record_duplicate_record_id() {
-        let mut buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 0,
-            x: 0,
-            y: 0,
-        });
+        let mut buffer = BusinessRecordBuffer::new(TileCoordinates::new(0, 0, 0).unwrap());
         assert!(
             buffer
                 .append_business_record(
@@ -543,48 +871,28 @@ mod tests {
 
     #[test]
     fn test_business_record_buffer_make_feature_id() {
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 0,
-            x: 0,
-            y: 0,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(0, 0, 0).unwrap());
         assert_eq!(buffer.make_feature_id(0), 0);
         assert_eq!(buffer.make_feature_id(1), 0x20);
         assert_eq!(buffer.make_feature_id(0xFFFFFFFF), 0x1FFFFFFFE0); // 32bits should be safe, although, theoretically, fewer bits are possible
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 1,
-            x: 0,
-            y: 0,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(1, 0, 0).unwrap());
         assert_eq!(buffer.make_feature_id(0), 1);
         assert_eq!(buffer.make_feature_id(1), 0x81);
         assert_eq!(buffer.make_feature_id(0xFFFFFFFF), 0x7FFFFFFF81); // 32bits should be safe, although, theoretically, fewer bits are possible
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 1,
-            x: 1,
-            y: 0,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(1, 1, 0).unwrap());
         assert_eq!(buffer.make_feature_id(0), 0x41);
         assert_eq!(buffer.make_feature_id(1), 0xC1);
         assert_eq!(buffer.make_feature_id(0xFFFFFFFF), 0x7FFFFFFFC1); // 32bits should be safe, although, theoretically, fewer bits are possible
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 1,
-            x: 0,
-            y: 1,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(1, 0, 1).unwrap());
         assert_eq!(buffer.make_feature_id(0), 0x21);
         assert_eq!(buffer.make_feature_id(1), 0xA1);
         assert_eq!(buffer.make_feature_id(0xFFFFFFFF), 0x7FFFFFFFA1); // 32bits should be safe, although, theoretically, fewer bits are possible
 
         // maximum feature ID
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 22,
-            x: 0x3FFFFF,
-            y: 0x3FFFFF,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(22, 0x3FFFFF, 0x3FFFFF).unwrap());
         assert_eq!(buffer.make_feature_id(0), 0x1FFFFFFFFFFF6);
         assert_eq!(buffer.make_feature_id(1), 0x3FFFFFFFFFFF6);
         assert_eq!(buffer.make_feature_id(0x7FFF), 0xFFFFFFFFFFFFFFF6); // 15bits are left for the index part
@@ -592,65 +900,65 @@ mod tests {
 
     #[test]
     fn test_business_record_buffer_u_from_longitude() {
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 0,
-            x: 0,
-            y: 0,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(0, 0, 0).unwrap());
         assert_eq!(buffer.u_from_longitude(TOKYO.longitude), 3638);
         assert_eq!(buffer.u_from_longitude(PITTSBURGH.longitude), 1137);
         assert_eq!(buffer.u_from_longitude(BUENOS_AIRES.longitude), 1383);
         assert_eq!(buffer.u_from_longitude(CAIRNS.longitude), 3708);
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 16,
-            x: 58211,
-            y: 25806,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(16, 58211, 25806).unwrap());
         assert_eq!(buffer.u_from_longitude(TOKYO.longitude), 3338);
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 22,
-            x: 1164993,
-            y: 1581136,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(22, 1164993, 1581136).unwrap());
         assert_eq!(buffer.u_from_longitude(PITTSBURGH.longitude), 270);
     }
 
     #[test]
     fn test_business_record_buffer_v_from_latitude() {
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 0,
-            x: 0,
-            y: 0,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(0, 0, 0).unwrap());
         assert_eq!(buffer.v_from_latitude(TOKYO.latitude), 1612);
         assert_eq!(buffer.v_from_latitude(PITTSBURGH.latitude), 1544);
         assert_eq!(buffer.v_from_latitude(BUENOS_AIRES.latitude), 2468);
         assert_eq!(buffer.v_from_latitude(CAIRNS.latitude), 2241);
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 16,
-            x: 58211,
-            y: 25806,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(16, 58211, 25806).unwrap());
         assert_eq!(buffer.v_from_latitude(TOKYO.latitude), 2387);
 
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 22,
-            x: 1164993,
-            y: 1581136,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(22, 1164993, 1581136).unwrap());
         assert_eq!(buffer.v_from_latitude(PITTSBURGH.latitude), 674);
     }
 
+    #[test]
+    fn test_business_record_buffer_with_buffer() {
+        // Tokyo is at u = 3338 in the tile (z = 16, x = 58211, y = 25806),
+        // and at u = -758 in the next tile to the right
+        let coordinates = TileCoordinates::new(16, 58212, 25806).unwrap();
+        let buffer = BusinessRecordBuffer::with_buffer(coordinates.clone(), 512);
+        assert!(!buffer.contains_location(&TOKYO));
+
+        let mut buffer = BusinessRecordBuffer::with_buffer(coordinates, 1024);
+        assert!(buffer.contains_location(&TOKYO));
+        assert_eq!(buffer.u_from_longitude(TOKYO.longitude), -758);
+        assert_eq!(buffer.v_from_latitude(TOKYO.latitude), 2387);
+        buffer
+            .append_business_record(
+                BusinessRecordBuilder::default()
+                    .record_id("test_record_1")
+                    .dog_id(None)
+                    .business_type(BusinessType::Pee)
+                    .location(TOKYO.clone())
+                    .timestamp(1_755_317_141)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        let tile: Tile = buffer.into();
+        assert_eq!(tile.layers[0].features[0].geometry, vec![9, 1515, 4774]);
+    }
+
     #[test]
     fn test_business_record_buffer_into_tile_earth() {
-        let mut buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 0,
-            x: 0,
-            y: 0,
-        });
+        let mut buffer = BusinessRecordBuffer::new(TileCoordinates::new(0, 0, 0).unwrap());
         assert!(
             buffer
                 .append_business_record(
@@ -708,10 +1016,9 @@ mod tests {
                 .is_ok(),
         );
 
-        assert_eq!(buffer.i64_values.len(), 3);
-        assert_eq!(*buffer.i64_values.get(&1_755_317_141).unwrap(), 1);
-        assert_eq!(*buffer.i64_values.get(&1_755_317_142).unwrap(), 2);
-        assert_eq!(*buffer.i64_values.get(&1_597_562_418).unwrap(), 1);
+        assert_eq!(buffer.value_freqs.get(&1_755_317_141.into()), Some(1));
+        assert_eq!(buffer.value_freqs.get(&1_755_317_142.into()), Some(2));
+        assert_eq!(buffer.value_freqs.get(&1_597_562_418.into()), Some(1));
 
         // 4 distinct business record IDs
         // 2 distinct business types
@@ -737,7 +1044,8 @@ mod tests {
         assert_eq!(keys[3], "dogId");
 
         // values are sorted by frequency in descending order
-        // no guarantee about the order of values with the same frequency
+        // see `test_business_record_buffer_into_layer_reproducible` for the
+        // order of values with the same frequency
         let values = &layer.values;
         // - 3 times
         let i_poo = values[0..=0].iter().position(expect_string("poo")).unwrap() as u32;
@@ -826,11 +1134,7 @@ mod tests {
             latitude: 35.5626801098,
         };
 
-        let mut buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 10,
-            x: 909,
-            y: 403,
-        });
+        let mut buffer = BusinessRecordBuffer::new(TileCoordinates::new(10, 909, 403).unwrap());
         assert!(
             buffer
                 .append_business_record(
@@ -911,22 +1215,31 @@ mod tests {
         assert_eq!(keys[2], "timestamp");
         assert_eq!(keys[3], "dogId");
 
-        // values are sorted by frequency in descending order
-        // no guarantee about the order of values with the same frequency
+        // values are sorted by frequency in descending order, and then by
+        // the natural order of values
         let values = &layer.values;
         // - 3 times
-        let i_1_755_317_141 = values[0..=0].iter().position(expect_int(1_755_317_141)).unwrap() as u32;
+        assert!(expect_int(1_755_317_141)(&values[0]));
         // - twice
-        let i_pee = values[1..=2].iter().position(expect_string("pee")).unwrap() as u32 + 1;
+        assert!(expect_string("pee")(&values[1]));
         // - once
-        let i_poo = values[2..=5].iter().position(expect_string("poo")).unwrap() as u32 + 2;
-        let i_dog_1 = values[2..=5].iter().position(expect_string("dog_1")).unwrap() as u32 + 2;
-        let i_dog_2 = values[2..=5].iter().position(expect_string("dog_2")).unwrap() as u32 + 2;
-        let i_dog_3 = values[2..=5].iter().position(expect_string("dog_3")).unwrap() as u32 + 2;
-        // - record IDs are guaranteed to be at the end
-        let i_tokyo_station = values[6..=8].iter().position(expect_string("tokyo_station")).unwrap() as u32 + 6;
-        let i_shinjuku_station = values[6..=8].iter().position(expect_string("shinjuku_station")).unwrap() as u32 + 6;
-        let i_kamata_station = values[6..=8].iter().position(expect_string("kamata_station")).unwrap() as u32 + 6;
+        assert!(expect_string("dog_1")(&values[2]));
+        assert!(expect_string("dog_2")(&values[3]));
+        assert!(expect_string("dog_3")(&values[4]));
+        assert!(expect_string("poo")(&values[5]));
+        // - record IDs are appended to the end in the order of features
+        assert!(expect_string("tokyo_station")(&values[6]));
+        assert!(expect_string("shinjuku_station")(&values[7]));
+        assert!(expect_string("kamata_station")(&values[8]));
+        let i_1_755_317_141 = 0;
+        let i_pee = 1;
+        let i_dog_1 = 2;
+        let i_dog_2 = 3;
+        let i_dog_3 = 4;
+        let i_poo = 5;
+        let i_tokyo_station = 6;
+        let i_shinjuku_station = 7;
+        let i_kamata_station = 8;
 
         let features = &layer.features;
         const BASE_FEATURE_ID: u64 = (909 << 15) | (403 << 5) | 10;
@@ -976,11 +1289,7 @@ mod tests {
 
     #[test]
     fn test_business_record_buffer_into_tile_empty() {
-        let buffer = BusinessRecordBuffer::new(TileCoordinates {
-            zoom: 16,
-            x: 58138,
-            y: 25860,
-        });
+        let buffer = BusinessRecordBuffer::new(TileCoordinates::new(16, 58138, 25860).unwrap());
 
         let tile: Tile = buffer.into();
         assert_eq!(tile.layers.len(), 1);
@@ -994,6 +1303,255 @@ mod tests {
         assert_eq!(layer.features.len(), 0);
     }
 
+    #[test]
+    fn test_business_record_buffer_into_feature_collection() {
+        let mut buffer = BusinessRecordBuffer::new(TileCoordinates::new(0, 0, 0).unwrap());
+        buffer
+            .append_business_record(
+                BusinessRecordBuilder::default()
+                    .record_id("test_record_1")
+                    .dog_id(Some("dog_1".to_string()))
+                    .business_type(BusinessType::Pee)
+                    .location(TOKYO.clone())
+                    .timestamp(1_755_317_141)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        buffer
+            .append_business_record(
+                BusinessRecordBuilder::default()
+                    .record_id("test_record_2")
+                    .dog_id(None)
+                    .business_type(BusinessType::Poo)
+                    .location(PITTSBURGH.clone())
+                    .timestamp(1_755_320_741)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+
+        let collection: FeatureCollection = buffer.into();
+        assert_eq!(
+            serde_json::to_value(&collection).unwrap(),
+            serde_json::json!({
+                "type": "FeatureCollection",
+                "features": [
+                    {
+                        "type": "Feature",
+                        "id": 0,
+                        "geometry": {
+                            "type": "Point",
+                            "coordinates": [TOKYO.longitude, TOKYO.latitude],
+                        },
+                        "properties": {
+                            "recordId": "test_record_1",
+                            "businessType": "pee",
+                            "timestamp": 1_755_317_141,
+                            "dogId": "dog_1",
+                        },
+                    },
+                    {
+                        "type": "Feature",
+                        "id": 1 << 5,
+                        "geometry": {
+                            "type": "Point",
+                            "coordinates": [PITTSBURGH.longitude, PITTSBURGH.latitude],
+                        },
+                        "properties": {
+                            "recordId": "test_record_2",
+                            "businessType": "poo",
+                            "timestamp": 1_755_320_741,
+                        },
+                    },
+                ],
+            }),
+        );
+    }
+
+    #[test]
+    fn test_business_record_buffers_by_type_into_layers() {
+        let mut buffers = BusinessRecordBuffersByType::with_buffer(
+            TileCoordinates::new(0, 0, 0).unwrap(),
+            0,
+        );
+        for (record_id, business_type, location) in [
+            ("test_record_1", BusinessType::Pee, TOKYO),
+            ("test_record_2", BusinessType::Poo, PITTSBURGH),
+            ("test_record_3", BusinessType::Pee, CAIRNS),
+        ] {
+            buffers
+                .append_business_record(
+                    BusinessRecordBuilder::default()
+                        .record_id(record_id)
+                        .dog_id(None)
+                        .business_type(business_type)
+                        .location(location)
+                        .timestamp(1_755_317_141)
+                        .build()
+                        .unwrap(),
+                )
+                .unwrap();
+        }
+        assert_eq!(buffers.len(), 3);
+
+        let layers = buffers.into_layers();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].name(), "business_records_pee");
+        assert_eq!(layers[0].features.len(), 2);
+        assert_eq!(layers[1].name(), "business_records_poo");
+        assert_eq!(layers[1].features.len(), 1);
+        // each layer has its own values
+        assert!(layers[0].values.iter().any(expect_string("test_record_3")));
+        assert!(!layers[0].values.iter().any(expect_string("test_record_2")));
+        assert!(!layers[1].values.iter().any(expect_string("pee")));
+    }
+
+    #[test]
+    fn test_business_record_buffers_by_type_with_budget() {
+        let mut buffers = BusinessRecordBuffersByType::with_buffer(
+            TileCoordinates::new(0, 0, 0).unwrap(),
+            0,
+        )
+        .with_budget(LayerBudget {
+            max_features: Some(2),
+            ..LayerBudget::default()
+        });
+        for (record_id, business_type, location, timestamp) in [
+            ("test_record_1", BusinessType::Pee, TOKYO, 1_755_317_141),
+            ("test_record_2", BusinessType::Poo, PITTSBURGH, 1_755_317_142),
+            ("test_record_3", BusinessType::Pee, CAIRNS, 1_755_317_143),
+        ] {
+            buffers
+                .append_business_record(
+                    BusinessRecordBuilder::default()
+                        .record_id(record_id)
+                        .dog_id(None)
+                        .business_type(business_type)
+                        .location(location)
+                        .timestamp(timestamp)
+                        .build()
+                        .unwrap(),
+                )
+                .unwrap();
+        }
+
+        // each layer is allowed a single feature
+        let layers = buffers.into_layers();
+        assert_eq!(layers[0].name(), "business_records_pee");
+        assert_eq!(layers[0].features.len(), 1);
+        assert!(layers[0].keys.iter().any(|key| key == PROPERTY_KEY_TRUNCATED));
+        // the newest pee record is kept
+        assert!(layers[0].values.iter().any(expect_string("test_record_3")));
+        assert_eq!(layers[1].name(), "business_records_poo");
+        assert_eq!(layers[1].features.len(), 1);
+        assert!(!layers[1].keys.iter().any(|key| key == PROPERTY_KEY_TRUNCATED));
+    }
+
+    #[test]
+    fn test_business_record_buffer_into_layer_reproducible() {
+        let layer: Layer = buffer_with_four_records(LayerBudget::default()).into();
+        // "pee" is the most frequent, and timestamps of the same frequency
+        // follow in ascending order. record IDs follow in the order of records
+        let expected: Vec<PropertyValue> = vec![
+            "pee".to_string().into(),
+            1_597_562_418.into(),
+            1_755_317_141.into(),
+            1_755_317_142.into(),
+            1_755_317_143.into(),
+            "test_record_1".to_string().into(),
+            "test_record_2".to_string().into(),
+            "test_record_3".to_string().into(),
+            "test_record_4".to_string().into(),
+        ];
+        let expected: Vec<Value> = expected.into_iter().map(Into::into).collect();
+        assert_eq!(layer.values, expected);
+
+        // every buffer has its own `HashMap` seeds
+        let bytes = layer.write_to_bytes().unwrap();
+        for _ in 0..10 {
+            let layer: Layer = buffer_with_four_records(LayerBudget::default()).into();
+            assert_eq!(layer.write_to_bytes().unwrap(), bytes);
+        }
+    }
+
+    fn buffer_with_four_records(budget: LayerBudget) -> BusinessRecordBuffer {
+        let mut buffer = BusinessRecordBuffer::new(TileCoordinates::new(0, 0, 0).unwrap()).with_budget(budget);
+        for (record_id, location, timestamp) in [
+            ("test_record_1", TOKYO, 1_755_317_141),
+            ("test_record_2", PITTSBURGH, 1_755_317_143),
+            ("test_record_3", BUENOS_AIRES, 1_755_317_142),
+            ("test_record_4", CAIRNS, 1_597_562_418),
+        ] {
+            buffer
+                .append_business_record(
+                    BusinessRecordBuilder::default()
+                        .record_id(record_id)
+                        .dog_id(None)
+                        .business_type(BusinessType::Pee)
+                        .location(location)
+                        .timestamp(timestamp)
+                        .build()
+                        .unwrap(),
+                )
+                .unwrap();
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_business_record_buffer_into_layer_within_budget() {
+        let layer: Layer = buffer_with_four_records(LayerBudget {
+            max_features: Some(4),
+            ..Default::default()
+        }).into();
+        assert_eq!(layer.features.len(), 4);
+        assert_eq!(layer.keys, vec!["recordId", "businessType", "timestamp", "dogId"]);
+        assert!(!layer.values.iter().any(|v| v.bool_value.is_some()));
+    }
+
+    #[test]
+    fn test_business_record_buffer_into_layer_over_feature_budget() {
+        let layer: Layer = buffer_with_four_records(LayerBudget {
+            max_features: Some(2),
+            ..Default::default()
+        }).into();
+        // keeps the newest records in the original order with their IDs
+        assert_eq!(layer.features.len(), 2);
+        assert_eq!(layer.features[0].id.unwrap(), 1 << 5);
+        assert_eq!(layer.features[1].id.unwrap(), 2 << 5);
+        assert!(!layer.values.iter().any(expect_string("test_record_1")));
+        assert!(!layer.values.iter().any(expect_string("test_record_4")));
+        // marks the layer as truncated
+        assert_eq!(layer.keys[4], PROPERTY_KEY_TRUNCATED);
+        for feature in layer.features.iter() {
+            let tags = &feature.tags;
+            assert_eq!(tags[tags.len() - 2], 4);
+            assert_eq!(layer.values[tags[tags.len() - 1] as usize].bool_value, Some(true));
+        }
+    }
+
+    #[test]
+    fn test_business_record_buffer_into_layer_over_byte_budget() {
+        let full: Layer = buffer_with_four_records(LayerBudget::default()).into();
+        let full_size = full.compute_size() as usize;
+
+        let layer: Layer = buffer_with_four_records(LayerBudget {
+            max_bytes: Some(full_size - 1),
+            ..Default::default()
+        }).into();
+        assert!(layer.features.len() < 4);
+        assert!((layer.compute_size() as usize) < full_size);
+        assert_eq!(layer.keys.last().unwrap(), PROPERTY_KEY_TRUNCATED);
+
+        // nothing fits
+        let layer: Layer = buffer_with_four_records(LayerBudget {
+            max_bytes: Some(0),
+            ..Default::default()
+        }).into();
+        assert!(layer.features.is_empty());
+    }
+
     /// Returns a closure that expects a `Value` is a static string value.
     #[inline]
     fn expect_string(s: &'static str) -> impl Fn(&Value) -> bool {
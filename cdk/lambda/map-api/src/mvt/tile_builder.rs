@@ -0,0 +1,150 @@
+//! Composition of multiple layers into a vector tile.
+//!
+//! Every layer producer builds its own layer including the keys and values,
+//! and [`TileBuilder`] simply collects the layers into a [`Tile`].
+
+use std::collections::HashSet;
+
+use crate::mvt::MvtError;
+use crate::protos::vector_tile::{Tile, tile::Layer};
+
+/// Producer of layers in a vector tile.
+///
+/// Anything that can be converted into a [`Layer`] produces a single layer.
+pub trait LayerProducer {
+    /// Converts the producer into layers.
+    fn into_layers(self) -> Vec<Layer>;
+}
+
+impl<T> LayerProducer for T
+where
+    T: Into<Layer>,
+{
+    fn into_layers(self) -> Vec<Layer> {
+        vec![self.into()]
+    }
+}
+
+/// Builder of a vector tile that consists of multiple layers.
+///
+/// Layers appear in the tile in the order they are added.
+#[derive(Clone, Debug, Default)]
+pub struct TileBuilder {
+    /// Layers in the tile.
+    layers: Vec<Layer>,
+
+    /// Names of the layers.
+    layer_names: HashSet<String>,
+}
+
+impl TileBuilder {
+    /// Creates a new [`TileBuilder`] without any layers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds layers produced by a given producer.
+    ///
+    /// May return the following error:
+    /// - [`MvtError::DuplicateLayerName`]: if the tile already has a layer
+    ///   with the same name as one of the produced layers. No layers are
+    ///   added in that case.
+    pub fn add_layers(&mut self, producer: impl LayerProducer) -> Result<(), MvtError> {
+        let layers = producer.into_layers();
+        let mut new_names = HashSet::with_capacity(layers.len());
+        for layer in layers.iter() {
+            let name = layer.name();
+            if self.layer_names.contains(name) || !new_names.insert(name) {
+                return Err(MvtError::DuplicateLayerName(name.to_string()));
+            }
+        }
+        self.layer_names.extend(layers.iter().map(|layer| layer.name().to_string()));
+        self.layers.extend(layers);
+        Ok(())
+    }
+
+    /// Returns the number of layers.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Returns if there are no layers.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Returns if no layer has features.
+    pub fn has_no_features(&self) -> bool {
+        self.layers.iter().all(|layer| layer.features.is_empty())
+    }
+
+    /// Builds the tile.
+    pub fn build(self) -> Tile {
+        let mut tile = Tile::new();
+        tile.layers = self.layers;
+        tile
+    }
+}
+
+impl From<TileBuilder> for Tile {
+    fn from(builder: TileBuilder) -> Self {
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mvt::VECTOR_TILE_VERSION;
+
+    fn layer(name: &str) -> Layer {
+        let mut layer = Layer::new();
+        layer.set_version(VECTOR_TILE_VERSION);
+        layer.set_name(name.to_string());
+        layer
+    }
+
+    /// Produces two layers.
+    struct PairOfLayers(&'static str, &'static str);
+
+    impl LayerProducer for PairOfLayers {
+        fn into_layers(self) -> Vec<Layer> {
+            vec![layer(self.0), layer(self.1)]
+        }
+    }
+
+    #[test]
+    fn test_tile_builder_add_layers() {
+        let mut builder = TileBuilder::new();
+        assert!(builder.is_empty());
+        assert!(builder.has_no_features());
+
+        builder.add_layers(layer("first")).unwrap();
+        builder.add_layers(PairOfLayers("second", "third")).unwrap();
+        assert_eq!(builder.len(), 3);
+        assert!(builder.has_no_features());
+
+        let tile = builder.build();
+        let names: Vec<&str> = tile.layers.iter().map(|layer| layer.name()).collect();
+        assert_eq!(names, vec!["first", "second", "third"]);
+    }
+
+    #[test]
+    fn test_tile_builder_add_layers_duplicate_name() {
+        let mut builder = TileBuilder::new();
+        builder.add_layers(layer("first")).unwrap();
+        assert!(matches!(
+            builder.add_layers(PairOfLayers("second", "first")),
+            Err(MvtError::DuplicateLayerName(name)) if name == "first",
+        ));
+        assert!(matches!(
+            builder.add_layers(PairOfLayers("second", "second")),
+            Err(MvtError::DuplicateLayerName(name)) if name == "second",
+        ));
+        // no layers are added on errors
+        assert_eq!(builder.len(), 1);
+    }
+}
@@ -3,11 +3,24 @@
 // includes the code generated by protobuf_codegen
 include!(concat!(env!("OUT_DIR"), "/protos/mod.rs"));
 
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
 /// Enum variants for [`Value`][vector_tile::tile::Value].
 ///
 /// Implements `Hash` so that different value types can be used as keys in a
-/// `HashMap`.
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+/// `HashMap`. `F64` values are compared by their bit patterns; i.e., `NaN`
+/// equals `NaN` and `0.0` does not equal `-0.0`.
+///
+/// Implements a total order so that values can be sorted deterministically.
+/// Values of different types are ordered by the type in the order of the
+/// variants; i.e., strings come first, and booleans come last. `F64` values
+/// are ordered by [`f64::total_cmp`].
+///
+/// Serialized as a bare JSON value.
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
 pub enum PropertyValue {
     /// String value corresponding to
     /// [`Value::string_value`][vector::tile::tile::Value::string_value].
@@ -15,15 +28,39 @@ pub enum PropertyValue {
     /// `i64` value corresponding to
     /// [`Value::int_value`][vector::tile::tile::Value::int_value].
     I64(i64),
+    /// `u64` value corresponding to
+    /// [`Value::uint_value`][vector::tile::tile::Value::uint_value].
+    ///
+    /// There is no `From<u64>` so that integer literals are inferred as
+    /// `i64`.
+    U64(u64),
+    /// `f64` value corresponding to
+    /// [`Value::double_value`][vector::tile::tile::Value::double_value].
+    F64(f64),
+    /// Boolean value corresponding to
+    /// [`Value::bool_value`][vector::tile::tile::Value::bool_value].
+    Bool(bool),
 }
 
 impl PropertyValue {
+    /// Returns the rank of the type to order values of different types.
+    #[inline]
+    const fn type_rank(&self) -> u8 {
+        match self {
+            PropertyValue::String(_) => 0,
+            PropertyValue::I64(_) => 1,
+            PropertyValue::U64(_) => 2,
+            PropertyValue::F64(_) => 3,
+            PropertyValue::Bool(_) => 4,
+        }
+    }
+
     /// Returns the string value if it is a string, otherwise `None`.
     #[inline]
     pub fn get_string(&self) -> Option<&String> {
         match self {
             PropertyValue::String(s) => Some(s),
-            PropertyValue::I64(_) => None,
+            _ => None,
         }
     }
 
@@ -31,8 +68,82 @@ impl PropertyValue {
     #[inline]
     pub fn get_i64(&self) -> Option<i64> {
         match self {
-            PropertyValue::String(_) => None,
             PropertyValue::I64(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Returns the `u64` value if it is a `u64`, otherwise `None`.
+    #[inline]
+    pub fn get_u64(&self) -> Option<u64> {
+        match self {
+            PropertyValue::U64(u) => Some(*u),
+            _ => None,
+        }
+    }
+
+    /// Returns the `f64` value if it is an `f64`, otherwise `None`.
+    #[inline]
+    pub fn get_f64(&self) -> Option<f64> {
+        match self {
+            PropertyValue::F64(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// Returns the boolean value if it is a boolean, otherwise `None`.
+    #[inline]
+    pub fn get_bool(&self) -> Option<bool> {
+        match self {
+            PropertyValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for PropertyValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (PropertyValue::String(a), PropertyValue::String(b)) => a == b,
+            (PropertyValue::I64(a), PropertyValue::I64(b)) => a == b,
+            (PropertyValue::U64(a), PropertyValue::U64(b)) => a == b,
+            (PropertyValue::F64(a), PropertyValue::F64(b)) => a.to_bits() == b.to_bits(),
+            (PropertyValue::Bool(a), PropertyValue::Bool(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for PropertyValue {}
+
+impl PartialOrd for PropertyValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PropertyValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (PropertyValue::String(a), PropertyValue::String(b)) => a.cmp(b),
+            (PropertyValue::I64(a), PropertyValue::I64(b)) => a.cmp(b),
+            (PropertyValue::U64(a), PropertyValue::U64(b)) => a.cmp(b),
+            (PropertyValue::F64(a), PropertyValue::F64(b)) => a.total_cmp(b),
+            (PropertyValue::Bool(a), PropertyValue::Bool(b)) => a.cmp(b),
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
+}
+
+impl Hash for PropertyValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            PropertyValue::String(s) => s.hash(state),
+            PropertyValue::I64(i) => i.hash(state),
+            PropertyValue::U64(u) => u.hash(state),
+            PropertyValue::F64(f) => f.to_bits().hash(state),
+            PropertyValue::Bool(b) => b.hash(state),
         }
     }
 }
@@ -49,6 +160,18 @@ impl From<i64> for PropertyValue {
     }
 }
 
+impl From<f64> for PropertyValue {
+    fn from(value: f64) -> Self {
+        PropertyValue::F64(value)
+    }
+}
+
+impl From<bool> for PropertyValue {
+    fn from(value: bool) -> Self {
+        PropertyValue::Bool(value)
+    }
+}
+
 impl From<PropertyValue> for vector_tile::tile::Value {
     fn from(value: PropertyValue) -> Self {
         match value {
@@ -62,6 +185,21 @@ impl From<PropertyValue> for vector_tile::tile::Value {
                 v.set_int_value(i);
                 v
             }
+            PropertyValue::U64(u) => {
+                let mut v = Self::new();
+                v.set_uint_value(u);
+                v
+            }
+            PropertyValue::F64(f) => {
+                let mut v = Self::new();
+                v.set_double_value(f);
+                v
+            }
+            PropertyValue::Bool(b) => {
+                let mut v = Self::new();
+                v.set_bool_value(b);
+                v
+            }
         }
     }
 }
@@ -185,5 +323,64 @@ mod tests {
         assert!(value.uint_value.is_none());
         assert!(value.sint_value.is_none());
         assert!(value.bool_value.is_none());
+
+        let value: Value = PropertyValue::U64(u64::MAX).into();
+        assert_eq!(value.uint_value.unwrap(), u64::MAX);
+        assert!(value.int_value.is_none());
+
+        let value: Value = PropertyValue::from(0.5).into();
+        assert_eq!(value.double_value.unwrap(), 0.5);
+        assert!(value.float_value.is_none());
+
+        let value: Value = PropertyValue::from(true).into();
+        assert!(value.bool_value.unwrap());
+        assert!(value.int_value.is_none());
+    }
+
+    #[test]
+    fn test_property_value_ord() {
+        let mut values: Vec<PropertyValue> = vec![
+            true.into(),
+            1.5.into(),
+            (-1.0).into(),
+            PropertyValue::U64(2),
+            2.into(),
+            (-3).into(),
+            "poo".to_string().into(),
+            "pee".to_string().into(),
+            false.into(),
+        ];
+        values.sort();
+        assert_eq!(values, vec![
+            "pee".to_string().into(),
+            "poo".to_string().into(),
+            (-3).into(),
+            2.into(),
+            PropertyValue::U64(2),
+            (-1.0).into(),
+            1.5.into(),
+            false.into(),
+            true.into(),
+        ]);
+    }
+
+    #[test]
+    fn test_property_value_other_types_eq_and_hash() {
+        let mut map = std::collections::HashMap::<PropertyValue, String>::new();
+        map.insert(PropertyValue::U64(1), "u1".to_string());
+        map.insert(1.0.into(), "f1".to_string());
+        map.insert(f64::NAN.into(), "nan".to_string());
+        map.insert(true.into(), "true".to_string());
+        assert_eq!(map.get(&PropertyValue::U64(1)).unwrap(), "u1");
+        assert_eq!(map.get(&1.0.into()).unwrap(), "f1");
+        assert_eq!(map.get(&f64::NAN.into()).unwrap(), "nan");
+        assert_eq!(map.get(&true.into()).unwrap(), "true");
+        assert!(!map.contains_key(&1.into()));
+        assert!(!map.contains_key(&false.into()));
+
+        assert_eq!(PropertyValue::U64(1).get_u64(), Some(1));
+        assert_eq!(PropertyValue::from(1.0).get_f64(), Some(1.0));
+        assert_eq!(PropertyValue::from(true).get_bool(), Some(true));
+        assert!(PropertyValue::from(1).get_u64().is_none());
     }
 }
@@ -0,0 +1,386 @@
+//! Planning and execution of queries for business records in a map tile.
+//!
+//! Business records are indexed only at specific zoom levels. A tile at a
+//! zoom level that is not indexed is covered either by:
+//! - a single tile at a coarser indexed zoom level, which may contain a lot of
+//!   records outside of the requested tile
+//! - multiple tiles at a finer indexed zoom level, all of which are inside of
+//!   the requested tile
+//!
+//! [`plan_tile_query`] chooses one of them, and [`collect_records_in_tile`]
+//! collects the records inside of the requested tile from the queries.
+//!
+//! Both take a buffer around the requested tile into account so that records
+//! near the edges of the tile are also collected. The buffer is in extent
+//! units; i.e., [`TILE_EXTENT`] is the edge length of the tile.
+//!
+//! Each planned tile is given a share of the records proportional to its
+//! overlap with the requested tile and buffer, so that neighboring tiles that
+//! only overlap the thin buffer do not cost as much as the tile itself.
+
+use futures::{
+    future,
+    stream::{Stream, StreamExt as _, TryStreamExt as _},
+};
+
+use business_core::{
+    mvt::TileCoordinates,
+    tables::TableError,
+    types::BusinessRecord,
+};
+
+use crate::mvt::TILE_EXTENT;
+
+/// Query of business records in a tile.
+#[derive(Clone, Debug)]
+pub struct TileQuery {
+    /// Coordinates of the tile to query.
+    pub coordinates: TileCoordinates,
+
+    /// Share of the records assigned to the query.
+    ///
+    /// Ratio of the area of the tile overlapping the requested tile and
+    /// buffer to the whole overlapping area of all the planned tiles.
+    /// Shares of all the planned tiles sum up to 1.0.
+    pub share: f64,
+}
+
+impl TileQuery {
+    /// Splits a given number of records for the query.
+    ///
+    /// Rounds up, and returns at least 1.
+    pub fn split(&self, num_records: usize) -> usize {
+        ((num_records as f64 * self.share).ceil() as usize).max(1)
+    }
+}
+
+/// Plans the tiles to query for business records in a tile at given
+/// coordinates.
+///
+/// `indexed_zoom_levels` must be sorted in ascending order and include the
+/// zoom level 0.
+///
+/// Returns queries of the tiles that overlap the tile and `buffer` at:
+/// - the zoom level of the tile if it is indexed
+/// - the next finer indexed zoom level, if the tiles are no more than
+///   `max_fan_out`
+/// - the largest indexed zoom level below the zoom level of the tile,
+///   otherwise
+///
+/// Panics if `indexed_zoom_levels` does not include the zoom level 0.
+pub fn plan_tile_query(
+    coordinates: &TileCoordinates,
+    indexed_zoom_levels: &[u32],
+    max_fan_out: usize,
+    buffer: u32,
+) -> Vec<TileQuery> {
+    let zoom = coordinates.zoom();
+    let margin = margin_from_buffer(buffer);
+    let finer_index = indexed_zoom_levels.partition_point(|&z| z <= zoom);
+    let coarser_zoom = indexed_zoom_levels[finer_index
+        .checked_sub(1)
+        .expect("zoom level 0 must be indexed")];
+    let mut tiles = None;
+    if coarser_zoom != zoom {
+        if let Some(&finer_zoom) = indexed_zoom_levels.get(finer_index) {
+            tiles = Some(coordinates.overlapping_tiles_at(finer_zoom, margin))
+                .filter(|tiles| tiles.len() <= max_fan_out);
+        }
+    }
+    let tiles = tiles.unwrap_or_else(|| coordinates.overlapping_tiles_at(coarser_zoom, margin));
+
+    // shares records in proportion to the overlapping areas
+    let areas: Vec<f64> = tiles
+        .iter()
+        .map(|tile| overlapping_area(coordinates, margin, tile))
+        .collect();
+    let total_area: f64 = areas.iter().sum();
+    tiles
+        .into_iter()
+        .zip(areas)
+        .map(|(coordinates, area)| TileQuery {
+            coordinates,
+            share: area / total_area,
+        })
+        .collect()
+}
+
+/// Calculates the area of a given tile overlapping a tile at given
+/// coordinates expanded by `margin`.
+///
+/// The area is in the square of the edge length of the tile at `coordinates`.
+fn overlapping_area(coordinates: &TileCoordinates, margin: f64, tile: &TileCoordinates) -> f64 {
+    // edge length of `tile` relative to the tile at `coordinates`
+    let size = 2.0_f64.powi(coordinates.zoom() as i32 - tile.zoom() as i32);
+    let overlap = |start: u32, other: u32| {
+        let start = start as f64 - margin;
+        let end = start + 1.0 + 2.0 * margin;
+        let other_start = other as f64 * size;
+        let other_end = other_start + size;
+        (end.min(other_end) - start.max(other_start)).max(0.0)
+    };
+    overlap(coordinates.x(), tile.x()) * overlap(coordinates.y(), tile.y())
+}
+
+/// Collects business records inside of a tile at given coordinates from
+/// queries.
+///
+/// `queries` are pairs of a query planned by [`plan_tile_query`] and a stream
+/// of business records in the tile. Each stream must yield records newest
+/// first.
+///
+/// `max_records` and `max_scanned_records` are split among the queries by
+/// [`TileQuery::split`]. Keeps pulling each stream until it yields its share of
+/// `max_records` records inside of the tile and `buffer`, or it has yielded
+/// its share of `max_scanned_records` records in total. Records outside of the
+/// tile and `buffer` do not consume `max_records`.
+///
+/// Returns at most `max_records` records ordered newest first. Records with
+/// the same timestamp are ordered by record ID, so that the order does not
+/// depend on the order of queries.
+pub async fn collect_records_in_tile<S>(
+    coordinates: &TileCoordinates,
+    queries: Vec<(&TileQuery, S)>,
+    max_records: usize,
+    max_scanned_records: usize,
+    buffer: u32,
+) -> Result<Vec<BusinessRecord>, TableError>
+where
+    S: Stream<Item = Result<BusinessRecord, TableError>>,
+{
+    let margin = margin_from_buffer(buffer);
+    let results = future::try_join_all(queries.into_iter().map(|(plan, query)| {
+        query
+            .take(plan.split(max_scanned_records))
+            .try_filter(|record| future::ready(
+                coordinates.contains_location_with_margin(&record.location, margin),
+            ))
+            .take(plan.split(max_records))
+            .try_collect::<Vec<_>>()
+    })).await?;
+    // merges results of queries
+    let mut records: Vec<BusinessRecord> = results.into_iter().flatten().collect();
+    records.sort_by(|a, b| b.timestamp
+        .cmp(&a.timestamp)
+        .then_with(|| a.record_id.cmp(&b.record_id)));
+    records.truncate(max_records);
+    Ok(records)
+}
+
+/// Converts a buffer in extent units into the ratio to the edge length of a
+/// tile.
+#[inline]
+fn margin_from_buffer(buffer: u32) -> f64 {
+    buffer as f64 / TILE_EXTENT as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{executor::block_on, stream};
+
+    use business_core::types::{BusinessRecordBuilder, BusinessType, GeolocationCoordinates};
+
+    const INDEXED_ZOOM_LEVELS: [u32; 8] = [0, 3, 6, 10, 15, 16, 17, 18];
+
+    fn make_record(record_id: &str, longitude: f64, timestamp: i64) -> BusinessRecord {
+        BusinessRecordBuilder::default()
+            .record_id(record_id)
+            .dog_id(None)
+            .business_type(BusinessType::Poo)
+            .location(GeolocationCoordinates {
+                longitude,
+                latitude: 35.6814709332,
+            })
+            .timestamp(timestamp)
+            .build()
+            .unwrap()
+    }
+
+    fn tile_xyz(query: &TileQuery) -> (u32, u32, u32) {
+        (query.coordinates.zoom(), query.coordinates.x(), query.coordinates.y())
+    }
+
+    fn whole_tile_query(coordinates: &TileCoordinates) -> TileQuery {
+        TileQuery {
+            coordinates: coordinates.clone(),
+            share: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_plan_tile_query_at_indexed_zoom() {
+        let coords = TileCoordinates::new(16, 58138, 25860).unwrap();
+        let tiles = plan_tile_query(&coords, &INDEXED_ZOOM_LEVELS, 4, 0);
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tile_xyz(&tiles[0]), (16, 58138, 25860));
+    }
+
+    #[test]
+    fn test_plan_tile_query_fans_out_to_finer_zoom() {
+        let coords = TileCoordinates::new(5, 28, 12).unwrap();
+        let tiles = plan_tile_query(&coords, &INDEXED_ZOOM_LEVELS, 4, 0);
+        assert_eq!(tiles.len(), 4);
+        assert!(tiles.iter().all(|t| t.coordinates.zoom() == 6));
+        assert!(tiles.iter().all(|t| t.coordinates.zoom_out_to(5).unwrap().x() == 28));
+        assert!(tiles.iter().all(|t| t.coordinates.zoom_out_to(5).unwrap().y() == 12));
+    }
+
+    #[test]
+    fn test_plan_tile_query_zooms_out_if_fan_out_is_too_large() {
+        // z = 4 → 6 requires 16 tiles
+        let coords = TileCoordinates::new(4, 14, 6).unwrap();
+        let tiles = plan_tile_query(&coords, &INDEXED_ZOOM_LEVELS, 4, 0);
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tile_xyz(&tiles[0]), (3, 7, 3));
+
+        // no finer zoom level is indexed
+        let coords = TileCoordinates::new(20, 930_211, 413_763).unwrap();
+        let tiles = plan_tile_query(&coords, &INDEXED_ZOOM_LEVELS, 4, 0);
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tile_xyz(&tiles[0]), (18, 232_552, 103_440));
+    }
+
+    #[test]
+    fn test_plan_tile_query_includes_neighbors_in_buffer() {
+        // buffer covers the neighbors at the same zoom level
+        let coords = TileCoordinates::new(16, 58138, 25860).unwrap();
+        let tiles = plan_tile_query(&coords, &INDEXED_ZOOM_LEVELS, 4, 64);
+        assert_eq!(tiles.len(), 9);
+        assert!(tiles.iter().all(|t| t.coordinates.zoom() == 16));
+
+        // 16 tiles at the finer zoom level are too many
+        let coords = TileCoordinates::new(5, 29, 13).unwrap();
+        let tiles = plan_tile_query(&coords, &INDEXED_ZOOM_LEVELS, 4, 64);
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tile_xyz(&tiles[0]), (3, 7, 3));
+        let tiles = plan_tile_query(&coords, &INDEXED_ZOOM_LEVELS, 16, 64);
+        assert_eq!(tiles.len(), 16);
+        assert!(tiles.iter().all(|t| t.coordinates.zoom() == 6));
+    }
+
+    #[test]
+    fn test_plan_tile_query_shares_records_by_overlapping_area() {
+        // the tile itself dominates, and neighbors only overlap the buffer
+        let coords = TileCoordinates::new(16, 58138, 25860).unwrap();
+        let tiles = plan_tile_query(&coords, &INDEXED_ZOOM_LEVELS, 4, 64);
+        assert_eq!(tiles.len(), 9);
+        let total: f64 = tiles.iter().map(|t| t.share).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        let center = tiles.iter().find(|t| tile_xyz(t) == (16, 58138, 25860)).unwrap();
+        assert!(center.share > 0.9);
+        assert_eq!(center.split(2_000), 1_881);
+        let edge = tiles.iter().find(|t| tile_xyz(t) == (16, 58139, 25860)).unwrap();
+        assert!(edge.share < 0.02);
+        assert_eq!(edge.split(2_000), 30);
+        let corner = tiles.iter().find(|t| tile_xyz(t) == (16, 58139, 25861)).unwrap();
+        assert_eq!(corner.split(2_000), 1);
+        let split_total: usize = tiles.iter().map(|t| t.split(2_000)).sum();
+        assert!(split_total < 2_000 + tiles.len());
+
+        // finer tiles inside of the tile share equally
+        let coords = TileCoordinates::new(5, 28, 12).unwrap();
+        let tiles = plan_tile_query(&coords, &INDEXED_ZOOM_LEVELS, 4, 0);
+        assert!(tiles.iter().all(|t| (t.share - 0.25).abs() < 1e-9));
+
+        // a coarser tile covers the buffer alone
+        let coords = TileCoordinates::new(5, 29, 13).unwrap();
+        let tiles = plan_tile_query(&coords, &INDEXED_ZOOM_LEVELS, 4, 64);
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tiles[0].share, 1.0);
+    }
+
+    #[test]
+    fn test_collect_records_in_tile_splits_max_records() {
+        let coords = TileCoordinates::new(10, 909, 403).unwrap();
+        let inside = 139.7670506677;
+        let half = TileQuery {
+            coordinates: coords.clone(),
+            share: 0.5,
+        };
+        let query_1 = stream::iter(vec![
+            Ok(make_record("record_1", inside, 6)),
+            Ok(make_record("record_2", inside, 5)),
+            Ok(make_record("record_3", inside, 4)),
+        ]);
+        let query_2 = stream::iter(vec![
+            Ok(make_record("record_4", inside, 3)),
+            Ok(make_record("record_5", inside, 2)),
+        ]);
+        let records = block_on(collect_records_in_tile(
+            &coords,
+            vec![(&half, query_1), (&half, query_2)],
+            4,
+            100,
+            0,
+        )).unwrap();
+        let record_ids: Vec<_> = records.iter().map(|r| r.record_id.as_str()).collect();
+        assert_eq!(record_ids, vec!["record_1", "record_2", "record_4", "record_5"]);
+    }
+
+    #[test]
+    fn test_collect_records_in_tile_includes_records_in_buffer() {
+        // Tokyo Station is near the left edge of the tile at zoom level 16
+        let coords = TileCoordinates::new(16, 58212, 25806).unwrap();
+        let plan = whole_tile_query(&coords);
+        let query = stream::iter(vec![Ok(make_record("record_1", 139.7670506677, 1))]);
+        let records = block_on(collect_records_in_tile(&coords, vec![(&plan, query)], 2, 100, 0)).unwrap();
+        assert!(records.is_empty());
+        let query = stream::iter(vec![Ok(make_record("record_1", 139.7670506677, 1))]);
+        let records = block_on(collect_records_in_tile(&coords, vec![(&plan, query)], 2, 100, 1024)).unwrap();
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn test_collect_records_in_tile_skips_records_outside_of_tile() {
+        // tile at zoom level 10 that contains Tokyo Station
+        let coords = TileCoordinates::new(10, 909, 403).unwrap();
+        let plan = whole_tile_query(&coords);
+        let inside = 139.7670506677;
+        let outside = 140.5;
+        let query = stream::iter(vec![
+            Ok(make_record("record_1", outside, 5)),
+            Ok(make_record("record_2", inside, 4)),
+            Ok(make_record("record_3", outside, 3)),
+            Ok(make_record("record_4", inside, 2)),
+            Ok(make_record("record_5", inside, 1)),
+        ]);
+        let records = block_on(collect_records_in_tile(&coords, vec![(&plan, query)], 2, 100, 0)).unwrap();
+        let record_ids: Vec<_> = records.iter().map(|r| r.record_id.as_str()).collect();
+        assert_eq!(record_ids, vec!["record_2", "record_4"]);
+    }
+
+    #[test]
+    fn test_collect_records_in_tile_stops_at_max_scanned_records() {
+        let coords = TileCoordinates::new(10, 909, 403).unwrap();
+        let plan = whole_tile_query(&coords);
+        let inside = 139.7670506677;
+        let outside = 140.5;
+        let query = stream::iter(vec![
+            Ok(make_record("record_1", outside, 3)),
+            Ok(make_record("record_2", outside, 2)),
+            Ok(make_record("record_3", inside, 1)),
+        ]);
+        let records = block_on(collect_records_in_tile(&coords, vec![(&plan, query)], 2, 2, 0)).unwrap();
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn test_collect_records_in_tile_merges_queries_newest_first() {
+        let coords = TileCoordinates::new(10, 909, 403).unwrap();
+        let plan = whole_tile_query(&coords);
+        let inside = 139.7670506677;
+        let query_1 = stream::iter(vec![
+            Ok(make_record("record_1", inside, 6)),
+            Ok(make_record("record_2", inside, 3)),
+        ]);
+        let query_2 = stream::iter(vec![
+            Ok(make_record("record_3", inside, 5)),
+            Ok(make_record("record_4", inside, 4)),
+        ]);
+        let records = block_on(collect_records_in_tile(&coords, vec![(&plan, query_1), (&plan, query_2)], 3, 100, 0)).unwrap();
+        let record_ids: Vec<_> = records.iter().map(|r| r.record_id.as_str()).collect();
+        assert_eq!(record_ids, vec!["record_1", "record_3", "record_4"]);
+    }
+}
@@ -46,7 +46,21 @@ pub fn y_from_latitude_at_zoom(latitude: f64, z: u32) -> f64 {
 /// Panics if `z` is greater than [`MAX_ZOOM`].
 #[inline]
 pub fn longitude_from_x_at_zoom(x: u32, z: u32) -> f64 {
-    let λ = (x as f64) * two_pi_div_tiles_per_edge_at_zoom(z) - PI;
+    longitude_from_fractional_x_at_zoom(x as f64, z)
+}
+
+/// Calculates longitude from fractional x coordinate at given zoom level.
+///
+/// Use this function to calculate longitude of a point in a tile.
+///
+/// Formula: `λ = x*2π/2ᶻ - π`
+///
+/// Returns `λ` in degrees.
+///
+/// Panics if `z` is greater than [`MAX_ZOOM`].
+#[inline]
+pub fn longitude_from_fractional_x_at_zoom(x: f64, z: u32) -> f64 {
+    let λ = x * two_pi_div_tiles_per_edge_at_zoom(z) - PI;
     λ.to_degrees()
 }
 
@@ -59,7 +73,21 @@ pub fn longitude_from_x_at_zoom(x: u32, z: u32) -> f64 {
 /// Panics if `z` is greater than [`MAX_ZOOM`].
 #[inline]
 pub fn latitude_from_y_at_zoom(y: u32, z: u32) -> f64 {
-    let exp = (PI - (y as f64) * two_pi_div_tiles_per_edge_at_zoom(z)).exp();
+    latitude_from_fractional_y_at_zoom(y as f64, z)
+}
+
+/// Calculates latitude from fractional y coordinate at given zoom level.
+///
+/// Use this function to calculate latitude of a point in a tile.
+///
+/// Formula: `φ = 2*atan(e^(π - y*2π/2ᶻ)) - π/2`
+///
+/// Returns `φ` in degrees.
+///
+/// Panics if `z` is greater than [`MAX_ZOOM`].
+#[inline]
+pub fn latitude_from_fractional_y_at_zoom(y: f64, z: u32) -> f64 {
+    let exp = (PI - y * two_pi_div_tiles_per_edge_at_zoom(z)).exp();
     let φ = 2.0 * exp.atan() - 0.5 * PI;
     φ.to_degrees()
 }
@@ -98,7 +126,7 @@ pub const fn two_pi_div_tiles_per_edge_at_zoom(z: u32) -> f64 {
 /// digits in `f64`.
 #[inline]
 pub const fn power_of_two(n: u32) -> f64 {
-    assert!(n + 1 < std::f64::MANTISSA_DIGITS);
+    assert!(n + 1 < f64::MANTISSA_DIGITS);
     (1u64 << n) as f64
 }
 
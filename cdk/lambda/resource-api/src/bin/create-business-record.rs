@@ -1,5 +1,9 @@
 //! Creates a business record.
 //!
+//! The public business record is created according to the advocacy setting of
+//! the dog, and is not created at all if the dog does not publish business
+//! records.
+//!
 //! ## Environment variables
 //!
 //! You have to configure the following environment variables:
@@ -7,8 +11,10 @@
 //!   information from
 //! - `BUSINESS_RECORD_TABLE_NAME`: name of the business record table to put a
 //!   new business record
+//! - `TILE_CACHE_BUCKET_NAME`: name of the S3 bucket that caches rendered map
+//!   tiles. map tiles affected by a new public business record are
+//!   invalidated.
 
-use aws_sdk_dynamodb::types::{AttributeValue, ReturnConsumedCapacity};
 use base64::{
     engine::general_purpose::URL_SAFE_NO_PAD as base64_encoder,
     Engine as _,
@@ -19,21 +25,36 @@ use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
-use business_core::types::BusinessType;
-use business_core::web_mercator::{
-    normalized_x_from_longitude,
-    normalized_y_from_latitude,
-    tiles_per_edge_at_zoom,
+use business_core::tables::{
+    BusinessRecordTable,
+    BusinessRecordTableBuilder,
+    NewBusinessRecordBuilder,
+    RecordWrite,
+    ResourceTable,
 };
+use business_core::tile_cache::{
+    DEFAULT_KEY_PREFIX,
+    INVALIDATION_MARGIN,
+    S3TileCache,
+    TileCache as _,
+    tiles_affected_by,
+};
+use business_core::types::BusinessType;
+
+/// Maximum number of attempts to create a business record.
+///
+/// Another attempt is made with the latest advocacy setting if the advocacy
+/// setting of the dog changes during an attempt.
+const MAX_ATTEMPTS: usize = 3;
 
 /// Shared state.
 struct SharedState {
-    /// DynamoDB client.
-    dynamodb_client: aws_sdk_dynamodb::Client,
-    /// Name of the resource table.
-    resource_table_name: String,
-    /// Name of the business record table.
-    business_record_table_name: String,
+    /// Resource table.
+    resource_table: ResourceTable,
+    /// Business record table.
+    business_record_table: BusinessRecordTable,
+    /// Cache of rendered map tiles.
+    tile_cache: S3TileCache,
 }
 
 impl SharedState {
@@ -44,14 +65,27 @@ impl SharedState {
         let business_record_table_name = std::env::var("BUSINESS_RECORD_TABLE_NAME")
             .map_err(|_| "BUSINESS_RECORD_TABLE_NAME env is not set")?;
 
-        // caches the DynamoDB client
+        let tile_cache_bucket_name = std::env::var("TILE_CACHE_BUCKET_NAME")
+            .map_err(|_| "TILE_CACHE_BUCKET_NAME env is not set")?;
+
+        // caches the tables and tile cache
         let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
         let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
+        let resource_table = ResourceTable::new(dynamodb_client.clone(), resource_table_name);
+        let business_record_table = BusinessRecordTableBuilder::default()
+            .client(dynamodb_client.clone())
+            .table_name(business_record_table_name)
+            .build()?;
+        let tile_cache = S3TileCache::new(
+            aws_sdk_s3::Client::new(&config),
+            tile_cache_bucket_name,
+            DEFAULT_KEY_PREFIX,
+        );
 
         Ok(Self {
-            dynamodb_client,
-            resource_table_name,
-            business_record_table_name,
+            resource_table,
+            business_record_table,
+            tile_cache,
         })
     }
 }
@@ -119,101 +153,68 @@ async fn function_handler(
     // makes sure that the user is a friend of the dog
     tracing::info!("checking if user {user_id} is a friend of dog {dog_id}");
     let relationship = shared_state
-        .dynamodb_client
-        .get_item()
-        .table_name(&shared_state.resource_table_name)
-        .key("pk", AttributeValue::S(format!("friend-of#{user_id}")))
-        .key("sk", AttributeValue::S(format!("dog#{dog_id}")))
-        .return_consumed_capacity(ReturnConsumedCapacity::Total)
-        .send()
+        .resource_table
+        .get_user_dog_relationship(&user_id, &dog_id)
         .await?;
-    tracing::info!("consumed capacity: {}", relationship.consumed_capacity.and_then(|c| c.capacity_units).unwrap_or(f64::NAN));
-    // TODO: deal with 429 errors
-    // - ProvisionedThroughputExceededException
-    // - RequestLimitExceeded
-    // - ThrottlingException
-    if relationship.item.is_none() {
+    if relationship.is_none() {
         return Err("user must be a friend of the dog".into());
     }
 
-    // calculates tile coordinates of all the zoom levels
-    let normalized_x = normalized_x_from_longitude(longitude);
-    let all_x: Vec<u32> = (0..=22)
-        .map(|z| (tiles_per_edge_at_zoom(z) * normalized_x).floor() as u32)
-        .collect();
-    let normalized_y = normalized_y_from_latitude(latitude);
-    let all_y: Vec<u32> = (0..=22)
-        .map(|z| (tiles_per_edge_at_zoom(z) * normalized_y).floor() as u32)
-        .collect();
-    let all_tile_coords: Vec<String> = all_x
-        .into_iter()
-        .zip(all_y.into_iter())
-        .map(|(x, y)| format!("{x}/{y}"))
-        .collect();
-
     // randomly generates a new record ID and encodes it in URL-safe Base64
     let record_id = Uuid::new_v4();
-    let record_id = base64_encoder.encode(&record_id);
-
-    // creates a private business record
-    tracing::info!("putting private business record");
-    let request = shared_state
-        .dynamodb_client
-        .put_item()
-        .table_name(&shared_state.business_record_table_name)
-        .item("pk", AttributeValue::S(record_id.clone()))
-        .item("sk", AttributeValue::S("private".to_string()))
-        .item("dogId", AttributeValue::S(dog_id.clone()))
-        .item("businessType", AttributeValue::S(business_type.to_string()))
-        .item("longitude", AttributeValue::N(format_geo_coordinate(longitude)))
-        .item("latitude", AttributeValue::N(format_geo_coordinate(latitude)))
-        .item("timestamp", AttributeValue::N(timestamp.to_string()));
-    let request = all_tile_coords
-        .iter()
-        .enumerate()
-        .fold(request, |request, (z, tile_coord)| {
-            request.item(
-                format!("tileAtZ{z}"),
-                AttributeValue::S(format!("dog#{dog_id}#{tile_coord}")),
-            )
-        });
-    let res = request
-        .return_consumed_capacity(ReturnConsumedCapacity::Total)
-        .send()
-        .await?;
-    tracing::info!("consumed capacity: {}", res.consumed_capacity.and_then(|c| c.capacity_units).unwrap_or(f64::NAN));
-
-    // leaves the latter half of the dog ID as the masked (semi-unique) dog ID
-    let (_, masked_dog_id) = dog_id.split_at(dog_id.len() / 2);
-
-    // creates a public business record
-    tracing::info!("putting public business record");
-    let request = shared_state
-        .dynamodb_client
-        .put_item()
-        .table_name(&shared_state.business_record_table_name)
-        .item("pk", AttributeValue::S(record_id.clone()))
-        .item("sk", AttributeValue::S("public".to_string()))
-        .item("maskedDogId", AttributeValue::S(masked_dog_id.to_string()))
-        .item("isAdvocated", AttributeValue::Bool(true)) // TODO: use dog's advocacy setting
-        .item("businessType", AttributeValue::S(business_type.to_string()))
-        .item("longitude", AttributeValue::N(format_geo_coordinate(longitude)))
-        .item("latitude", AttributeValue::N(format_geo_coordinate(latitude)))
-        .item("timestamp", AttributeValue::N((timestamp / 3600).to_string()));
-    let request = all_tile_coords
-        .iter()
-        .enumerate()
-        .fold(request, |request, (z, tile_coord)| {
-            request.item(
-                format!("tileAtZ{z}"),
-                AttributeValue::S(format!("public#{tile_coord}")),
-            )
-        });
-    let res = request
-        .return_consumed_capacity(ReturnConsumedCapacity::Total)
-        .send()
-        .await?;
-    tracing::info!("consumed capacity: {}", res.consumed_capacity.and_then(|c| c.capacity_units).unwrap_or(f64::NAN));
+    let record_id = base64_encoder.encode(record_id);
+
+    let location = business_core::types::GeolocationCoordinates {
+        longitude,
+        latitude,
+    };
+
+    // creates the private and public business records at once
+    // the public record is omitted if the dog does not publish records
+    // retries with the latest advocacy setting if it changes meanwhile
+    let mut attempts = 0;
+    let advocacy_setting = loop {
+        attempts += 1;
+        tracing::info!("getting advocacy setting of dog {dog_id}");
+        let advocacy_setting = shared_state
+            .resource_table
+            .get_dog_advocacy_setting(&dog_id)
+            .await?
+            .ok_or("no dog item")?;
+        tracing::info!("creating business record {record_id} ({advocacy_setting})");
+        let new_record = NewBusinessRecordBuilder::default()
+            .record_id(record_id.clone())
+            .dog_id(dog_id.clone())
+            .business_type(business_type.clone())
+            .location(location.clone())
+            .timestamp(timestamp as i64)
+            .advocacy_setting(advocacy_setting)
+            .build()?;
+        match shared_state
+            .business_record_table
+            .create_record(&new_record, &shared_state.resource_table)
+            .await?
+        {
+            RecordWrite::Written => break advocacy_setting,
+            RecordWrite::SettingChanged if attempts < MAX_ATTEMPTS => {
+                tracing::warn!("advocacy setting of dog {dog_id} has changed, retrying");
+            }
+            RecordWrite::SettingChanged => {
+                return Err("advocacy setting of the dog keeps changing".into());
+            }
+        }
+    };
+
+    // invalidates cached map tiles that may contain the new record
+    // the record has been created even if the invalidation fails
+    // cached map tiles are not affected if the record is not published
+    if advocacy_setting.is_published() {
+        let affected_tiles = tiles_affected_by(&location, INVALIDATION_MARGIN);
+        tracing::info!("invalidating {} cached map tile(s)", affected_tiles.len());
+        if let Err(e) = shared_state.tile_cache.invalidate(&affected_tiles).await {
+            tracing::error!("failed to invalidate cached map tiles: {e}");
+        }
+    }
 
     Ok(BusinessRecord {
         record_id,
@@ -227,11 +228,6 @@ async fn function_handler(
     })
 }
 
-/// Formats a geographic coordinate (longitude or latitude) as a string.
-fn format_geo_coordinate(coord: f64) -> String {
-    format!("{coord:.10}")
-}
-
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     tracing_subscriber::fmt()
@@ -11,11 +11,14 @@ use base64::{
     Engine as _,
 };
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
+use business_core::types::AdvocacySetting;
+use resource_api::DogInfo;
+
 /// Shared state.
 struct SharedState {
     /// DynamoDB client.
@@ -49,16 +52,6 @@ struct DogCreationParams {
     name: String,
 }
 
-/// Information about a dog.
-#[derive(Clone, Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct DogInfo {
-    /// ID of the dog.
-    dog_id: String,
-    /// Name of the dog.
-    name: String,
-}
-
 async fn function_handler(
     shared_state: Arc<SharedState>,
     event: LambdaEvent<DogCreationParams>,
@@ -67,7 +60,10 @@ async fn function_handler(
 
     // randomly generates a new dog ID and encodes it in URL-safe Base64
     let dog_id = Uuid::new_v4();
-    let dog_id = base64_encoder.encode(&dog_id);
+    let dog_id = base64_encoder.encode(dog_id);
+
+    // being an advocate of the app is opt-in
+    let advocacy_setting = AdvocacySetting::default();
 
     // puts the dog into the resource table
     // treats (almost impossible) ID duplication as an internal error
@@ -79,6 +75,7 @@ async fn function_handler(
         .item("pk", AttributeValue::S(format!("dog#{dog_id}")))
         .item("sk", AttributeValue::S("info".to_string()))
         .item("name", AttributeValue::S(event.payload.name.clone()))
+        .item("advocacySetting", AttributeValue::S(advocacy_setting.to_string()))
         .item("createdAt", AttributeValue::N(now.to_string()))
         .item("updatedAt", AttributeValue::N(now.to_string()))
         .condition_expression("attribute_not_exists(pk)") // no update
@@ -116,6 +113,7 @@ async fn function_handler(
     Ok(DogInfo {
         dog_id,
         name: event.payload.name.clone(),
+        advocacy_setting,
     })
 }
 
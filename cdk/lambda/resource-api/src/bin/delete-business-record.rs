@@ -0,0 +1,162 @@
+//! Deletes a business record.
+//!
+//! Both the private and public business records are deleted.
+//!
+//! ## Environment variables
+//!
+//! You have to configure the following environment variables:
+//! - `RESOURCE_TABLE_NAME`: name of the resource table to obtain the dog
+//!   information from
+//! - `BUSINESS_RECORD_TABLE_NAME`: name of the business record table to delete
+//!   the business record from
+//! - `TILE_CACHE_BUCKET_NAME`: name of the S3 bucket that caches rendered map
+//!   tiles. map tiles affected by the deleted business record are
+//!   invalidated.
+
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use business_core::tables::{
+    BusinessRecordTable,
+    BusinessRecordTableBuilder,
+    ResourceTable,
+    TableError,
+};
+use business_core::tile_cache::{
+    DEFAULT_KEY_PREFIX,
+    INVALIDATION_MARGIN,
+    S3TileCache,
+    TileCache as _,
+    tiles_affected_by,
+};
+use business_core::types::BusinessRecord;
+
+/// Shared state.
+struct SharedState {
+    /// Resource table.
+    resource_table: ResourceTable,
+    /// Business record table.
+    business_record_table: BusinessRecordTable,
+    /// Cache of rendered map tiles.
+    tile_cache: S3TileCache,
+}
+
+impl SharedState {
+    async fn new() -> Result<Self, Error> {
+        // caches the table names
+        let resource_table_name = std::env::var("RESOURCE_TABLE_NAME")
+            .map_err(|_| "RESOURCE_TABLE_NAME env is not set")?;
+        let business_record_table_name = std::env::var("BUSINESS_RECORD_TABLE_NAME")
+            .map_err(|_| "BUSINESS_RECORD_TABLE_NAME env is not set")?;
+
+        let tile_cache_bucket_name = std::env::var("TILE_CACHE_BUCKET_NAME")
+            .map_err(|_| "TILE_CACHE_BUCKET_NAME env is not set")?;
+
+        // caches the tables and tile cache
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
+        let resource_table = ResourceTable::new(dynamodb_client.clone(), resource_table_name);
+        let business_record_table = BusinessRecordTableBuilder::default()
+            .client(dynamodb_client)
+            .table_name(business_record_table_name)
+            .build()?;
+        let tile_cache = S3TileCache::new(
+            aws_sdk_s3::Client::new(&config),
+            tile_cache_bucket_name,
+            DEFAULT_KEY_PREFIX,
+        );
+
+        Ok(Self {
+            resource_table,
+            business_record_table,
+            tile_cache,
+        })
+    }
+}
+
+/// Parameters for deleting a business record.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BusinessRecordDeletionParams {
+    /// ID of the user who makes a request on behalf of the dog who carried out
+    /// the business. Must be a friend of the dog.
+    user_id: String,
+    /// ID of the dog who carried out the business.
+    dog_id: String,
+    /// ID of the business record to delete.
+    record_id: String,
+}
+
+async fn function_handler(
+    shared_state: Arc<SharedState>,
+    event: LambdaEvent<BusinessRecordDeletionParams>,
+) -> Result<BusinessRecord, Error> {
+    let BusinessRecordDeletionParams {
+        user_id,
+        dog_id,
+        record_id,
+    } = event.payload;
+
+    // makes sure that the user is a friend of the dog
+    tracing::info!("checking if user {user_id} is a friend of dog {dog_id}");
+    let relationship = shared_state
+        .resource_table
+        .get_user_dog_relationship(&user_id, &dog_id)
+        .await?;
+    if relationship.is_none() {
+        // TODO: return 403 error
+        return Err("user must be a friend of the dog".into());
+    }
+
+    // obtains the business record to locate the affected map tiles
+    tracing::info!("getting business record {record_id}");
+    let record = shared_state
+        .business_record_table
+        .get_record(&record_id)
+        .await?
+        .filter(|record| record.dog_id.as_deref() == Some(dog_id.as_str()))
+        // TODO: return 404 error
+        .ok_or("no such business record of the dog")?;
+
+    // deletes the private and public business records at once
+    tracing::info!("deleting business record {record_id}");
+    match shared_state
+        .business_record_table
+        .delete_record(&record_id, &dog_id)
+        .await
+    {
+        Ok(_) => {}
+        // deleted by another request in the meantime
+        Err(TableError::ConditionFailed(_)) => {
+            return Err("no such business record of the dog".into());
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    // invalidates cached map tiles that may contain the deleted record
+    // the record has been deleted even if the invalidation fails
+    let affected_tiles = tiles_affected_by(&record.location, INVALIDATION_MARGIN);
+    tracing::info!("invalidating {} cached map tile(s)", affected_tiles.len());
+    if let Err(e) = shared_state.tile_cache.invalidate(&affected_tiles).await {
+        tracing::error!("failed to invalidate cached map tiles: {e}");
+    }
+
+    Ok(record)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        // disable printing the name of the module in every log line.
+        .with_target(false)
+        // disabling time is handy because CloudWatch will add the ingestion time.
+        .without_time()
+        .init();
+
+    let shared_state = Arc::new(SharedState::new().await?);
+    run(service_fn(|req| async {
+        function_handler(shared_state.clone(), req).await
+    })).await
+}
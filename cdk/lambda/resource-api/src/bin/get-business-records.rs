@@ -1,5 +1,8 @@
 //! Obtains business records carried out by a given dog.
 //!
+//! Business records are returned page by page in descending order of
+//! timestamps. Specify the `nextToken` of a response to obtain the next page.
+//!
 //! ## Environment variables:
 //!
 //! You have to configure the following environment variables:
@@ -10,17 +13,16 @@
 //! - `DOG_INDEX_NAME`: name of the global secondary index (GSI) on the
 //!   business record table for querying business records by dog IDs
 
-use futures::stream::TryStreamExt as _;
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 use business_core::{
-    tables::{BusinessRecordTableBuilder, ResourceTable},
+    tables::{BusinessRecordTableBuilder, ContinuationToken, ResourceTable},
     types::BusinessRecord,
 };
 
-/// Maximum number of business records to request.
+/// Maximum number of business records in a page.
 const MAX_BUSINESS_RECORD_COUNT: usize = 200;
 
 /// Shared state.
@@ -67,14 +69,46 @@ struct BusinessRecordsQueryParams {
     user_id: String,
     /// Dog ID to query business records for.
     dog_id: String,
+    /// Maximum number of business records in the page.
+    ///
+    /// Clamped to `1..=MAX_BUSINESS_RECORD_COUNT`, and
+    /// `MAX_BUSINESS_RECORD_COUNT` if omitted.
+    #[serde(default)]
+    page_size: Option<usize>,
+    /// Token to obtain the next page.
+    ///
+    /// The first page is returned if omitted.
+    #[serde(default)]
+    next_token: Option<ContinuationToken>,
+}
+
+/// Page of business records.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BusinessRecordsPage {
+    /// Business records in the page.
+    business_records: Vec<BusinessRecord>,
+    /// Token to obtain the next page.
+    ///
+    /// Omitted if there are no more business records.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_token: Option<ContinuationToken>,
 }
 
 async fn function_handler(
     shared_state: Arc<SharedState>,
     event: LambdaEvent<BusinessRecordsQueryParams>,
-) -> Result<Vec<BusinessRecord>, Error> {
-    let BusinessRecordsQueryParams { user_id, dog_id } = event.payload;
+) -> Result<BusinessRecordsPage, Error> {
+    let BusinessRecordsQueryParams {
+        user_id,
+        dog_id,
+        page_size,
+        next_token,
+    } = event.payload;
     tracing::info!("getting business records: dog={dog_id}, user={user_id}");
+    let page_size = page_size
+        .unwrap_or(MAX_BUSINESS_RECORD_COUNT)
+        .clamp(1, MAX_BUSINESS_RECORD_COUNT);
 
     // makes sure that the user is a friend of the dog
     tracing::info!("checking user-dog relationship");
@@ -91,17 +125,19 @@ async fn function_handler(
     }
 
     // queries business records of the dog
-    tracing::info!("querying business records");
+    tracing::info!("querying business records: page size={page_size}, next token={next_token:?}");
     let record_table = BusinessRecordTableBuilder::default()
         .client(shared_state.dynamodb_client.clone())
         .table_name(&shared_state.business_record_table_name)
         .dog_index_name(Some(shared_state.dog_index_name.clone()))
         .build()?;
-    let records = record_table.query_by_dog_id(&dog_id, MAX_BUSINESS_RECORD_COUNT)?;
-    records
-        .try_collect()
-        .await
-        .map_err(Into::into)
+    let page = record_table
+        .query_by_dog_id(&dog_id, page_size, next_token.as_ref())
+        .await?;
+    Ok(BusinessRecordsPage {
+        business_records: page.records,
+        next_token: page.next_token,
+    })
 }
 
 #[tokio::main]
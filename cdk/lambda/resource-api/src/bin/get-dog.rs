@@ -10,6 +10,7 @@ use lambda_runtime::{run, service_fn, Error, LambdaEvent};
 use serde::Deserialize;
 use std::sync::Arc;
 
+use business_core::tables::ResourceTable;
 use resource_api::DogInfo;
 
 /// Shared state.
@@ -96,10 +97,12 @@ async fn function_handler(
             "name is not a string"
         }))?
         .clone();
+    let advocacy_setting = ResourceTable::parse_advocacy_setting(&item)?;
 
     Ok(DogInfo {
         dog_id,
         name,
+        advocacy_setting,
     })
 }
 
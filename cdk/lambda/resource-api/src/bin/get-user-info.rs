@@ -31,7 +31,7 @@ impl SharedState {
             .await?
             .parameter
             .and_then(|p| p.value)
-            .ok_or_else(|| "MAPBOX_ACCESS_TOKEN_PARAMETER_PATH is not configured")?;
+            .ok_or("MAPBOX_ACCESS_TOKEN_PARAMETER_PATH is not configured")?;
         Ok(Self {
             mapbox_access_token,
         })
@@ -43,6 +43,10 @@ impl SharedState {
 #[serde(rename_all = "camelCase")]
 struct UserId {
     /// User ID issued by Passquito.
+    ///
+    /// Required in the request, but the user information does not depend on
+    /// it for now.
+    #[allow(dead_code)]
     user_id: String,
 }
 
@@ -56,7 +60,7 @@ struct UserInfo {
 
 async fn function_handler(
     shared_state: Arc<SharedState>,
-    event: LambdaEvent<UserId>,
+    _event: LambdaEvent<UserId>,
 ) -> Result<UserInfo, Error> {
     Ok(UserInfo {
         mapbox_access_token: shared_state.mapbox_access_token.clone(),
@@ -0,0 +1,258 @@
+//! Propagates the advocacy setting of a dog to its existing business records.
+//!
+//! Processes a batch of business records of a dog per invocation, and
+//! returns the progress, which is supposed to be fed to the next invocation
+//! until the status becomes other than `in-progress` or `rate-limited`.
+//! A state machine drives the invocations.
+//!
+//! The public row of each business record is put, overwritten, or deleted
+//! according to the latest advocacy setting of the dog. If the advocacy
+//! setting changes during the propagation, this propagation is superseded by
+//! the one triggered by the change.
+//!
+//! Map tiles affected by the updated business records are invalidated at the
+//! end of each batch. If the invalidation fails, the invocation fails so that
+//! the state machine retries the batch from the same token; syncing public
+//! rows is idempotent.
+//!
+//! ## Environment variables
+//!
+//! You have to configure the following environment variables:
+//! - `RESOURCE_TABLE_NAME`: name of the resource table to obtain the dog
+//!   information from
+//! - `BUSINESS_RECORD_TABLE_NAME`: name of the business record table to update
+//! - `DOG_INDEX_NAME`: name of the GSI to query business records by dog ID
+//! - `TILE_CACHE_BUCKET_NAME`: name of the S3 bucket that caches rendered map
+//!   tiles. map tiles affected by the updated business records are
+//!   invalidated.
+
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use business_core::mvt::TileCoordinates;
+use business_core::tables::{
+    BusinessRecordTable,
+    BusinessRecordTableBuilder,
+    ContinuationToken,
+    NewBusinessRecord,
+    PublicRecordSync,
+    ResourceTable,
+    TableError,
+};
+use business_core::tile_cache::{
+    DEFAULT_KEY_PREFIX,
+    INVALIDATION_MARGIN,
+    S3TileCache,
+    TileCache as _,
+    tiles_affected_by,
+};
+
+/// Maximum number of business records processed in a single invocation.
+///
+/// Every record invalidates dozens of map tiles across the zoom levels.
+const MAX_RECORDS_PER_BATCH: usize = 25;
+
+/// Shared state.
+struct SharedState {
+    /// Resource table.
+    resource_table: ResourceTable,
+    /// Business record table.
+    business_record_table: BusinessRecordTable,
+    /// Cache of rendered map tiles.
+    tile_cache: S3TileCache,
+}
+
+impl SharedState {
+    async fn new() -> Result<Self, Error> {
+        // caches the table and index names
+        let resource_table_name = std::env::var("RESOURCE_TABLE_NAME")
+            .map_err(|_| "RESOURCE_TABLE_NAME env is not set")?;
+        let business_record_table_name = std::env::var("BUSINESS_RECORD_TABLE_NAME")
+            .map_err(|_| "BUSINESS_RECORD_TABLE_NAME env is not set")?;
+        let dog_index_name = std::env::var("DOG_INDEX_NAME")
+            .map_err(|_| "DOG_INDEX_NAME env is not set")?;
+
+        let tile_cache_bucket_name = std::env::var("TILE_CACHE_BUCKET_NAME")
+            .map_err(|_| "TILE_CACHE_BUCKET_NAME env is not set")?;
+
+        // caches the tables and tile cache
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
+        let resource_table = ResourceTable::new(dynamodb_client.clone(), resource_table_name);
+        let business_record_table = BusinessRecordTableBuilder::default()
+            .client(dynamodb_client)
+            .table_name(business_record_table_name)
+            .dog_index_name(Some(dog_index_name))
+            .build()?;
+        let tile_cache = S3TileCache::new(
+            aws_sdk_s3::Client::new(&config),
+            tile_cache_bucket_name,
+            DEFAULT_KEY_PREFIX,
+        );
+
+        Ok(Self {
+            resource_table,
+            business_record_table,
+            tile_cache,
+        })
+    }
+}
+
+/// Parameters for propagating the advocacy setting.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PropagationParams {
+    /// ID of the dog.
+    dog_id: String,
+    /// Token to resume the propagation from.
+    ///
+    /// Starts from the newest business record if omitted.
+    #[serde(default)]
+    next_token: Option<ContinuationToken>,
+}
+
+/// Progress of the propagation.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PropagationProgress {
+    /// ID of the dog.
+    dog_id: String,
+    /// Token to resume the propagation from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_token: Option<ContinuationToken>,
+    /// Status of the propagation.
+    status: PropagationStatus,
+    /// Number of business records processed in this invocation.
+    processed_records: usize,
+}
+
+/// Status of the propagation.
+#[derive(Clone, Copy, Debug, Serialize)]
+enum PropagationStatus {
+    /// More business records remain.
+    #[serde(rename = "in-progress")]
+    InProgress,
+    /// Rate limited. Should resume after a while.
+    #[serde(rename = "rate-limited")]
+    RateLimited,
+    /// All the business records have been processed.
+    #[serde(rename = "done")]
+    Done,
+    /// Advocacy setting has been changed during the propagation.
+    #[serde(rename = "superseded")]
+    Superseded,
+}
+
+async fn function_handler(
+    shared_state: Arc<SharedState>,
+    event: LambdaEvent<PropagationParams>,
+) -> Result<PropagationProgress, Error> {
+    let PropagationParams { dog_id, next_token } = event.payload;
+    tracing::info!("propagating advocacy setting: dog={dog_id}, next token={next_token:?}");
+
+    let progress = |next_token, status, processed_records| PropagationProgress {
+        dog_id: dog_id.clone(),
+        next_token,
+        status,
+        processed_records,
+    };
+
+    let Some(advocacy_setting) = shared_state
+        .resource_table
+        .get_dog_advocacy_setting(&dog_id)
+        .await?
+    else {
+        tracing::warn!("no dog item: {dog_id}");
+        return Ok(progress(None, PropagationStatus::Done, 0));
+    };
+    tracing::info!("advocacy setting: {advocacy_setting}");
+
+    let page = match shared_state
+        .business_record_table
+        .query_by_dog_id(&dog_id, MAX_RECORDS_PER_BATCH, next_token.as_ref())
+        .await
+    {
+        Ok(page) => page,
+        Err(TableError::RateLimited(e)) => {
+            tracing::warn!("rate limited: {e}");
+            return Ok(progress(next_token, PropagationStatus::RateLimited, 0));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut last_token = next_token;
+    let mut affected_tiles: Vec<TileCoordinates> = Vec::new();
+    let mut status = None;
+    for (i, record) in page.records.iter().enumerate() {
+        let new_record = NewBusinessRecord::from_private_record(record, advocacy_setting)?;
+        match shared_state
+            .business_record_table
+            .sync_public_record(&new_record, &shared_state.resource_table)
+            .await
+        {
+            Ok(PublicRecordSync::Synced) => {
+                affected_tiles.extend(tiles_affected_by(&record.location, INVALIDATION_MARGIN));
+            }
+            Ok(PublicRecordSync::RecordNotFound) => {
+                tracing::info!("skipping deleted record: {}", record.record_id);
+            }
+            Ok(PublicRecordSync::SettingChanged) => {
+                tracing::info!("advocacy setting has been changed");
+                status = Some((PropagationStatus::Superseded, i));
+                break;
+            }
+            Err(TableError::RateLimited(e)) => {
+                tracing::warn!("rate limited: {e}");
+                status = Some((PropagationStatus::RateLimited, i));
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        last_token = Some(record.into());
+    }
+
+    // invalidates cached map tiles that may contain the updated records
+    // fails the invocation so that the state machine retries the batch,
+    // otherwise stale tiles would remain until they expire
+    affected_tiles.sort_by_key(|tile| (tile.zoom(), tile.x(), tile.y()));
+    affected_tiles.dedup_by_key(|tile| (tile.zoom(), tile.x(), tile.y()));
+    tracing::info!("invalidating {} cached map tile(s)", affected_tiles.len());
+    if let Err(e) = shared_state.tile_cache.invalidate(&affected_tiles).await {
+        tracing::error!("failed to invalidate cached map tiles: {e}");
+        return Err(e.into());
+    }
+
+    let progress = match status {
+        Some((PropagationStatus::Superseded, processed_records)) => {
+            progress(None, PropagationStatus::Superseded, processed_records)
+        }
+        Some((status, processed_records)) => progress(last_token, status, processed_records),
+        None => match page.next_token {
+            Some(next_token) => progress(
+                Some(next_token),
+                PropagationStatus::InProgress,
+                page.records.len(),
+            ),
+            None => progress(None, PropagationStatus::Done, page.records.len()),
+        },
+    };
+    tracing::info!("processed {} record(s): {:?}", progress.processed_records, progress.status);
+    Ok(progress)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        // disable printing the name of the module in every log line.
+        .with_target(false)
+        // disabling time is handy because CloudWatch will add the ingestion time.
+        .without_time()
+        .init();
+
+    let shared_state = Arc::new(SharedState::new().await?);
+    run(service_fn(|req| async {
+        function_handler(shared_state.clone(), req).await
+    })).await
+}
@@ -0,0 +1,214 @@
+//! Updates a business record.
+//!
+//! The business type and location of a business record can be changed.
+//! Both the private and public business records are updated, and the public
+//! business record follows the current advocacy setting of the dog.
+//!
+//! ## Environment variables
+//!
+//! You have to configure the following environment variables:
+//! - `RESOURCE_TABLE_NAME`: name of the resource table to obtain the dog
+//!   information from
+//! - `BUSINESS_RECORD_TABLE_NAME`: name of the business record table to update
+//!   the business record in
+//! - `TILE_CACHE_BUCKET_NAME`: name of the S3 bucket that caches rendered map
+//!   tiles. map tiles affected by the business record before and after the
+//!   update are invalidated.
+
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use business_core::tables::{
+    BusinessRecordTable,
+    BusinessRecordTableBuilder,
+    NewBusinessRecord,
+    RecordWrite,
+    ResourceTable,
+    TableError,
+};
+use business_core::tile_cache::{
+    DEFAULT_KEY_PREFIX,
+    INVALIDATION_MARGIN,
+    S3TileCache,
+    TileCache as _,
+    tiles_affected_by,
+};
+use business_core::types::{BusinessRecord, BusinessType, GeolocationCoordinates};
+
+/// Maximum number of attempts to update a business record.
+///
+/// Another attempt is made with the latest advocacy setting if the advocacy
+/// setting of the dog changes during an attempt.
+const MAX_ATTEMPTS: usize = 3;
+
+/// Shared state.
+struct SharedState {
+    /// Resource table.
+    resource_table: ResourceTable,
+    /// Business record table.
+    business_record_table: BusinessRecordTable,
+    /// Cache of rendered map tiles.
+    tile_cache: S3TileCache,
+}
+
+impl SharedState {
+    async fn new() -> Result<Self, Error> {
+        // caches the table names
+        let resource_table_name = std::env::var("RESOURCE_TABLE_NAME")
+            .map_err(|_| "RESOURCE_TABLE_NAME env is not set")?;
+        let business_record_table_name = std::env::var("BUSINESS_RECORD_TABLE_NAME")
+            .map_err(|_| "BUSINESS_RECORD_TABLE_NAME env is not set")?;
+
+        let tile_cache_bucket_name = std::env::var("TILE_CACHE_BUCKET_NAME")
+            .map_err(|_| "TILE_CACHE_BUCKET_NAME env is not set")?;
+
+        // caches the tables and tile cache
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
+        let resource_table = ResourceTable::new(dynamodb_client.clone(), resource_table_name);
+        let business_record_table = BusinessRecordTableBuilder::default()
+            .client(dynamodb_client)
+            .table_name(business_record_table_name)
+            .build()?;
+        let tile_cache = S3TileCache::new(
+            aws_sdk_s3::Client::new(&config),
+            tile_cache_bucket_name,
+            DEFAULT_KEY_PREFIX,
+        );
+
+        Ok(Self {
+            resource_table,
+            business_record_table,
+            tile_cache,
+        })
+    }
+}
+
+/// Parameters for updating a business record.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BusinessRecordUpdateParams {
+    /// ID of the user who makes a request on behalf of the dog who carried out
+    /// the business. Must be a friend of the dog.
+    user_id: String,
+    /// ID of the dog who carried out the business.
+    dog_id: String,
+    /// ID of the business record to update.
+    record_id: String,
+    /// New type of the business.
+    ///
+    /// Unchanged if omitted.
+    #[serde(default)]
+    business_type: Option<BusinessType>,
+    /// New location of the business.
+    ///
+    /// Unchanged if omitted.
+    #[serde(default)]
+    location: Option<GeolocationCoordinates>,
+}
+
+async fn function_handler(
+    shared_state: Arc<SharedState>,
+    event: LambdaEvent<BusinessRecordUpdateParams>,
+) -> Result<BusinessRecord, Error> {
+    let BusinessRecordUpdateParams {
+        user_id,
+        dog_id,
+        record_id,
+        business_type,
+        location,
+    } = event.payload;
+
+    // makes sure that the user is a friend of the dog
+    tracing::info!("checking if user {user_id} is a friend of dog {dog_id}");
+    let relationship = shared_state
+        .resource_table
+        .get_user_dog_relationship(&user_id, &dog_id)
+        .await?;
+    if relationship.is_none() {
+        // TODO: return 403 error
+        return Err("user must be a friend of the dog".into());
+    }
+
+    // obtains the current business record
+    tracing::info!("getting business record {record_id}");
+    let mut record = shared_state
+        .business_record_table
+        .get_record(&record_id)
+        .await?
+        .filter(|record| record.dog_id.as_deref() == Some(dog_id.as_str()))
+        // TODO: return 404 error
+        .ok_or("no such business record of the dog")?;
+    let old_location = record.location.clone();
+    if let Some(business_type) = business_type {
+        record.business_type = business_type;
+    }
+    if let Some(location) = location {
+        record.location = location;
+    }
+
+    // updates the private and public business records at once
+    // tile coordinates at all the zoom levels are recalculated
+    // retries with the latest advocacy setting if it changes meanwhile
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        tracing::info!("getting advocacy setting of dog {dog_id}");
+        let advocacy_setting = shared_state
+            .resource_table
+            .get_dog_advocacy_setting(&dog_id)
+            .await?
+            .ok_or("no dog item")?;
+        tracing::info!("updating business record {record_id} ({advocacy_setting})");
+        let updated_record = NewBusinessRecord::from_private_record(&record, advocacy_setting)?;
+        match shared_state
+            .business_record_table
+            .update_record(&updated_record, &shared_state.resource_table)
+            .await
+        {
+            Ok(RecordWrite::Written) => break,
+            Ok(RecordWrite::SettingChanged) if attempts < MAX_ATTEMPTS => {
+                tracing::warn!("advocacy setting of dog {dog_id} has changed, retrying");
+            }
+            Ok(RecordWrite::SettingChanged) => {
+                return Err("advocacy setting of the dog keeps changing".into());
+            }
+            // deleted by another request in the meantime
+            Err(TableError::ConditionFailed(_)) => {
+                return Err("no such business record of the dog".into());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // invalidates cached map tiles that may contain the record before or
+    // after the update
+    // the record has been updated even if the invalidation fails
+    let mut affected_tiles = tiles_affected_by(&old_location, INVALIDATION_MARGIN);
+    affected_tiles.extend(tiles_affected_by(&record.location, INVALIDATION_MARGIN));
+    affected_tiles.sort_by_key(|tile| (tile.zoom(), tile.x(), tile.y()));
+    affected_tiles.dedup_by_key(|tile| (tile.zoom(), tile.x(), tile.y()));
+    tracing::info!("invalidating {} cached map tile(s)", affected_tiles.len());
+    if let Err(e) = shared_state.tile_cache.invalidate(&affected_tiles).await {
+        tracing::error!("failed to invalidate cached map tiles: {e}");
+    }
+
+    Ok(record)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        // disable printing the name of the module in every log line.
+        .with_target(false)
+        // disabling time is handy because CloudWatch will add the ingestion time.
+        .without_time()
+        .init();
+
+    let shared_state = Arc::new(SharedState::new().await?);
+    run(service_fn(|req| async {
+        function_handler(shared_state.clone(), req).await
+    })).await
+}
@@ -0,0 +1,113 @@
+//! Updates the settings of a dog.
+//!
+//! A change of the advocacy setting is propagated to the existing business
+//! records of the dog in the background by `propagate-advocacy-setting`.
+//!
+//! ## Environment variables
+//!
+//! You have to configure the following environment variable:
+//! - `RESOURCE_TABLE_NAME`: name of the resource table that stores dogs
+
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use business_core::tables::{ResourceTable, UserDogRelationship};
+use business_core::types::AdvocacySetting;
+
+/// Shared state.
+struct SharedState {
+    /// Resource table.
+    resource_table: ResourceTable,
+}
+
+impl SharedState {
+    async fn new() -> Result<Self, Error> {
+        // caches the resource table name
+        let resource_table_name = std::env::var("RESOURCE_TABLE_NAME")
+            .map_err(|_| "RESOURCE_TABLE_NAME env is not set")?;
+        // caches the DynamoDB client
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
+        Ok(Self {
+            resource_table: ResourceTable::new(dynamodb_client, resource_table_name),
+        })
+    }
+}
+
+/// Parameters for updating a dog.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DogUpdateParams {
+    /// ID of the user who requests the update.
+    ///
+    /// The user must be a guardian of the dog.
+    user_id: String,
+    /// ID of the dog.
+    dog_id: String,
+    /// New advocacy setting of the dog.
+    advocacy_setting: AdvocacySetting,
+}
+
+/// Updated settings of a dog.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DogSettings {
+    /// ID of the dog.
+    dog_id: String,
+    /// Advocacy setting of the dog.
+    advocacy_setting: AdvocacySetting,
+}
+
+async fn function_handler(
+    shared_state: Arc<SharedState>,
+    event: LambdaEvent<DogUpdateParams>,
+) -> Result<DogSettings, Error> {
+    let DogUpdateParams {
+        user_id,
+        dog_id,
+        advocacy_setting,
+    } = event.payload;
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    // makes sure that the user is a guardian of the dog
+    // friends may create business records but cannot change the settings
+    tracing::info!("checking relationship between user and dog: {} - {}", user_id, dog_id);
+    let relationship = shared_state
+        .resource_table
+        .get_user_dog_relationship(&user_id, &dog_id)
+        .await?;
+    if !matches!(relationship, Some(UserDogRelationship::Guardian)) {
+        // TODO: return 403 error
+        return Err("only guardian can update dog".into());
+    }
+
+    tracing::info!("updating advocacy setting of dog {dog_id}: {advocacy_setting}");
+    shared_state
+        .resource_table
+        .update_dog_advocacy_setting(&dog_id, advocacy_setting, now as i64)
+        .await?;
+
+    Ok(DogSettings {
+        dog_id,
+        advocacy_setting,
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        // disable printing the name of the module in every log line.
+        .with_target(false)
+        // disabling time is handy because CloudWatch will add the ingestion time.
+        .without_time()
+        .init();
+
+    let shared_state = Arc::new(SharedState::new().await?);
+    run(service_fn(|req| async {
+        function_handler(shared_state.clone(), req).await
+    })).await
+}
@@ -2,6 +2,8 @@
 
 use serde::Serialize;
 
+use business_core::types::AdvocacySetting;
+
 /// Information on a dog.
 #[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -10,4 +12,6 @@ pub struct DogInfo {
     pub dog_id: String,
     /// Name of the dog.
     pub name: String,
+    /// Advocacy setting of the dog.
+    pub advocacy_setting: AdvocacySetting,
 }
@@ -0,0 +1,235 @@
+import {
+  Duration,
+  aws_cloudwatch as cloudwatch,
+  aws_iam as iam,
+  aws_lambda as lambda,
+  aws_pipes as pipes,
+  aws_stepfunctions as sfn,
+  aws_stepfunctions_tasks as tasks,
+} from 'aws-cdk-lib';
+import { RustFunction } from 'cargo-lambda-cdk';
+import { Construct } from 'constructs';
+
+import type { BusinessRecordTable } from './business-record-table';
+import { DOG_INDEX_NAME } from './business-record-table';
+import type { ResourceTable } from './resource-table';
+import type { TileCacheBucket } from './tile-cache-bucket';
+
+/** Time to wait before resuming a rate-limited propagation. */
+const RATE_LIMIT_WAIT = Duration.seconds(30);
+
+/** Possible values of the advocacy setting of a dog. */
+const ADVOCACY_SETTINGS = ['publish', 'publish-anonymized', 'do-not-publish'] as const;
+
+/** Advocacy setting applied if a dog item has no advocacy setting. */
+const DEFAULT_ADVOCACY_SETTING = 'publish-anonymized';
+
+/** Maximum number of retries of a failed batch. */
+const MAX_BATCH_RETRIES = 5;
+
+/**
+ * Properties for {@link AdvocacyPropagation}.
+ *
+ * @beta
+ */
+export interface AdvocacyPropagationProps {
+  /** Path to the Cargo.toml of the Lambda function. */
+  readonly manifestPath: string;
+
+  /** Resource table. */
+  readonly resourceTable: ResourceTable;
+
+  /** Business record table. */
+  readonly businessRecordTable: BusinessRecordTable;
+
+  /** S3 bucket that caches rendered map tiles. */
+  readonly tileCacheBucket: TileCacheBucket;
+}
+
+/**
+ * CDK construct which propagates changes of the advocacy setting of a dog to
+ * its existing business records.
+ *
+ * @remarks
+ *
+ * An EventBridge pipe starts the state machine whenever the advocacy setting
+ * of a dog item in the resource table is changed. Other modifications of a
+ * dog item; e.g., renaming, do not start the state machine. The state machine repeatedly invokes the Lambda
+ * function, which processes a batch of business records of the dog per
+ * invocation, until all the business records are processed.
+ * A failed batch is retried with exponential backoff, and the execution fails
+ * if the retries are exhausted, which triggers {@link failureAlarm}.
+ *
+ * @beta
+ */
+export class AdvocacyPropagation extends Construct {
+  /** Lambda function to process a batch of business records. */
+  readonly propagateAdvocacySettingLambda: lambda.IFunction;
+
+  /** State machine that drives the propagation. */
+  readonly stateMachine: sfn.StateMachine;
+
+  /** Alarm that goes off when a propagation fails or times out. */
+  readonly failureAlarm: cloudwatch.Alarm;
+
+  constructor(scope: Construct, id: string, props: AdvocacyPropagationProps) {
+    super(scope, id);
+
+    const {
+      businessRecordTable,
+      manifestPath,
+      resourceTable,
+      tileCacheBucket,
+    } = props;
+
+    // Lambda function
+    this.propagateAdvocacySettingLambda = new RustFunction(this, 'PropagateAdvocacySettingLambda', {
+      manifestPath,
+      binaryName: 'propagate-advocacy-setting',
+      architecture: lambda.Architecture.ARM_64,
+      // a batch puts hundreds of invalidation markers of map tiles
+      memorySize: 256,
+      timeout: Duration.minutes(5),
+      environment: {
+        RESOURCE_TABLE_NAME: resourceTable.table.tableName,
+        BUSINESS_RECORD_TABLE_NAME: businessRecordTable.table.tableName,
+        DOG_INDEX_NAME: DOG_INDEX_NAME,
+        TILE_CACHE_BUCKET_NAME: tileCacheBucket.bucket.bucketName,
+      },
+    });
+    resourceTable.table.grantReadData(this.propagateAdvocacySettingLambda);
+    businessRecordTable.table.grantReadWriteData(this.propagateAdvocacySettingLambda);
+    tileCacheBucket.grantInvalidate(this.propagateAdvocacySettingLambda);
+
+    // state machine
+    // - extracts the dog ID from the partition key "dog#{dogId}"
+    const extractDogId = new sfn.Pass(this, 'ExtractDogId', {
+      parameters: {
+        'dogId.$': "States.ArrayGetItem(States.StringSplit($.pk, '#'), 1)",
+      },
+    });
+    // - processes a batch of business records
+    //   retries with exponential backoff; the batch restarts from the same
+    //   token and is idempotent
+    const processBatch = new tasks.LambdaInvoke(this, 'ProcessBatch', {
+      lambdaFunction: this.propagateAdvocacySettingLambda,
+      payloadResponseOnly: true,
+    });
+    processBatch.addRetry({
+      errors: [sfn.Errors.ALL],
+      interval: Duration.seconds(5),
+      backoffRate: 2,
+      maxAttempts: MAX_BATCH_RETRIES,
+      jitterStrategy: sfn.JitterType.FULL,
+    });
+    // - fails the execution if the retries are exhausted
+    processBatch.addCatch(new sfn.Fail(this, 'PropagationFailed', {
+      comment: 'Failed to propagate the advocacy setting',
+    }), {
+      resultPath: '$.error',
+    });
+    // - waits before resuming a rate-limited propagation
+    const waitForRateLimit = new sfn.Wait(this, 'WaitForRateLimit', {
+      time: sfn.WaitTime.duration(RATE_LIMIT_WAIT),
+    });
+    waitForRateLimit.next(processBatch);
+    // - repeats until the propagation is done or superseded
+    const checkStatus = new sfn.Choice(this, 'CheckStatus')
+      .when(sfn.Condition.stringEquals('$.status', 'in-progress'), processBatch)
+      .when(sfn.Condition.stringEquals('$.status', 'rate-limited'), waitForRateLimit)
+      .otherwise(new sfn.Succeed(this, 'Finished'));
+    this.stateMachine = new sfn.StateMachine(this, 'StateMachine', {
+      definitionBody: sfn.DefinitionBody.fromChainable(
+        extractDogId.next(processBatch).next(checkStatus),
+      ),
+      timeout: Duration.days(1),
+    });
+
+    // alarm on failed or timed-out executions
+    this.failureAlarm = new cloudwatch.Alarm(this, 'FailureAlarm', {
+      alarmDescription: 'Propagation of the advocacy setting of a dog failed',
+      metric: new cloudwatch.MathExpression({
+        expression: 'failed + timedOut',
+        usingMetrics: {
+          failed: this.stateMachine.metricFailed(),
+          timedOut: this.stateMachine.metricTimedOut(),
+        },
+        period: Duration.minutes(5),
+      }),
+      threshold: 1,
+      evaluationPeriods: 1,
+      comparisonOperator: cloudwatch.ComparisonOperator.GREATER_THAN_OR_EQUAL_TO_THRESHOLD,
+      treatMissingData: cloudwatch.TreatMissingData.NOT_BREACHING,
+    });
+
+    // pipe from the resource table stream to the state machine
+    const pipeRole = new iam.Role(this, 'PipeRole', {
+      assumedBy: new iam.ServicePrincipal('pipes.amazonaws.com'),
+    });
+    resourceTable.table.grantStreamRead(pipeRole);
+    this.stateMachine.grantStartExecution(pipeRole);
+    new pipes.CfnPipe(this, 'DogUpdatePipe', {
+      description: 'Starts propagating the changed advocacy setting of a dog',
+      roleArn: pipeRole.roleArn,
+      source: resourceTable.table.tableStreamArn!,
+      sourceParameters: {
+        dynamoDbStreamParameters: {
+          startingPosition: 'LATEST',
+          batchSize: 1,
+        },
+        // only MODIFY events that change the advocacy setting.
+        // an event pattern cannot compare the old and new images, so every
+        // old setting has its own filter, and filters are ORed.
+        filterCriteria: {
+          filters: [
+            ...ADVOCACY_SETTINGS.map((oldSetting) => ({
+              pattern: makeAdvocacySettingChangePattern(
+                { S: [oldSetting] },
+                ADVOCACY_SETTINGS.filter((s) => s !== oldSetting),
+              ),
+            })),
+            // dogs created before the advocacy setting was introduced
+            {
+              pattern: makeAdvocacySettingChangePattern(
+                { S: [{ exists: false }] },
+                ADVOCACY_SETTINGS.filter((s) => s !== DEFAULT_ADVOCACY_SETTING),
+              ),
+            },
+          ],
+        },
+      },
+      target: this.stateMachine.stateMachineArn,
+      targetParameters: {
+        stepFunctionStateMachineParameters: {
+          invocationType: 'FIRE_AND_FORGET',
+        },
+        inputTemplate: '{"pk": "<$.dynamodb.Keys.pk.S>"}',
+      },
+    });
+  }
+}
+
+/**
+ * Makes an event pattern that matches a DynamoDB stream record of a dog item
+ * whose advocacy setting changes from `oldSetting` to one of `newSettings`.
+ */
+function makeAdvocacySettingChangePattern(
+  oldSetting: object,
+  newSettings: readonly string[],
+): string {
+  return JSON.stringify({
+    eventName: ['MODIFY'],
+    dynamodb: {
+      Keys: {
+        pk: { S: [{ prefix: 'dog#' }] },
+        sk: { S: ['info'] },
+      },
+      OldImage: {
+        advocacySetting: oldSetting,
+      },
+      NewImage: {
+        advocacySetting: { S: newSettings },
+      },
+    },
+  });
+}
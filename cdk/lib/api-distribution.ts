@@ -64,9 +64,47 @@ export class ApiDistribution extends Construct {
         headerBehavior: cloudfront.CacheHeaderBehavior.allowList(
           'X-Api-Key',
         ),
+        // filters, the output format, and layers of tiles
+        queryStringBehavior: cloudfront.CacheQueryStringBehavior.allowList(
+          'since',
+          'until',
+          'businessType',
+          'format',
+          'layers',
+        ),
         minTtl: Duration.minutes(5),
         maxTtl: Duration.minutes(15),
         defaultTtl: Duration.minutes(5),
+        // map tiles are compressed by the origin according to the
+        // normalized Accept-Encoding header
+        enableAcceptEncodingGzip: true,
+        enableAcceptEncodingBrotli: true,
+      },
+    );
+
+    // cache policy for private dog tiles of the Map API
+    // - Authorization must be forwarded to the Cognito authorizer. CloudFront
+    //   forwards Authorization only if it is included in the cache key.
+    // - dog tiles are not cached by default because responses have no
+    //   Cache-Control header. even if cached, tiles are never shared among
+    //   users because the cache key includes Authorization.
+    const dogTileCachePolicy = new cloudfront.CachePolicy(
+      this,
+      'DogTileCachePolicy',
+      {
+        comment: 'cache policy for private dog tiles of the Dog\'s Business Map API',
+        headerBehavior: cloudfront.CacheHeaderBehavior.allowList(
+          'Authorization',
+        ),
+        // filters of tiles
+        queryStringBehavior: cloudfront.CacheQueryStringBehavior.allowList(
+          'since',
+          'until',
+          'businessType',
+        ),
+        minTtl: Duration.seconds(0),
+        maxTtl: Duration.minutes(5),
+        defaultTtl: Duration.seconds(0),
       },
     );
 
@@ -90,6 +128,8 @@ export class ApiDistribution extends Construct {
       },
     );
 
+    const mapApiBasePath = mapApi.basePath.replace(/\/$/, '');
+    const mapApiOrigin = new origins.RestApiOrigin(mapApi.api);
     this.distribution = new cloudfront.Distribution(this, 'Distribution', {
       comment: 'Dog\'s Business APIs',
       // routes to the Resource API by default
@@ -100,9 +140,17 @@ export class ApiDistribution extends Construct {
         responseHeadersPolicy: corsHeadersPolicy,
         viewerProtocolPolicy: cloudfront.ViewerProtocolPolicy.HTTPS_ONLY,
       },
+      // behaviors are evaluated in order; private dog tiles must precede the
+      // catch-all behavior of the Map API
       additionalBehaviors: {
-        [`${mapApi.basePath.replace(/\/$/, '')}/*`]: {
-          origin: new origins.RestApiOrigin(mapApi.api),
+        [`${mapApiBasePath}/dog/*`]: {
+          origin: mapApiOrigin,
+          cachePolicy: dogTileCachePolicy,
+          responseHeadersPolicy: corsHeadersPolicy,
+          viewerProtocolPolicy: cloudfront.ViewerProtocolPolicy.HTTPS_ONLY,
+        },
+        [`${mapApiBasePath}/*`]: {
+          origin: mapApiOrigin,
           cachePolicy: mapApiCachePolicy,
           responseHeadersPolicy: corsHeadersPolicy,
           viewerProtocolPolicy: cloudfront.ViewerProtocolPolicy.HTTPS_ONLY,
@@ -44,6 +44,9 @@ export class BusinessRecordTable extends Construct {
    *
    * ### Public business reccords
    *
+   * Not created if the dog who carried out the business does not publish
+   * business records.
+   *
    * - `pk`: "{recordId}"
    *   - `recordId`: unique ID of the business record
    * - `sk`: "public"
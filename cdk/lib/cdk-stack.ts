@@ -20,6 +20,7 @@ import { ResourceApi } from './resource-api';
 import { ResourceTable } from './resource-table';
 import { SsmParameters } from './ssm-parameters';
 import { StackReader } from './stack-reader';
+import { TileCacheBucket } from './tile-cache-bucket';
 
 export interface CdkStackProps extends StackProps {
   /** Deployment stage. */
@@ -96,18 +97,24 @@ export class CdkStack extends Stack {
     const businessRecordTable = new BusinessRecordTable(this, 'BusinessRecordTable', {
       deploymentStage,
     });
+    const tileCacheBucket = new TileCacheBucket(this, 'TileCacheBucket', {
+      deploymentStage,
+    });
     const resourceApi = new ResourceApi(this, 'ResourceApi', {
       basePath: '/dogs-business-api/resource',
       allowOrigins,
       resourceTable,
       businessRecordTable,
+      tileCacheBucket,
       userPool: passquito.userPool.userPool,
       ssmParameters,
     })
     const mapApi = new MapApi(this, 'MapApi', {
       basePath: '/dogs-business-api/map',
       allowOrigins,
+      resourceTable,
       businessRecordTable,
+      tileCacheBucket,
       userPool: passquito.userPool.userPool,
     });
     const apiDistribution = new ApiDistribution(this, 'ApiDistribution', {
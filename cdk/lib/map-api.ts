@@ -12,10 +12,12 @@ import {
   makeIntegrationResponsesAllowCors,
   makeMethodResponsesAllowCors,
 } from '@codemonger-io/cdk-cors-utils';
-import { RestApiWithSpec } from '@codemonger-io/cdk-rest-api-with-spec';
-import { composeMappingTemplate } from '@codemonger-io/mapping-template-compose';
+import { RestApiWithSpec, augmentAuthorizer } from '@codemonger-io/cdk-rest-api-with-spec';
+import { composeMappingTemplate, ifThen } from '@codemonger-io/mapping-template-compose';
 
 import type { BusinessRecordTable } from './business-record-table';
+import type { ResourceTable } from './resource-table';
+import type { TileCacheBucket } from './tile-cache-bucket';
 import {
   INDEXED_ZOOM_LEVELS,
   TILE_INDEX_NAME_PREFIX,
@@ -39,9 +41,15 @@ export interface MapApiProps {
    */
   readonly allowOrigins: string[];
 
+  /** Resource table. */
+  readonly resourceTable: ResourceTable;
+
   /** Business record table. */
   readonly businessRecordTable: BusinessRecordTable;
 
+  /** S3 bucket that caches rendered map tiles. */
+  readonly tileCacheBucket: TileCacheBucket;
+
   /** User pool for authentication. */
   readonly userPool: cognito.UserPool;
 }
@@ -55,13 +63,23 @@ export class MapApi extends Construct {
   /** Lambda function to obtain a tile. */
   readonly getTileLambda: lambda.IFunction;
 
+  /** Lambda function to obtain a private tile of a dog. */
+  readonly getDogTileLambda: lambda.IFunction;
+
   /** API Gateway REST API. */
   readonly api: RestApiWithSpec;
 
   constructor(scope: Construct, id: string, readonly props: MapApiProps) {
     super(scope, id);
 
-    const { allowOrigins, basePath, businessRecordTable, userPool } = props;
+    const {
+      allowOrigins,
+      basePath,
+      businessRecordTable,
+      resourceTable,
+      tileCacheBucket,
+      userPool,
+    } = props;
     const manifestPath = path.join('lambda', 'map-api', 'Cargo.toml');
 
     // Lambda functions
@@ -76,9 +94,27 @@ export class MapApi extends Construct {
         BUSINESS_RECORD_TABLE_NAME: businessRecordTable.table.tableName,
         INDEXED_ZOOM_LEVELS: INDEXED_ZOOM_LEVELS.join(','),
         TILE_INDEX_NAME_PREFIX,
+        TILE_CACHE_BUCKET_NAME: tileCacheBucket.bucket.bucketName,
       },
     });
     businessRecordTable.table.grantReadData(this.getTileLambda);
+    tileCacheBucket.grantReadWrite(this.getTileLambda);
+    // - get a private tile of a dog
+    this.getDogTileLambda = new RustFunction(this, 'GetDogTileLambda', {
+      manifestPath,
+      binaryName: 'get-dog-tile',
+      architecture: lambda.Architecture.ARM_64,
+      memorySize: 128,
+      timeout: Duration.seconds(5),
+      environment: {
+        RESOURCE_TABLE_NAME: resourceTable.table.tableName,
+        BUSINESS_RECORD_TABLE_NAME: businessRecordTable.table.tableName,
+        INDEXED_ZOOM_LEVELS: INDEXED_ZOOM_LEVELS.join(','),
+        TILE_INDEX_NAME_PREFIX,
+      },
+    });
+    resourceTable.table.grantReadData(this.getDogTileLambda);
+    businessRecordTable.table.grantReadData(this.getDogTileLambda);
 
     // REST API
     this.api = new RestApiWithSpec(this, 'MapApi', {
@@ -87,6 +123,8 @@ export class MapApi extends Construct {
         version: '0.1.0',
       },
       openApiOutputPath: path.join('openapi', 'map-api.json'),
+      // get-tile returns Base64-encoded mvt data with this Content-Type, and
+      // API Gateway converts it to binary. GeoJSON is returned as text.
       binaryMediaTypes: ['application/vnd.mapbox-vector-tile'],
       minCompressionSize: Size.kibibytes(4),
       defaultCorsPreflightOptions: allowOrigins.length > 0 ? {
@@ -129,6 +167,19 @@ export class MapApi extends Construct {
       });
     }
 
+    // user pool authorizer
+    const authorizer = augmentAuthorizer(
+      new apigw.CognitoUserPoolsAuthorizer(this, 'UserPoolAuthorizer', {
+        cognitoUserPools: [userPool],
+      }),
+      {
+        description: 'Authorizer that authenticates users by ID tokens issued by the Cognito user pool',
+        type: 'apiKey',
+        in: 'header',
+        name: 'Authorization',
+      },
+    );
+
     // gets to the base path
     const root = basePath
       .split('/')
@@ -147,20 +198,104 @@ export class MapApi extends Construct {
     const tileZX = tileZ.addResource('{x}');
     // /tile/{z}/{x}/{y}
     const tileZXY = tileZX.addResource('{y}');
+    // the Lambda function takes the proxy event and returns a proper HTTP
+    // response including the status code, headers, and body
+    const getTileIntegration = new apigw.LambdaIntegration(this.getTileLambda);
+    // method responses common to the tile formats
+    const tileMethodResponses = [
+      {
+        statusCode: '304',
+        description: 'Map tile matches the ETag in the If-None-Match header',
+      },
+      {
+        statusCode: '400',
+        description: 'Invalid tile coordinates or query parameters',
+      },
+      {
+        statusCode: '429',
+        description: 'Too many requests',
+      },
+    ];
     // /tile/{z}/{x}/{y}/tile.mvt
     const tileMvt = tileZXY.addResource('tile.mvt');
     // - GET
-    tileMvt.addMethod(
+    tileMvt.addMethod('GET', getTileIntegration, {
+      description: 'Obtain a map tile at a given zoom level, x, and y coordinates',
+      methodResponses: makeMethodResponsesAllowCors([
+        {
+          statusCode: '200',
+          description: 'Map tile in the Mapbox vector tile format',
+          responseParameters: {
+            'method.response.header.Content-Type': true,
+            'method.response.header.Content-Encoding': true,
+            'method.response.header.Cache-Control': true,
+            'method.response.header.ETag': true,
+          },
+        },
+        {
+          statusCode: '204',
+          description: 'Map tile has no features',
+        },
+        ...tileMethodResponses,
+      ]),
+    });
+
+    // /tile/{z}/{x}/{y}/tile.geojson
+    const tileGeoJson = tileZXY.addResource('tile.geojson');
+    // - GET
+    tileGeoJson.addMethod('GET', getTileIntegration, {
+      description: 'Obtain business records in a map tile at a given zoom level, x, and y coordinates as GeoJSON',
+      methodResponses: makeMethodResponsesAllowCors([
+        {
+          statusCode: '200',
+          description: 'GeoJSON FeatureCollection of business records in the map tile',
+          responseParameters: {
+            'method.response.header.Content-Type': true,
+            'method.response.header.Cache-Control': true,
+            'method.response.header.ETag': true,
+          },
+        },
+        ...tileMethodResponses,
+      ]),
+    });
+
+    // dog tile endpoints
+    // /dog
+    const dog = root.addResource('dog');
+    // /dog/{dogId}
+    const dogId = dog.addResource('{dogId}');
+    // /dog/{dogId}/tile/{z}/{x}/{y}/tile.mvt
+    const dogTileMvt = ['tile', '{z}', '{x}', '{y}', 'tile.mvt'].reduce(
+      (resource, part) => resource.addResource(part),
+      dogId,
+    );
+    // - GET
+    dogTileMvt.addMethod(
       'GET',
-      new apigw.LambdaIntegration(this.getTileLambda, {
+      new apigw.LambdaIntegration(this.getDogTileLambda, {
         proxy: false,
         passthroughBehavior: apigw.PassthroughBehavior.NEVER,
         requestTemplates: {
           'application/json': composeMappingTemplate([
+            ['userId', '"$context.authorizer.claims["cognito:username"]"'],
+            ['dogId', `"$util.escapeJavaScript($input.params("dogId")).replaceAll("\\'","'")"`],
             // zoom, x, y should be numbers
             ['zoom', '$util.escapeJavaScript($input.params("z"))'],
             ['x', '$util.escapeJavaScript($input.params("x"))'],
             ['y', '$util.escapeJavaScript($input.params("y"))'],
+            // optional filters: since, until should be numbers
+            ifThen(
+              '$input.params("since") != ""',
+              [['since', '$util.escapeJavaScript($input.params("since"))']],
+            ),
+            ifThen(
+              '$input.params("until") != ""',
+              [['until', '$util.escapeJavaScript($input.params("until"))']],
+            ),
+            ifThen(
+              '$input.params("businessType") != ""',
+              [['businessType', '"$util.escapeJavaScript($input.params("businessType"))"']],
+            ),
           ]),
         },
         integrationResponses: makeIntegrationResponsesAllowCors([
@@ -171,10 +306,28 @@ export class MapApi extends Construct {
               'method.response.header.Content-Type': "'application/vnd.mapbox-vector-tile'",
             },
           },
+          {
+            // the Lambda function fails to deserialize the request
+            statusCode: '400',
+            selectionPattern: '[\\s\\S]*invalid tile coordinates[\\s\\S]*',
+            responseTemplates: {
+              'application/json': '{"message": "invalid tile coordinates"}',
+            },
+          },
+          {
+            // `since` is later than `until`
+            statusCode: '400',
+            selectionPattern: '[\\s\\S]*invalid time window[\\s\\S]*',
+            responseTemplates: {
+              'application/json': '{"message": "invalid time window"}',
+            },
+          },
         ]),
       }),
       {
-        description: 'Obtain a map tile at a given zoom level, x, and y coordinates',
+        description: 'Obtain a private map tile of the dog friend identified by a given ID token',
+        authorizer,
+        authorizationType: apigw.AuthorizationType.COGNITO,
         methodResponses: makeMethodResponsesAllowCors([
           {
             statusCode: '200',
@@ -183,6 +336,10 @@ export class MapApi extends Construct {
               'method.response.header.Content-Type': true,
             },
           },
+          {
+            statusCode: '400',
+            description: 'Invalid tile coordinates or time window',
+          },
         ]),
       },
     );
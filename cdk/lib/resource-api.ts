@@ -15,10 +15,12 @@ import { RestApiWithSpec, augmentAuthorizer } from '@codemonger-io/cdk-rest-api-
 import type { KeyValue } from '@codemonger-io/mapping-template-compose';
 import { composeMappingTemplate, ifThen } from '@codemonger-io/mapping-template-compose';
 
+import { AdvocacyPropagation } from './advocacy-propagation';
 import type { BusinessRecordTable } from './business-record-table';
 import { DOG_INDEX_NAME } from './business-record-table';
 import type { ResourceTable } from './resource-table';
 import type { SsmParameters } from './ssm-parameters';
+import type { TileCacheBucket } from './tile-cache-bucket';
 
 /**
  * Props for {@link ResourceApi}.
@@ -44,6 +46,9 @@ export interface ResourceApiProps {
   /** Business record table. */
   readonly businessRecordTable: BusinessRecordTable;
 
+  /** S3 bucket that caches rendered map tiles. */
+  readonly tileCacheBucket: TileCacheBucket;
+
   /** User pool for authentication. */
   readonly userPool: cognito.UserPool;
 
@@ -66,12 +71,27 @@ export class ResourceApi extends Construct {
   /** Lambda function to get a dog friend. */
   readonly getDogLambda: lambda.IFunction;
 
+  /** Lambda function to update a dog friend. */
+  readonly updateDogLambda: lambda.IFunction;
+
   /** Lambda function to create a business record. */
   readonly createBusinessRecordLambda: lambda.IFunction;
 
+  /** Lambda function to update a business record. */
+  readonly updateBusinessRecordLambda: lambda.IFunction;
+
+  /** Lambda function to delete a business record. */
+  readonly deleteBusinessRecordLambda: lambda.IFunction;
+
   /** Lambda function to get business records. */
   readonly getBusinessRecordsLambda: lambda.IFunction;
 
+  /**
+   * Background job that propagates the advocacy setting of a dog to its
+   * existing business records.
+   */
+  readonly advocacyPropagation: AdvocacyPropagation;
+
   /** API Gateway REST API. */
   readonly api: RestApiWithSpec;
 
@@ -84,6 +104,7 @@ export class ResourceApi extends Construct {
       businessRecordTable,
       ssmParameters,
       resourceTable,
+      tileCacheBucket,
       userPool,
     } = props;
     const manifestPath = path.join('lambda', 'resource-api', 'Cargo.toml');
@@ -125,6 +146,18 @@ export class ResourceApi extends Construct {
       },
     });
     resourceTable.table.grantReadData(this.getDogLambda);
+    // - update dog
+    this.updateDogLambda = new RustFunction(this, 'UpdateDogLambda', {
+      manifestPath,
+      binaryName: 'update-dog',
+      architecture: lambda.Architecture.ARM_64,
+      memorySize: 128,
+      timeout: Duration.seconds(5),
+      environment: {
+        RESOURCE_TABLE_NAME: resourceTable.table.tableName,
+      },
+    });
+    resourceTable.table.grantReadWriteData(this.updateDogLambda);
     // - create business record
     this.createBusinessRecordLambda = new RustFunction(this, 'CreateBusinessRecordLambda', {
       manifestPath,
@@ -135,10 +168,44 @@ export class ResourceApi extends Construct {
       environment: {
         RESOURCE_TABLE_NAME: resourceTable.table.tableName,
         BUSINESS_RECORD_TABLE_NAME: businessRecordTable.table.tableName,
+        TILE_CACHE_BUCKET_NAME: tileCacheBucket.bucket.bucketName,
       },
     });
     resourceTable.table.grantReadData(this.createBusinessRecordLambda);
     businessRecordTable.table.grantReadWriteData(this.createBusinessRecordLambda);
+    tileCacheBucket.grantInvalidate(this.createBusinessRecordLambda);
+    // - update business record
+    this.updateBusinessRecordLambda = new RustFunction(this, 'UpdateBusinessRecordLambda', {
+      manifestPath,
+      binaryName: 'update-business-record',
+      architecture: lambda.Architecture.ARM_64,
+      memorySize: 128,
+      timeout: Duration.seconds(5),
+      environment: {
+        RESOURCE_TABLE_NAME: resourceTable.table.tableName,
+        BUSINESS_RECORD_TABLE_NAME: businessRecordTable.table.tableName,
+        TILE_CACHE_BUCKET_NAME: tileCacheBucket.bucket.bucketName,
+      },
+    });
+    resourceTable.table.grantReadData(this.updateBusinessRecordLambda);
+    businessRecordTable.table.grantReadWriteData(this.updateBusinessRecordLambda);
+    tileCacheBucket.grantInvalidate(this.updateBusinessRecordLambda);
+    // - delete business record
+    this.deleteBusinessRecordLambda = new RustFunction(this, 'DeleteBusinessRecordLambda', {
+      manifestPath,
+      binaryName: 'delete-business-record',
+      architecture: lambda.Architecture.ARM_64,
+      memorySize: 128,
+      timeout: Duration.seconds(5),
+      environment: {
+        RESOURCE_TABLE_NAME: resourceTable.table.tableName,
+        BUSINESS_RECORD_TABLE_NAME: businessRecordTable.table.tableName,
+        TILE_CACHE_BUCKET_NAME: tileCacheBucket.bucket.bucketName,
+      },
+    });
+    resourceTable.table.grantReadData(this.deleteBusinessRecordLambda);
+    businessRecordTable.table.grantReadWriteData(this.deleteBusinessRecordLambda);
+    tileCacheBucket.grantInvalidate(this.deleteBusinessRecordLambda);
     // - get business records
     this.getBusinessRecordsLambda = new RustFunction(this, 'GetBusinessRecordsLambda', {
       manifestPath,
@@ -155,6 +222,15 @@ export class ResourceApi extends Construct {
     resourceTable.table.grantReadData(this.getBusinessRecordsLambda);
     businessRecordTable.table.grantReadData(this.getBusinessRecordsLambda);
 
+    // background job
+    // - propagate advocacy setting
+    this.advocacyPropagation = new AdvocacyPropagation(this, 'AdvocacyPropagation', {
+      manifestPath,
+      resourceTable,
+      businessRecordTable,
+      tileCacheBucket,
+    });
+
     // REST API
     this.api = new RestApiWithSpec(this, 'ResourceApi', {
       description: "Dog's Business Resource API",
@@ -164,7 +240,7 @@ export class ResourceApi extends Construct {
       openApiOutputPath: path.join('openapi', 'resource-api.json'),
       defaultCorsPreflightOptions: allowOrigins.length > 0 ? {
         allowHeaders: ['Authorization', 'Content-Type'],
-        allowMethods: ['GET', 'POST'],
+        allowMethods: ['DELETE', 'GET', 'PATCH', 'POST'],
         allowOrigins,
         maxAge: Duration.days(1),
       } : undefined,
@@ -219,6 +295,7 @@ export class ResourceApi extends Construct {
     const mappingTemplateParts = {
       userId: ['userId', '"$context.authorizer.claims["cognito:username"]"'] as KeyValue,
       dogIdSegment: ['dogId', `"$util.escapeJavaScript($input.params("dogId")).replaceAll("\\'","'")"`] as KeyValue,
+      recordIdSegment: ['recordId', `"$util.escapeJavaScript($input.params("recordId")).replaceAll("\\'","'")"`] as KeyValue,
     };
 
     // gets to the base path
@@ -328,6 +405,37 @@ export class ResourceApi extends Construct {
         ]),
       },
     );
+    // - PATCH
+    dogId.addMethod(
+      'PATCH',
+      new apigw.LambdaIntegration(this.updateDogLambda, {
+        proxy: false,
+        passthroughBehavior: apigw.PassthroughBehavior.NEVER,
+        requestTemplates: {
+          'application/json': composeMappingTemplate([
+            mappingTemplateParts.userId,
+            mappingTemplateParts.dogIdSegment,
+            ['advocacySetting', '$input.json("$.advocacySetting")'],
+          ]),
+        },
+        integrationResponses: makeIntegrationResponsesAllowCors([
+          {
+            statusCode: '200',
+          },
+        ]),
+      }),
+      {
+        description: 'Update the settings of the dog friend identified by a given ID for the user associated with the ID token, who must be a guardian of the dog',
+        authorizer,
+        authorizationType: apigw.AuthorizationType.COGNITO,
+        methodResponses: makeMethodResponsesAllowCors([
+          {
+            statusCode: '200',
+            description: 'Dog friend has successfully been updated',
+          },
+        ]),
+      },
+    );
     // /dog/{dogId}/business-record
     const businessRecord = dogId.addResource('business-record');
     // - POST
@@ -362,6 +470,79 @@ export class ResourceApi extends Construct {
         ]),
       },
     );
+    // /dog/{dogId}/business-record/{recordId}
+    const businessRecordId = businessRecord.addResource('{recordId}');
+    // - PATCH
+    businessRecordId.addMethod(
+      'PATCH',
+      new apigw.LambdaIntegration(this.updateBusinessRecordLambda, {
+        proxy: false,
+        passthroughBehavior: apigw.PassthroughBehavior.NEVER,
+        requestTemplates: {
+          'application/json': composeMappingTemplate([
+            mappingTemplateParts.userId,
+            mappingTemplateParts.dogIdSegment,
+            mappingTemplateParts.recordIdSegment,
+            // optional updates
+            ifThen(
+              '$input.path("$.businessType") != ""',
+              [['businessType', '$input.json("$.businessType")']],
+            ),
+            ifThen(
+              '$input.path("$.location") != ""',
+              [['location', '$input.json("$.location")']],
+            ),
+          ]),
+        },
+        integrationResponses: makeIntegrationResponsesAllowCors([
+          {
+            statusCode: '200',
+          },
+        ]),
+      }),
+      {
+        description: 'Update the business type or location of a business record carried out by the dog friend identified by a given ID',
+        authorizer,
+        authorizationType: apigw.AuthorizationType.COGNITO,
+        methodResponses: makeMethodResponsesAllowCors([
+          {
+            statusCode: '200',
+            description: 'Business record has successfully been updated',
+          },
+        ]),
+      },
+    );
+    // - DELETE
+    businessRecordId.addMethod(
+      'DELETE',
+      new apigw.LambdaIntegration(this.deleteBusinessRecordLambda, {
+        proxy: false,
+        passthroughBehavior: apigw.PassthroughBehavior.NEVER,
+        requestTemplates: {
+          'application/json': composeMappingTemplate([
+            mappingTemplateParts.userId,
+            mappingTemplateParts.dogIdSegment,
+            mappingTemplateParts.recordIdSegment,
+          ]),
+        },
+        integrationResponses: makeIntegrationResponsesAllowCors([
+          {
+            statusCode: '200',
+          },
+        ]),
+      }),
+      {
+        description: 'Delete a business record carried out by the dog friend identified by a given ID',
+        authorizer,
+        authorizationType: apigw.AuthorizationType.COGNITO,
+        methodResponses: makeMethodResponsesAllowCors([
+          {
+            statusCode: '200',
+            description: 'Business record has successfully been deleted',
+          },
+        ]),
+      },
+    );
 
     // /dog/{dogId}/business-records
     const businessRecords = dogId.addResource('business-records');
@@ -375,6 +556,15 @@ export class ResourceApi extends Construct {
           'application/json': composeMappingTemplate([
             mappingTemplateParts.userId,
             mappingTemplateParts.dogIdSegment,
+            // optional pagination: pageSize should be a number
+            ifThen(
+              '$input.params("pageSize") != ""',
+              [['pageSize', '$util.escapeJavaScript($input.params("pageSize"))']],
+            ),
+            ifThen(
+              '$input.params("nextToken") != ""',
+              [['nextToken', '"$util.escapeJavaScript($input.params("nextToken"))"']],
+            ),
           ]),
         },
         integrationResponses: makeIntegrationResponsesAllowCors([
@@ -384,7 +574,7 @@ export class ResourceApi extends Construct {
         ]),
       }),
       {
-        description: 'Obtain the business records carried out by the dog friend identified by a given ID token',
+        description: 'Obtain a page of the business records carried out by the dog friend identified by a given ID token',
         authorizer,
         authorizationType: apigw.AuthorizationType.COGNITO,
         methodResponses: makeMethodResponsesAllowCors([
@@ -48,6 +48,14 @@ export class ResourceTable extends Construct {
    *   - `dogId`: unique dog ID
    * - `sk`: "info"
    * - `name`: (string) dog name
+   * - `advocacySetting`: (string) how business records of the dog appear on
+   *   the public map. "publish-anonymized" if missing.
+   *   - "publish": published as an advocate of the app. visible at any
+   *     anonymity level.
+   *   - "publish-anonymized": published but visible only if the anonymity
+   *     level is high enough.
+   *   - "do-not-publish": never published. no public business record is
+   *     created.
    * - `createdAt`: (timestamp) time of creation
    * - `updatedAt`: (timestamp) time of last update
    *
@@ -72,6 +80,9 @@ export class ResourceTable extends Construct {
         name: 'sk',
         type: dynamodb.AttributeType.STRING,
       },
+      // changes of the advocacy setting of dogs trigger the propagation.
+      // old and new images are necessary to detect the changes
+      dynamoStream: dynamodb.StreamViewType.NEW_AND_OLD_IMAGES,
       // TODO: increase the caps for production
       billing: dynamodb.Billing.onDemand({
         maxReadRequestUnits: 2,
@@ -0,0 +1,91 @@
+import {
+  Duration,
+  RemovalPolicy,
+  aws_iam as iam,
+  aws_s3 as s3,
+} from 'aws-cdk-lib';
+import { Construct } from 'constructs';
+
+import type { DeploymentStage } from './deployment-stage';
+
+/**
+ * Number of days to keep a cached tile.
+ *
+ * @remarks
+ *
+ * Only reclaims storage. The Lambda functions ignore cached tiles older than
+ * 15 minutes (`MAX_TILE_AGE_MILLIS` in `business_core::tile_cache`), while
+ * one day is the shortest expiration of S3 lifecycle rules.
+ */
+export const TILE_CACHE_EXPIRATION_DAYS = 1;
+
+/**
+ * Properties for {@link TileCacheBucket}.
+ *
+ * @beta
+ */
+export interface TileCacheBucketProps {
+  /** Deployment stage. */
+  readonly deploymentStage: DeploymentStage;
+}
+
+/**
+ * CDK construct which provisions an S3 bucket that caches rendered map tiles.
+ *
+ * @beta
+ */
+export class TileCacheBucket extends Construct {
+  /**
+   * S3 bucket that caches rendered map tiles.
+   *
+   * @remarks
+   *
+   * ## Bucket structure
+   *
+   * - `tiles/{z}/{x}/{y}/{variant}`: rendered map tile
+   *   - `z`: zoom level of the tile
+   *   - `x`: x coordinate of the tile
+   *   - `y`: y coordinate of the tile
+   *   - `variant`: distinguishes representations of the same tile; e.g.,
+   *     filters, format, and content encoding.
+   *   - `tile-etag` metadata: ETag of the tile
+   *   - `tile-rendered-at` metadata: time when the tile started rendering
+   * - `tiles/{z}/{x}/{y}/invalidated`: invalidation marker of a tile
+   *   - `tile-invalidated-at` metadata: time of the last invalidation
+   *
+   * A tile is invalidated when a business record in the tile is created,
+   * updated, or deleted, or when a change of the advocacy setting of a dog
+   * updates a business record in the tile. Invalidating a tile overwrites
+   * the invalidation marker instead of deleting the variants of the tile,
+   * and variants that started rendering before the invalidation are ignored.
+   * Since every object is just a cache, the bucket is destroyed with the
+   * stack.
+   */
+  readonly bucket: s3.Bucket;
+
+  constructor(scope: Construct, id: string, props: TileCacheBucketProps) {
+    super(scope, id);
+
+    this.bucket = new s3.Bucket(this, 'TileCacheBucket', {
+      blockPublicAccess: s3.BlockPublicAccess.BLOCK_ALL,
+      encryption: s3.BucketEncryption.S3_MANAGED,
+      lifecycleRules: [
+        {
+          expiration: Duration.days(TILE_CACHE_EXPIRATION_DAYS),
+        },
+      ],
+      removalPolicy: RemovalPolicy.DESTROY,
+      autoDeleteObjects: true,
+    });
+  }
+
+  /** Grants a given principal permissions to read and write cached tiles. */
+  grantReadWrite(grantee: iam.IGrantable): iam.Grant {
+    return this.bucket.grantReadWrite(grantee);
+  }
+
+  /** Grants a given principal permissions to invalidate cached tiles. */
+  grantInvalidate(grantee: iam.IGrantable): iam.Grant {
+    return this.bucket.grantPut(grantee, 'tiles/*/invalidated');
+  }
+}
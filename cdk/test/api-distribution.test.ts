@@ -0,0 +1,75 @@
+import { App, Stack, aws_apigateway as apigw } from 'aws-cdk-lib'
+import { Match, Template } from 'aws-cdk-lib/assertions'
+
+import { ApiDistribution } from '../lib/api-distribution'
+import type { MapApi } from '../lib/map-api'
+import type { ResourceApi } from '../lib/resource-api'
+
+// minimal stand-in for the APIs, which otherwise build Rust Lambda functions
+function makeApi(stack: Stack, id: string, basePath: string) {
+  const api = new apigw.RestApi(stack, id)
+  api.root.addMethod('GET', new apigw.MockIntegration())
+  return { api, basePath }
+}
+
+function synthesize(): Template {
+  const stack = new Stack(new App(), 'TestStack')
+  new ApiDistribution(stack, 'ApiDistribution', {
+    deploymentStage: 'development',
+    resourceApi: makeApi(stack, 'ResourceApi', '/dogs-business-api/resource') as unknown as ResourceApi,
+    mapApi: makeApi(stack, 'MapApi', '/dogs-business-api/map') as unknown as MapApi,
+  })
+  return Template.fromStack(stack)
+}
+
+describe('ApiDistribution', () => {
+  const template = synthesize()
+
+  function findCachePolicyId(comment: RegExp): string {
+    const policies = template.findResources('AWS::CloudFront::CachePolicy')
+    const ids = Object.keys(policies).filter((id) => {
+      return comment.test(policies[id].Properties.CachePolicyConfig.Comment)
+    })
+    expect(ids).toHaveLength(1)
+    return ids[0]
+  }
+
+  function getCacheBehaviors(): any[] {
+    const distributions = template.findResources('AWS::CloudFront::Distribution')
+    const [distribution] = Object.values(distributions)
+    return distribution.Properties.DistributionConfig.CacheBehaviors
+  }
+
+  it('should route private dog tiles before the catch-all of the Map API', () => {
+    const paths = getCacheBehaviors().map((behavior) => behavior.PathPattern)
+    expect(paths).toEqual([
+      '/dogs-business-api/map/dog/*',
+      '/dogs-business-api/map/*',
+    ])
+  })
+
+  it('should include Authorization in the cache key of private dog tiles', () => {
+    const policyId = findCachePolicyId(/private dog tiles/)
+    const [dogBehavior] = getCacheBehaviors()
+    expect(dogBehavior.CachePolicyId).toEqual({ Ref: policyId })
+    template.hasResourceProperties('AWS::CloudFront::CachePolicy', {
+      CachePolicyConfig: {
+        Comment: Match.stringLikeRegexp('private dog tiles'),
+        DefaultTTL: 0,
+        MinTTL: 0,
+        ParametersInCacheKeyAndForwardedToOrigin: {
+          HeadersConfig: {
+            HeaderBehavior: 'whitelist',
+            Headers: ['Authorization'],
+          },
+        },
+      },
+    })
+  })
+
+  it('should apply the Map API cache policy to the other Map API requests', () => {
+    const policyId = findCachePolicyId(/^cache policy for the Dog's Business Map API$/)
+    const [, mapBehavior] = getCacheBehaviors()
+    expect(mapBehavior.CachePolicyId).toEqual({ Ref: policyId })
+  })
+})
@@ -48,12 +48,34 @@ export class OnlineBusinessRecordDatabaseImpl implements OnlineBusinessRecordDat
     }
   }
 
-  /** Loads business records of a given dog from the database. */
+  /**
+   * Loads business records of a given dog from the database.
+   *
+   * @remarks
+   *
+   * Follows the pages until all the business records are loaded.
+   */
   async loadBusinessRecords(dogId: string): Promise<BusinessRecord<string, string>[]> {
     if (process.env.NODE_ENV !== 'production') {
       console.log('OnlineBusinessRecordDatabaseImpl.loadBusinessRecords', dogId)
     }
-    const url = `${import.meta.env.VITE_DOGS_BUSINESS_RESOURCE_API_BASE_URL}/dog/${dogId}/business-records`
+    const records: BusinessRecord<string, string>[] = []
+    let nextToken: string | undefined = undefined
+    do {
+      const page = await this.loadBusinessRecordsPage(dogId, nextToken)
+      records.push(...page.businessRecords)
+      nextToken = page.nextToken
+    } while (nextToken != null)
+    return records
+  }
+
+  // loads a page of business records of a given dog.
+  private async loadBusinessRecordsPage(
+    dogId: string,
+    nextToken?: string
+  ): Promise<BusinessRecordsPage> {
+    const query = nextToken != null ? `?nextToken=${encodeURIComponent(nextToken)}` : ''
+    const url = `${import.meta.env.VITE_DOGS_BUSINESS_RESOURCE_API_BASE_URL}/dog/${dogId}/business-records${query}`
     const res = await fetch(url, {
       method: 'GET',
       headers: {
@@ -61,24 +83,45 @@ export class OnlineBusinessRecordDatabaseImpl implements OnlineBusinessRecordDat
       }
     })
     if (res.ok) {
-      const records = await res.json()
-      if (!Array.isArray(records)) {
+      const page = await res.json()
+      if (!isBusinessRecordsPage(page)) {
         throw new Error('invalid business records response from server')
       }
-      if (!records.every(isOnlineRecord)) {
-        throw new Error('invalid business records response from server')
-      }
-      return records
+      return page
     } else {
       if (res.status === 401) {
         this.accountProvider.handleUnauthorized()
       }
       const message = await res.text()
-      throw new Error(`failed to create business record: ${res.status} ${message}`)
+      throw new Error(`failed to load business records: ${res.status} ${message}`)
     }
   }
 }
 
+// page of business records returned from the server.
+interface BusinessRecordsPage {
+  businessRecords: BusinessRecord<string, string>[]
+  nextToken?: string
+}
+
+// returns if a given value is a `BusinessRecordsPage`.
+function isBusinessRecordsPage(value: unknown): value is BusinessRecordsPage {
+  if (value == null || typeof value !== 'object') {
+    return false
+  }
+  const maybePage = value as BusinessRecordsPage
+  if (!Array.isArray(maybePage.businessRecords)) {
+    return false
+  }
+  if (!maybePage.businessRecords.every(isOnlineRecord)) {
+    return false
+  }
+  if (maybePage.nextToken != null && typeof maybePage.nextToken !== 'string') {
+    return false
+  }
+  return true
+}
+
 // returns if a given value is a `BusinessRecord<string, string>`.
 function isOnlineRecord(value: unknown): value is BusinessRecord<string, string> {
   if (!isBusinessRecord(value)) {